@@ -0,0 +1,43 @@
+#version 330 core
+
+layout(location = 0) out vec4 color;
+
+in vec4 v_Color;
+in vec2 v_TexCoord;
+flat in float v_TexIndex;
+flat in float v_Layer;
+
+//LAYERED batches sample texture arrays at the layer of the sprite
+#if defined(LAYERED)
+uniform sampler2DArray u_Textures[16];
+#define TEX_COORD vec3(v_TexCoord, v_Layer)
+#elif !defined(UNTEXTURED)
+uniform sampler2D u_Textures[16];
+#define TEX_COORD v_TexCoord
+#endif
+
+void main() {
+	vec4 texColor = vec4(1.0);
+#ifndef UNTEXTURED
+	//indexing a sampler array with a non constant is undefined in 330 so go through a switch
+	switch (int(v_TexIndex)) {
+		case 0: texColor = texture(u_Textures[0], TEX_COORD); break;
+		case 1: texColor = texture(u_Textures[1], TEX_COORD); break;
+		case 2: texColor = texture(u_Textures[2], TEX_COORD); break;
+		case 3: texColor = texture(u_Textures[3], TEX_COORD); break;
+		case 4: texColor = texture(u_Textures[4], TEX_COORD); break;
+		case 5: texColor = texture(u_Textures[5], TEX_COORD); break;
+		case 6: texColor = texture(u_Textures[6], TEX_COORD); break;
+		case 7: texColor = texture(u_Textures[7], TEX_COORD); break;
+		case 8: texColor = texture(u_Textures[8], TEX_COORD); break;
+		case 9: texColor = texture(u_Textures[9], TEX_COORD); break;
+		case 10: texColor = texture(u_Textures[10], TEX_COORD); break;
+		case 11: texColor = texture(u_Textures[11], TEX_COORD); break;
+		case 12: texColor = texture(u_Textures[12], TEX_COORD); break;
+		case 13: texColor = texture(u_Textures[13], TEX_COORD); break;
+		case 14: texColor = texture(u_Textures[14], TEX_COORD); break;
+		case 15: texColor = texture(u_Textures[15], TEX_COORD); break;
+	}
+#endif
+	color = texColor * v_Color;
+}
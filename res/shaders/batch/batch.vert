@@ -0,0 +1,22 @@
+#version 330 core
+
+#include "../include/camera.glsl"
+
+layout(location = 0) in vec2 a_Position;
+layout(location = 1) in vec4 a_Color;
+layout(location = 2) in vec2 a_TexCoord;
+layout(location = 3) in float a_TexIndex;
+layout(location = 4) in float a_Layer;
+
+out vec4 v_Color;
+out vec2 v_TexCoord;
+flat out float v_TexIndex;
+flat out float v_Layer;
+
+void main() {
+	gl_Position = camera.viewProjection * vec4(a_Position, 0.0, 1.0);
+	v_Color = a_Color;
+	v_TexCoord = a_TexCoord;
+	v_TexIndex = a_TexIndex;
+	v_Layer = a_Layer;
+}
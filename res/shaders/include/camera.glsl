@@ -0,0 +1,11 @@
+//per frame data shared by every shader, filled by CameraData in uniform_buffer.rs
+//the block is attached to binding 0 when the shader is linked
+#pragma once
+
+layout(std140) uniform Camera {
+	mat4 view;
+	mat4 projection;
+	mat4 viewProjection;
+	vec2 resolution;
+	float time;
+} camera;
@@ -0,0 +1,15 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+uniform float u_Intensity;
+
+void main() {
+	vec3 scene = texture(u_Scene, v_TexCoord).rgb;
+	vec3 glow = texture(u_Source, v_TexCoord).rgb;
+	color = vec4(scene + glow * u_Intensity, 1.0);
+}
@@ -0,0 +1,18 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//brightness above which pixels start to glow
+uniform float u_Threshold;
+
+void main() {
+	vec3 source = texture(u_Source, v_TexCoord).rgb;
+	float brightness = max(source.r, max(source.g, source.b));
+	//a soft knee so the glow doesn't pop in
+	float contribution = smoothstep(u_Threshold, u_Threshold + 0.1, brightness);
+	color = vec4(source * contribution, 1.0);
+}
@@ -0,0 +1,24 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//(1, 0) blurs horizontally and (0, 1) vertically, run both for a full gaussian blur
+uniform vec2 u_Direction;
+//distance between taps in pixels
+uniform float u_Radius;
+
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+	vec2 step = u_Direction * u_Radius / u_Resolution;
+	vec3 sum = texture(u_Source, v_TexCoord).rgb * weights[0];
+	for (int i = 1; i < 5; i++) {
+		sum += texture(u_Source, v_TexCoord + step * float(i)).rgb * weights[i];
+		sum += texture(u_Source, v_TexCoord - step * float(i)).rgb * weights[i];
+	}
+	color = vec4(sum, 1.0);
+}
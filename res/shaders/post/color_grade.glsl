@@ -0,0 +1,32 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//a strip of u_LutSize slices, each u_LutSize pixels square. inside a slice red goes right and
+//green goes down, blue picks the slice
+uniform sampler2D u_Lut;
+uniform float u_LutSize;
+//0 keeps the colors, 1 is fully graded
+uniform float u_Intensity;
+
+vec3 lookup(vec3 source) {
+	float size = u_LutSize;
+	float blue = clamp(source.b, 0.0, 1.0) * (size - 1.0);
+	float lower = floor(blue);
+	float upper = min(lower + 1.0, size - 1.0);
+	vec2 cell = (clamp(source.rg, 0.0, 1.0) * (size - 1.0) + 0.5) / vec2(size * size, size);
+	//textures are flipped when loaded, so the top row of the image is at v = 1
+	cell.y = 1.0 - cell.y;
+	vec3 a = texture(u_Lut, cell + vec2(lower / size, 0.0)).rgb;
+	vec3 b = texture(u_Lut, cell + vec2(upper / size, 0.0)).rgb;
+	return mix(a, b, blue - lower);
+}
+
+void main() {
+	vec3 source = texture(u_Source, v_TexCoord).rgb;
+	color = vec4(mix(source, lookup(source), u_Intensity), 1.0);
+}
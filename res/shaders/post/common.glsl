@@ -0,0 +1,14 @@
+//inputs every post process pass gets from PostProcessStack in post_process.rs
+//passes should write an alpha of 1 so blending doesn't mix them with what is below
+#pragma once
+
+in vec2 v_TexCoord;
+
+layout(location = 0) out vec4 color;
+
+//output of the previous pass, or the scene for the first one
+uniform sampler2D u_Source;
+//everything drawn between begin and end, before any pass ran
+uniform sampler2D u_Scene;
+//size of u_Source in pixels
+uniform vec2 u_Resolution;
@@ -0,0 +1,29 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//how much the screen bulges, 0 is flat
+uniform float u_Curvature;
+//how dark the gaps between scanlines are, 0 hides them
+uniform float u_ScanlineStrength;
+
+void main() {
+	//push the corners outwards like the glass of an old tube
+	vec2 centered = v_TexCoord * 2.0 - 1.0;
+	centered *= 1.0 + u_Curvature * dot(centered.yx, centered.yx);
+	vec2 uv = centered * 0.5 + 0.5;
+	if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+		color = vec4(0.0, 0.0, 0.0, 1.0);
+		return;
+	}
+
+	vec3 source = texture(u_Source, uv).rgb;
+	//one dark line every other pixel row
+	float scanline = 0.5 + 0.5 * cos(uv.y * u_Resolution.y * 3.14159265);
+	source *= 1.0 - u_ScanlineStrength * scanline;
+	color = vec4(source, 1.0);
+}
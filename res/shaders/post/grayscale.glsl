@@ -0,0 +1,16 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//0 keeps the colors, 1 is fully gray
+uniform float u_Strength;
+
+void main() {
+	vec3 source = texture(u_Source, v_TexCoord).rgb;
+	float luma = dot(source, vec3(0.2126, 0.7152, 0.0722));
+	color = vec4(mix(source, vec3(luma), u_Strength), 1.0);
+}
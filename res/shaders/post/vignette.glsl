@@ -0,0 +1,20 @@
+#shader vertex
+#version 330 core
+#include "fullscreen.glsl"
+
+#shader fragment
+#version 330 core
+#include "common.glsl"
+
+//distance from the center where darkening starts, 0.5 reaches the edges
+uniform float u_Radius;
+//width of the fade to black
+uniform float u_Softness;
+
+void main() {
+	vec3 source = texture(u_Source, v_TexCoord).rgb;
+	//keep the falloff round on wide screens
+	vec2 offset = (v_TexCoord - 0.5) * vec2(u_Resolution.x / u_Resolution.y, 1.0);
+	float vignette = smoothstep(u_Radius, u_Radius - u_Softness, length(offset));
+	color = vec4(source * vignette, 1.0);
+}
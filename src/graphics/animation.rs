@@ -0,0 +1,243 @@
+use std::time::Duration;
+
+/// what happens when an animation reaches its last frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    /// starts over at the first frame
+    Loop,
+    /// plays backwards to the first frame, then forwards again
+    PingPong,
+    /// stops on the last frame
+    Once,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationEvent {
+    /// a loop or ping-pong animation finished a cycle and started the next one
+    Looped,
+    /// a one-shot animation reached its last frame
+    Finished,
+}
+
+/// plays a list of sprite sheet frames, advanced with `update(fps_counter.time_delta)`
+///
+/// ```ignore
+/// let mut walk = Animation::new(&[0, 1, 2, 3], Duration::from_millis(100), PlayMode::Loop);
+/// walk.update(fps_counter.time_delta);
+/// renderer_2d.draw_sprite(&sheet.sprite(walk.get_frame(), position, size));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// (sprite sheet frame, how long it's shown)
+    frames: Vec<(usize, Duration)>,
+    mode: PlayMode,
+    speed: f32,
+    /// index into `frames`
+    current: usize,
+    /// time spent on the current frame
+    elapsed: Duration,
+    backwards: bool,
+    playing: bool,
+    finished: bool,
+}
+
+impl Animation {
+    /// shows every frame for the same `frame_duration`
+    pub fn new(frames: &[usize], frame_duration: Duration, mode: PlayMode) -> Animation {
+        let frames: Vec<_> = frames
+            .iter()
+            .map(|&frame| (frame, frame_duration))
+            .collect();
+        Self::with_durations(&frames, mode)
+    }
+
+    /// (sprite sheet frame, duration) pairs for animations that hold some frames longer
+    pub fn with_durations(frames: &[(usize, Duration)], mode: PlayMode) -> Animation {
+        assert!(!frames.is_empty(), "an animation needs at least one frame");
+        assert!(
+            frames.iter().all(|(_, duration)| !duration.is_zero()),
+            "animation frames need a duration above zero"
+        );
+        Animation {
+            frames: frames.to_vec(),
+            mode,
+            speed: 1.0,
+            current: 0,
+            elapsed: Duration::ZERO,
+            backwards: false,
+            playing: true,
+            finished: false,
+        }
+    }
+
+    /// advances by `time_delta` scaled by the speed, returns the loops and finishes that happened
+    pub fn update(&mut self, time_delta: Duration) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        if !self.playing || self.finished {
+            return events;
+        }
+
+        self.elapsed += time_delta.mul_f64(self.speed as f64);
+        while self.elapsed >= self.frames[self.current].1 {
+            self.elapsed -= self.frames[self.current].1;
+            if let Some(event) = self.advance() {
+                events.push(event);
+            }
+            if self.finished {
+                self.elapsed = Duration::ZERO;
+                break;
+            }
+        }
+        events
+    }
+
+    /// moves to the next frame in play order
+    fn advance(&mut self) -> Option<AnimationEvent> {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            PlayMode::Loop => {
+                if self.current == last {
+                    self.current = 0;
+                    return Some(AnimationEvent::Looped);
+                }
+                self.current += 1;
+            }
+            //a single frame has nothing to bounce between
+            PlayMode::PingPong if last == 0 => return Some(AnimationEvent::Looped),
+            PlayMode::PingPong => {
+                if self.backwards && self.current == 0 {
+                    self.backwards = false;
+                    self.current = 1;
+                    return Some(AnimationEvent::Looped);
+                }
+                if !self.backwards && self.current == last {
+                    self.backwards = true;
+                }
+                if self.backwards {
+                    self.current -= 1;
+                } else {
+                    self.current += 1;
+                }
+            }
+            PlayMode::Once => {
+                if self.current == last {
+                    self.finished = true;
+                    return Some(AnimationEvent::Finished);
+                }
+                self.current += 1;
+            }
+        }
+        None
+    }
+
+    /// the sprite sheet frame to draw
+    pub fn get_frame(&self) -> usize {
+        self.frames[self.current].0
+    }
+
+    /// playback speed, 2 plays twice as fast
+    pub fn set_speed(&mut self, speed: f32) {
+        assert!(
+            speed.is_finite(),
+            "animation speed has to be finite, got {}",
+            speed
+        );
+        assert!(
+            speed >= 0.0,
+            "animations can't play backwards, use PlayMode::PingPong"
+        );
+        self.speed = speed;
+    }
+
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// back to the first frame, also replays finished one-shot animations
+    pub fn restart(&mut self) {
+        self.current = 0;
+        self.elapsed = Duration::ZERO;
+        self.backwards = false;
+        self.finished = false;
+        self.playing = true;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing && !self.finished
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(100);
+
+    /// the frame after each `FRAME` step
+    fn frames(animation: &mut Animation, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.update(FRAME);
+                animation.get_frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn play_modes_pick_the_next_frame() {
+        let mut looping = Animation::new(&[0, 1, 2], FRAME, PlayMode::Loop);
+        assert_eq!(frames(&mut looping, 5), [1, 2, 0, 1, 2]);
+
+        let mut ping_pong = Animation::new(&[0, 1, 2], FRAME, PlayMode::PingPong);
+        assert_eq!(frames(&mut ping_pong, 6), [1, 2, 1, 0, 1, 2]);
+
+        let mut once = Animation::new(&[4, 5], FRAME, PlayMode::Once);
+        assert_eq!(frames(&mut once, 3), [5, 5, 5]);
+        assert!(once.is_finished());
+    }
+
+    #[test]
+    fn events_fire_when_a_cycle_ends() {
+        let mut looping = Animation::new(&[0, 1], FRAME, PlayMode::Loop);
+        assert_eq!(looping.update(FRAME), []);
+        assert_eq!(looping.update(FRAME), [AnimationEvent::Looped]);
+        //a long frame can wrap more than once
+        assert_eq!(looping.update(FRAME * 4), [AnimationEvent::Looped; 2]);
+
+        let mut once = Animation::new(&[0, 1], FRAME, PlayMode::Once);
+        assert_eq!(once.update(FRAME * 10), [AnimationEvent::Finished]);
+        assert_eq!(once.update(FRAME), []);
+        once.restart();
+        assert_eq!((once.get_frame(), once.is_playing()), (0, true));
+    }
+
+    #[test]
+    fn speed_and_durations_scale_time() {
+        let mut animation =
+            Animation::with_durations(&[(0, FRAME), (1, FRAME * 3), (2, FRAME)], PlayMode::Loop);
+        animation.set_speed(2.0);
+        assert_eq!(frames(&mut animation, 3), [1, 2, 1]);
+
+        animation.pause();
+        animation.update(FRAME * 10);
+        assert_eq!(animation.get_frame(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "has to be finite")]
+    fn infinite_speed_panics() {
+        Animation::new(&[0, 1], FRAME, PlayMode::Loop).set_speed(f32::INFINITY);
+    }
+}
@@ -0,0 +1,117 @@
+//! abstraction over where draw calls end up
+//!
+//! `Renderer` and `Renderer2D` draw, upload and bind textures through a backend.
+//! `OpenGLBackend` forwards everything to the regular gl wrappers (`VertexArray`, `Shader`, ...),
+//! `SoftwareBackend` rasterizes on the cpu so rendering logic can run without any gl context
+
+pub mod opengl;
+pub mod software;
+
+use super::buffers::uniform_buffer::CameraData;
+use super::buffers::vertex_buffer_layout::VertexBufferLayout;
+use super::shader_error::ShaderError;
+use super::vertex_layout_error::VertexLayoutError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexBufferHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndexBufferHandle(usize);
+
+/// handles only mean something to the backend that created them, the OpenGL backend also takes
+/// any `Texture` or `TextureArray` as `TextureHandle::from(&texture)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle {
+    id: usize,
+    layered: bool,
+}
+
+impl TextureHandle {
+    /// true for texture arrays, sampled with a layer index
+    pub fn is_layered(&self) -> bool {
+        self.layered
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProgramHandle(usize);
+
+/// a value that can be assigned to a uniform through a backend
+#[derive(Debug, Clone, PartialEq)]
+pub enum Uniform {
+    Int(i32),
+    IntArray(Vec<i32>),
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec4(glm::Vec4),
+    Mat4(glm::Mat4),
+}
+
+pub trait RenderBackend {
+    /// creates a vertex buffer with the attributes described by `layout`
+    fn create_vertex_buffer(
+        &mut self,
+        data: &[f32],
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle;
+
+    /// creates an empty vertex buffer with room for `size` bytes, filled with `set_vertex_data`
+    fn create_dynamic_vertex_buffer(
+        &mut self,
+        size: usize,
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle;
+
+    /// replaces the contents of `buffer`, it grows when `data` doesn't fit
+    fn set_vertex_data(&mut self, buffer: VertexBufferHandle, data: &[f32]);
+
+    fn create_index_buffer(&mut self, data: &[u32]) -> IndexBufferHandle;
+
+    /// creates a texture from RGBA8 pixels, rows go bottom to top like glTexImage2D expects
+    fn create_texture(&mut self, width: i32, height: i32, pixels: &[u8]) -> TextureHandle;
+
+    /// creates a texture array from RGBA8 layers stored one after the other, rows go bottom to top
+    fn create_texture_array(
+        &mut self,
+        width: i32,
+        height: i32,
+        layers: i32,
+        pixels: &[u8],
+    ) -> TextureHandle;
+
+    /// how many textures a single draw can bind
+    fn get_max_texture_slots(&self) -> usize;
+
+    /// creates a program from a shader directory or single file (see `Shader::with_defines`)
+    fn create_program(
+        &mut self,
+        path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<ProgramHandle, ShaderError>;
+
+    /// rebuilds `program` if its files changed (see `Shader::reload_if_changed`), uniforms have
+    /// to be set again when this returns true
+    fn reload_program(&mut self, program: ProgramHandle) -> Result<bool, ShaderError>;
+
+    fn set_uniform(&mut self, program: ProgramHandle, name: &str, value: Uniform);
+
+    /// fills the `Camera` block of res/shaders/include/camera.glsl, shared by every program
+    fn set_camera(&mut self, camera: &CameraData);
+
+    fn clear(&mut self, color: glm::Vec4);
+
+    /// draws triangles from the first `count` indices, `textures[i]` is bound to texture slot i
+    /// as a 2d texture or a texture array.
+    /// fails without drawing when the vertex buffer doesn't feed the attributes of the program
+    fn draw_indexed(
+        &mut self,
+        vertices: VertexBufferHandle,
+        indices: IndexBufferHandle,
+        program: ProgramHandle,
+        textures: &[TextureHandle],
+        count: i32,
+    ) -> Result<(), VertexLayoutError>;
+
+    /// reads the render target back as RGBA8, top row first
+    fn read_pixels(&self) -> Vec<u8>;
+}
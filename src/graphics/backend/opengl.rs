@@ -0,0 +1,277 @@
+use super::{
+    IndexBufferHandle, ProgramHandle, RenderBackend, TextureHandle, Uniform, VertexBufferHandle,
+};
+use crate::graphics::buffers::index_buffer::IndexBuffer;
+use crate::graphics::buffers::uniform_buffer::{CameraData, UniformBuffer};
+use crate::graphics::buffers::vertex_array::VertexArray;
+use crate::graphics::buffers::vertex_buffer::VertexBuffer;
+use crate::graphics::buffers::vertex_buffer_layout::VertexBufferLayout;
+use crate::graphics::shader::Shader;
+use crate::graphics::shader_error::ShaderError;
+use crate::graphics::texture::{Texture, TextureFormat};
+use crate::graphics::texture_array::TextureArray;
+use crate::graphics::texture_spec::TextureSpec;
+use crate::graphics::vertex_layout_error::VertexLayoutError;
+
+/// backend that draws with the regular gl wrappers into whatever framebuffer is bound
+pub struct OpenGLBackend {
+    vertex_buffers: Vec<(VertexArray, VertexBuffer)>,
+    index_buffers: Vec<IndexBuffer>,
+    textures: Vec<Texture>,
+    texture_arrays: Vec<TextureArray>,
+    programs: Vec<Shader>,
+    /// created by the first `set_camera`
+    camera: Option<UniformBuffer>,
+}
+
+impl OpenGLBackend {
+    /// needs a current context
+    pub fn new() -> OpenGLBackend {
+        OpenGLBackend {
+            vertex_buffers: Vec::new(),
+            index_buffers: Vec::new(),
+            textures: Vec::new(),
+            texture_arrays: Vec::new(),
+            programs: Vec::new(),
+            camera: None,
+        }
+    }
+
+    /// draws the first `count` indices of `ib` with objects that weren't created through the
+    /// backend, see `VertexArray::bind_with` for when this fails
+    pub fn draw_elements(
+        &self,
+        va: &VertexArray,
+        ib: &IndexBuffer,
+        shader: &Shader,
+        count: i32,
+    ) -> Result<(), VertexLayoutError> {
+        shader.bind();
+        va.bind_with(shader)?;
+        ib.bind();
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, count, gl::UNSIGNED_INT, std::ptr::null());
+        }
+        Ok(())
+    }
+
+    /// like `draw_elements` with `base_vertex` added to each index
+    pub fn draw_elements_base_vertex(
+        &self,
+        va: &VertexArray,
+        ib: &IndexBuffer,
+        shader: &Shader,
+        count: i32,
+        base_vertex: i32,
+    ) -> Result<(), VertexLayoutError> {
+        shader.bind();
+        va.bind_with(shader)?;
+        ib.bind();
+        unsafe {
+            gl::DrawElementsBaseVertex(
+                gl::TRIANGLES,
+                count,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+                base_vertex,
+            );
+        }
+        Ok(())
+    }
+
+    fn add_vertex_buffer(
+        &mut self,
+        vb: VertexBuffer,
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle {
+        let mut va = VertexArray::new();
+        va.add_buffer(&vb, layout);
+        self.vertex_buffers.push((va, vb));
+        VertexBufferHandle(self.vertex_buffers.len() - 1)
+    }
+}
+
+impl Default for OpenGLBackend {
+    fn default() -> OpenGLBackend {
+        OpenGLBackend::new()
+    }
+}
+
+/// the handle of a texture is its gl name, so textures loaded anywhere can be drawn
+impl From<&Texture> for TextureHandle {
+    fn from(texture: &Texture) -> TextureHandle {
+        TextureHandle {
+            id: texture.get_id() as usize,
+            layered: false,
+        }
+    }
+}
+
+impl From<&TextureArray> for TextureHandle {
+    fn from(array: &TextureArray) -> TextureHandle {
+        TextureHandle {
+            id: array.get_id() as usize,
+            layered: true,
+        }
+    }
+}
+
+impl RenderBackend for OpenGLBackend {
+    fn create_vertex_buffer(
+        &mut self,
+        data: &[f32],
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle {
+        self.add_vertex_buffer(VertexBuffer::new(data), layout)
+    }
+
+    fn create_dynamic_vertex_buffer(
+        &mut self,
+        size: usize,
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle {
+        self.add_vertex_buffer(VertexBuffer::new_dynamic(size), layout)
+    }
+
+    fn set_vertex_data(&mut self, buffer: VertexBufferHandle, data: &[f32]) {
+        //orphaning keeps later uploads of the frame from waiting on the draws of earlier ones
+        self.vertex_buffers[buffer.0].1.set_data(data);
+    }
+
+    fn create_index_buffer(&mut self, data: &[u32]) -> IndexBufferHandle {
+        let ib = IndexBuffer::new(data);
+        ib.unbind();
+        self.index_buffers.push(ib);
+        IndexBufferHandle(self.index_buffers.len() - 1)
+    }
+
+    fn create_texture(&mut self, width: i32, height: i32, pixels: &[u8]) -> TextureHandle {
+        let texture = Texture::from_rgba(width, height, pixels);
+        let handle = TextureHandle::from(&texture);
+        self.textures.push(texture);
+        handle
+    }
+
+    fn create_texture_array(
+        &mut self,
+        width: i32,
+        height: i32,
+        layers: i32,
+        pixels: &[u8],
+    ) -> TextureHandle {
+        let array = TextureArray::from_pixels(
+            width,
+            height,
+            layers,
+            TextureFormat::RGBA8,
+            pixels,
+            &TextureSpec::default(),
+        );
+        let handle = TextureHandle::from(&array);
+        self.texture_arrays.push(array);
+        handle
+    }
+
+    fn get_max_texture_slots(&self) -> usize {
+        let mut max_units = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut max_units);
+        }
+        max_units.max(0) as usize
+    }
+
+    fn create_program(
+        &mut self,
+        path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<ProgramHandle, ShaderError> {
+        self.programs.push(Shader::with_defines(path, defines)?);
+        Ok(ProgramHandle(self.programs.len() - 1))
+    }
+
+    fn reload_program(&mut self, program: ProgramHandle) -> Result<bool, ShaderError> {
+        self.programs[program.0].reload_if_changed()
+    }
+
+    fn set_uniform(&mut self, program: ProgramHandle, name: &str, value: Uniform) {
+        let shader = &mut self.programs[program.0];
+        shader.bind();
+        match value {
+            Uniform::Int(value) => shader.set_uniform1i(name, value),
+            Uniform::IntArray(values) => shader.set_uniform1iv(name, &values),
+            Uniform::Float(value) => shader.set_uniform1f(name, value),
+            Uniform::Vec2(v) => shader.set_uniform2f(name, v.x, v.y),
+            Uniform::Vec4(v) => shader.set_uniform4f(name, v.x, v.y, v.z, v.w),
+            Uniform::Mat4(matrix) => shader.set_uniform_mat4f(name, &matrix),
+        }
+    }
+
+    /// the buffer sits at `CameraData::BINDING`, so it replaces any other buffer attached there
+    fn set_camera(&mut self, camera: &CameraData) {
+        self.camera
+            .get_or_insert_with(CameraData::create_buffer)
+            .set_data(&camera.to_std140());
+    }
+
+    fn clear(&mut self, color: glm::Vec4) {
+        unsafe {
+            gl::ClearColor(color.x, color.y, color.z, color.w);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn draw_indexed(
+        &mut self,
+        vertices: VertexBufferHandle,
+        indices: IndexBufferHandle,
+        program: ProgramHandle,
+        textures: &[TextureHandle],
+        count: i32,
+    ) -> Result<(), VertexLayoutError> {
+        for (slot, texture) in textures.iter().enumerate() {
+            let target = if texture.layered {
+                gl::TEXTURE_2D_ARRAY
+            } else {
+                gl::TEXTURE_2D
+            };
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + slot as u32);
+                gl::BindTexture(target, texture.id as u32);
+            }
+        }
+
+        let (va, _) = &self.vertex_buffers[vertices.0];
+        self.draw_elements(
+            va,
+            &self.index_buffers[indices.0],
+            &self.programs[program.0],
+            count,
+        )
+    }
+
+    /// reads the current viewport of the bound read framebuffer
+    fn read_pixels(&self) -> Vec<u8> {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        let [x, y, width, height] = viewport;
+        let row_len = (width * 4) as usize;
+        let mut pixels = vec![0u8; row_len * height as usize];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                x,
+                y,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+
+        //opengl starts at the bottom left, images start at the top left
+        pixels.chunks(row_len).rev().flatten().copied().collect()
+    }
+}
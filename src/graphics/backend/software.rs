@@ -0,0 +1,730 @@
+use super::{
+    IndexBufferHandle, ProgramHandle, RenderBackend, TextureHandle, Uniform, VertexBufferHandle,
+};
+use crate::graphics::buffers::uniform_buffer::CameraData;
+use crate::graphics::buffers::vertex_array::{attribute_shape, check_layout};
+use crate::graphics::buffers::vertex_buffer_layout::{VertexBufferElement, VertexBufferLayout};
+use crate::graphics::shader_error::ShaderError;
+use crate::graphics::shader_reflection::AttributeInfo;
+use crate::graphics::vertex_layout_error::VertexLayoutError;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type Uniforms = HashMap<String, Uniform>;
+
+/// RGBA8 texture or texture array in cpu memory, rows go bottom to top like in gl
+pub struct SoftwareTexture {
+    width: i32,
+    height: i32,
+    /// 1 for plain textures
+    layers: i32,
+    pixels: Vec<u8>,
+}
+
+impl SoftwareTexture {
+    /// bilinear sample with clamp to edge, the same sampler state `Texture` uses
+    pub fn sample(&self, uv: glm::Vec2) -> glm::Vec4 {
+        self.sample_layer(uv, 0.0)
+    }
+
+    /// samples a layer of an array, `layer` is rounded and clamped like gl does
+    pub fn sample_layer(&self, uv: glm::Vec2, layer: f32) -> glm::Vec4 {
+        let layer = ((layer + 0.5).floor() as i32).clamp(0, self.layers - 1);
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let texel = |x, y| self.texel(x, y, layer);
+        let bottom = glm::mix(&texel(x0, y0), &texel(x0 + 1, y0), fx);
+        let top = glm::mix(&texel(x0, y0 + 1), &texel(x0 + 1, y0 + 1), fx);
+        glm::mix(&bottom, &top, fy)
+    }
+
+    fn texel(&self, x: i32, y: i32, layer: i32) -> glm::Vec4 {
+        let x = x.clamp(0, self.width - 1);
+        let y = y.clamp(0, self.height - 1);
+        let index = (((layer * self.height + y) * self.width + x) * 4) as usize;
+        let texel = &self.pixels[index..index + 4];
+        glm::vec4(
+            texel[0] as f32,
+            texel[1] as f32,
+            texel[2] as f32,
+            texel[3] as f32,
+        ) / 255.0
+    }
+}
+
+/// cpu stand in for a glsl program
+pub trait SoftwareShader {
+    /// the attributes the glsl version reads, draws fail when the vertex buffer doesn't feed them
+    fn get_attributes(&self) -> Vec<AttributeInfo>;
+
+    /// runs for every vertex, `attributes[i]` holds the components of `get_attributes()[i]`,
+    /// missing ones are filled in from (0, 0, 0, 1). pushes the values to interpolate onto
+    /// `varyings` and returns the clip space position (gl_Position)
+    fn vertex(
+        &self,
+        attributes: &[&[f32]],
+        uniforms: &Uniforms,
+        varyings: &mut Vec<f32>,
+    ) -> glm::Vec4;
+
+    /// runs for every covered pixel, `textures[i]` is whatever is bound to slot i
+    fn fragment(
+        &self,
+        varyings: &[f32],
+        uniforms: &Uniforms,
+        textures: &[&SoftwareTexture],
+    ) -> glm::Vec4;
+}
+
+fn attribute(name: &str, location: i32, type_: u32) -> AttributeInfo {
+    AttributeInfo {
+        name: name.to_string(),
+        location,
+        type_,
+        size: 1,
+    }
+}
+
+fn uniform_mat4(uniforms: &Uniforms, name: &str) -> glm::Mat4 {
+    match uniforms.get(name) {
+        Some(Uniform::Mat4(matrix)) => *matrix,
+        _ => glm::Mat4::identity(),
+    }
+}
+
+/// unbound slots sample black like an incomplete texture in gl, plain textures ignore `layer`
+fn sample_slot(textures: &[&SoftwareTexture], slot: usize, uv: glm::Vec2, layer: f32) -> glm::Vec4 {
+    match textures.get(slot) {
+        Some(texture) => texture.sample_layer(uv, layer),
+        None => glm::vec4(0.0, 0.0, 0.0, 1.0),
+    }
+}
+
+/// same as res/shaders: u_MVP * position, output texture(u_Texture, v_TexCoord)
+pub struct BasicShader;
+
+impl SoftwareShader for BasicShader {
+    fn get_attributes(&self) -> Vec<AttributeInfo> {
+        vec![
+            attribute("position", 0, gl::FLOAT_VEC4),
+            attribute("texCoord", 1, gl::FLOAT_VEC2),
+        ]
+    }
+
+    fn vertex(
+        &self,
+        attributes: &[&[f32]],
+        uniforms: &Uniforms,
+        varyings: &mut Vec<f32>,
+    ) -> glm::Vec4 {
+        varyings.extend_from_slice(attributes[1]);
+        uniform_mat4(uniforms, "u_MVP") * glm::make_vec4(attributes[0])
+    }
+
+    fn fragment(
+        &self,
+        varyings: &[f32],
+        uniforms: &Uniforms,
+        textures: &[&SoftwareTexture],
+    ) -> glm::Vec4 {
+        let slot = match uniforms.get("u_Texture") {
+            Some(Uniform::Int(slot)) => *slot as usize,
+            _ => 0,
+        };
+        sample_slot(textures, slot, glm::vec2(varyings[0], varyings[1]), 0.0)
+    }
+}
+
+/// same as res/shaders/batch, see `Sprite::write_vertices` for the vertex format.
+/// positions go through camera.viewProjection of the Camera block
+pub struct BatchShader;
+
+impl SoftwareShader for BatchShader {
+    fn get_attributes(&self) -> Vec<AttributeInfo> {
+        vec![
+            attribute("a_Position", 0, gl::FLOAT_VEC2),
+            attribute("a_Color", 1, gl::FLOAT_VEC4),
+            attribute("a_TexCoord", 2, gl::FLOAT_VEC2),
+            attribute("a_TexIndex", 3, gl::FLOAT),
+            attribute("a_Layer", 4, gl::FLOAT),
+        ]
+    }
+
+    fn vertex(
+        &self,
+        attributes: &[&[f32]],
+        uniforms: &Uniforms,
+        varyings: &mut Vec<f32>,
+    ) -> glm::Vec4 {
+        varyings.extend_from_slice(attributes[1]);
+        varyings.extend_from_slice(attributes[2]);
+        varyings.extend_from_slice(attributes[3]);
+        varyings.extend_from_slice(attributes[4]);
+        let position = glm::vec4(attributes[0][0], attributes[0][1], 0.0, 1.0);
+        uniform_mat4(uniforms, "camera.viewProjection") * position
+    }
+
+    fn fragment(
+        &self,
+        varyings: &[f32],
+        uniforms: &Uniforms,
+        textures: &[&SoftwareTexture],
+    ) -> glm::Vec4 {
+        let tint = glm::vec4(varyings[0], varyings[1], varyings[2], varyings[3]);
+        let uv = glm::vec2(varyings[4], varyings[5]);
+        //flat in the glsl version, rounding undoes the interpolation error
+        let index = varyings[6].round() as i32;
+        if index < 0 {
+            return tint;
+        }
+
+        let slot = match uniforms.get("u_Textures") {
+            Some(Uniform::IntArray(slots)) => slots.get(index as usize).copied().unwrap_or(index),
+            _ => index,
+        };
+        //the LAYERED variant, a plain texture has a single layer
+        let layer = varyings[7];
+        sample_slot(textures, slot as usize, uv, layer).component_mul(&tint)
+    }
+}
+
+/// vertex data with the layout it was created with
+struct SoftwareVertexBuffer {
+    data: Vec<f32>,
+    elements: Vec<VertexBufferElement>,
+    /// floats per vertex
+    stride: usize,
+}
+
+struct ShadedVertex {
+    position: glm::Vec4,
+    varyings: Vec<f32>,
+}
+
+/// screen space position plus 1/w for perspective correct interpolation
+#[derive(Clone, Copy)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    inv_w: f32,
+}
+
+/// rasterizes triangles into an RGBA8 color buffer on the cpu
+///
+/// there is no glsl compiler, programs are looked up by path in a registry of `SoftwareShader`s
+/// that handle every combination of defines themselves and never reload.
+/// res/shaders and res/shaders/batch are registered by default. uniform blocks are plain uniforms
+/// named like the members in glsl, e.g. "camera.viewProjection". blending is always
+/// SRC_ALPHA / ONE_MINUS_SRC_ALPHA like main sets up and there is no depth buffer or near plane clipping
+pub struct SoftwareBackend {
+    width: i32,
+    height: i32,
+    color: Vec<u8>,
+    vertex_buffers: Vec<SoftwareVertexBuffer>,
+    index_buffers: Vec<Vec<u32>>,
+    textures: Vec<SoftwareTexture>,
+    programs: Vec<(Rc<dyn SoftwareShader>, Uniforms)>,
+    shaders: HashMap<String, Rc<dyn SoftwareShader>>,
+    /// the members of the Camera block, every program gets a copy
+    camera: Uniforms,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: i32, height: i32) -> SoftwareBackend {
+        let mut backend = SoftwareBackend {
+            width,
+            height,
+            color: vec![0; (width * height * 4) as usize],
+            vertex_buffers: Vec::new(),
+            index_buffers: Vec::new(),
+            textures: Vec::new(),
+            programs: Vec::new(),
+            shaders: HashMap::new(),
+            camera: Uniforms::new(),
+        };
+        backend.register_shader("res/shaders", Rc::new(BasicShader));
+        backend.register_shader("res/shaders/batch", Rc::new(BatchShader));
+        backend
+    }
+
+    /// makes `create_program(path)` use `shader`
+    pub fn register_shader(&mut self, path: &str, shader: Rc<dyn SoftwareShader>) {
+        self.shaders.insert(path.to_string(), shader);
+    }
+
+    /// the element feeding each attribute of `shader` and how many components the attribute has,
+    /// fails like `VertexArray::check_attributes` or for elements that aren't floats
+    fn feed<'a>(
+        elements: &'a [VertexBufferElement],
+        shader: &dyn SoftwareShader,
+    ) -> Result<Vec<(&'a VertexBufferElement, usize)>, VertexLayoutError> {
+        let attributes = shader.get_attributes();
+        check_layout(elements, &attributes)?;
+        attributes
+            .iter()
+            .map(|attribute| {
+                //check_layout found an element for every attribute
+                let element = elements
+                    .iter()
+                    .find(|element| element.location as i32 == attribute.location)
+                    .unwrap();
+                if element.type_ != gl::FLOAT {
+                    return Err(VertexLayoutError::UnsupportedType {
+                        name: attribute.name.clone(),
+                        location: attribute.location,
+                        type_: element.type_,
+                    });
+                }
+                let (components, _) = attribute_shape(attribute.type_);
+                Ok((element, components as usize))
+            })
+            .collect()
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn create_vertex_buffer(
+        &mut self,
+        data: &[f32],
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle {
+        self.vertex_buffers.push(SoftwareVertexBuffer {
+            data: data.to_vec(),
+            elements: layout.elements.clone(),
+            stride: layout.stride as usize / std::mem::size_of::<f32>(),
+        });
+        VertexBufferHandle(self.vertex_buffers.len() - 1)
+    }
+
+    fn create_dynamic_vertex_buffer(
+        &mut self,
+        _size: usize,
+        layout: &VertexBufferLayout,
+    ) -> VertexBufferHandle {
+        self.create_vertex_buffer(&[], layout)
+    }
+
+    fn set_vertex_data(&mut self, buffer: VertexBufferHandle, data: &[f32]) {
+        let vertices = &mut self.vertex_buffers[buffer.0].data;
+        vertices.clear();
+        vertices.extend_from_slice(data);
+    }
+
+    fn create_index_buffer(&mut self, data: &[u32]) -> IndexBufferHandle {
+        self.index_buffers.push(data.to_vec());
+        IndexBufferHandle(self.index_buffers.len() - 1)
+    }
+
+    /// stored like an array with a single layer
+    fn create_texture(&mut self, width: i32, height: i32, pixels: &[u8]) -> TextureHandle {
+        let handle = self.create_texture_array(width, height, 1, pixels);
+        TextureHandle {
+            layered: false,
+            ..handle
+        }
+    }
+
+    fn create_texture_array(
+        &mut self,
+        width: i32,
+        height: i32,
+        layers: i32,
+        pixels: &[u8],
+    ) -> TextureHandle {
+        assert_eq!(
+            pixels.len(),
+            (width * height * layers * 4) as usize,
+            "pixel data doesn't match the texture size"
+        );
+        self.textures.push(SoftwareTexture {
+            width,
+            height,
+            layers,
+            pixels: pixels.to_vec(),
+        });
+        TextureHandle {
+            id: self.textures.len() - 1,
+            layered: true,
+        }
+    }
+
+    /// every slot a shader samples from exists
+    fn get_max_texture_slots(&self) -> usize {
+        usize::MAX
+    }
+
+    fn create_program(
+        &mut self,
+        path: &str,
+        _defines: &[(&str, &str)],
+    ) -> Result<ProgramHandle, ShaderError> {
+        let Some(shader) = self.shaders.get(path).cloned() else {
+            return Err(ShaderError::Io {
+                path: path.to_string(),
+                error: std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no software shader is registered for it",
+                ),
+            });
+        };
+        self.programs.push((shader, self.camera.clone()));
+        Ok(ProgramHandle(self.programs.len() - 1))
+    }
+
+    fn reload_program(&mut self, _program: ProgramHandle) -> Result<bool, ShaderError> {
+        Ok(false)
+    }
+
+    fn set_uniform(&mut self, program: ProgramHandle, name: &str, value: Uniform) {
+        self.programs[program.0].1.insert(name.to_string(), value);
+    }
+
+    fn set_camera(&mut self, camera: &CameraData) {
+        self.camera = Uniforms::from([
+            ("camera.view".to_string(), Uniform::Mat4(camera.view)),
+            (
+                "camera.projection".to_string(),
+                Uniform::Mat4(camera.projection),
+            ),
+            (
+                "camera.viewProjection".to_string(),
+                Uniform::Mat4(camera.projection * camera.view),
+            ),
+            (
+                "camera.resolution".to_string(),
+                Uniform::Vec2(camera.resolution),
+            ),
+            ("camera.time".to_string(), Uniform::Float(camera.time)),
+        ]);
+        for (_, uniforms) in &mut self.programs {
+            uniforms.extend(self.camera.clone());
+        }
+    }
+
+    fn clear(&mut self, color: glm::Vec4) {
+        let color = to_rgba8(&color);
+        for pixel in self.color.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    fn draw_indexed(
+        &mut self,
+        vertices: VertexBufferHandle,
+        indices: IndexBufferHandle,
+        program: ProgramHandle,
+        textures: &[TextureHandle],
+        count: i32,
+    ) -> Result<(), VertexLayoutError> {
+        let buffer = &self.vertex_buffers[vertices.0];
+        let (shader, uniforms) = &self.programs[program.0];
+        let feed = Self::feed(&buffer.elements, shader.as_ref())?;
+        let textures: Vec<&SoftwareTexture> = textures
+            .iter()
+            .map(|texture| &self.textures[texture.id])
+            .collect();
+
+        //every vertex goes through the vertex shader once, triangles share the results
+        let shaded: Vec<ShadedVertex> = buffer
+            .data
+            .chunks_exact(buffer.stride)
+            .map(|vertex| {
+                //missing components default to (0, 0, 0, 1) like vertex attributes in gl
+                let values: Vec<Vec<f32>> = feed
+                    .iter()
+                    .map(|&(element, components)| {
+                        let start = element.offset as usize / std::mem::size_of::<f32>();
+                        let count = element.count as usize;
+                        let mut value = vec![0.0, 0.0, 0.0, 1.0];
+                        value[..count].copy_from_slice(&vertex[start..start + count]);
+                        value.truncate(components);
+                        value
+                    })
+                    .collect();
+                let attributes: Vec<&[f32]> = values.iter().map(Vec::as_slice).collect();
+                let mut varyings = Vec::new();
+                let position = shader.vertex(&attributes, uniforms, &mut varyings);
+                ShadedVertex { position, varyings }
+            })
+            .collect();
+
+        let mut target = Target {
+            width: self.width,
+            height: self.height,
+            color: &mut self.color,
+        };
+        for triangle in self.index_buffers[indices.0][..count as usize].chunks_exact(3) {
+            let triangle = [
+                &shaded[triangle[0] as usize],
+                &shaded[triangle[1] as usize],
+                &shaded[triangle[2] as usize],
+            ];
+            target.rasterize(triangle, shader.as_ref(), uniforms, &textures);
+        }
+        Ok(())
+    }
+
+    fn read_pixels(&self) -> Vec<u8> {
+        //stored bottom row first like gl, images start at the top left
+        let row_len = (self.width * 4) as usize;
+        self.color
+            .chunks(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+struct Target<'a> {
+    width: i32,
+    height: i32,
+    color: &'a mut [u8],
+}
+
+impl Target<'_> {
+    fn rasterize(
+        &mut self,
+        triangle: [&ShadedVertex; 3],
+        shader: &dyn SoftwareShader,
+        uniforms: &Uniforms,
+        textures: &[&SoftwareTexture],
+    ) {
+        //there is no clipping so anything touching the camera plane is dropped
+        if triangle.iter().any(|vertex| vertex.position.w <= 0.0) {
+            return;
+        }
+
+        let mut screen = triangle.map(|vertex| {
+            let p = vertex.position;
+            ScreenVertex {
+                x: (p.x / p.w + 1.0) * 0.5 * self.width as f32,
+                y: (p.y / p.w + 1.0) * 0.5 * self.height as f32,
+                inv_w: 1.0 / p.w,
+            }
+        });
+        let mut vertices = triangle;
+
+        //no culling, clockwise triangles just get flipped so the edge functions are positive inside
+        let mut area = edge(&screen[0], &screen[1], screen[2].x, screen[2].y);
+        if area == 0.0 {
+            return;
+        }
+        if area < 0.0 {
+            screen.swap(1, 2);
+            vertices.swap(1, 2);
+            area = -area;
+        }
+
+        let min_x = screen.iter().map(|v| v.x).fold(f32::MAX, f32::min);
+        let max_x = screen.iter().map(|v| v.x).fold(f32::MIN, f32::max);
+        let min_y = screen.iter().map(|v| v.y).fold(f32::MAX, f32::min);
+        let max_y = screen.iter().map(|v| v.y).fold(f32::MIN, f32::max);
+        let min_x = (min_x.floor() as i32).max(0);
+        let max_x = (max_x.ceil() as i32).min(self.width - 1);
+        let min_y = (min_y.floor() as i32).max(0);
+        let max_y = (max_y.ceil() as i32).min(self.height - 1);
+
+        let varying_count = vertices[0].varyings.len();
+        let mut varyings = vec![0.0; varying_count];
+
+        for py in min_y..=max_y {
+            for px in min_x..=max_x {
+                //sample at the pixel center like gl does
+                let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+                let w0 = edge(&screen[1], &screen[2], x, y);
+                let w1 = edge(&screen[2], &screen[0], x, y);
+                let w2 = edge(&screen[0], &screen[1], x, y);
+                if !covers(w0, &screen[1], &screen[2])
+                    || !covers(w1, &screen[2], &screen[0])
+                    || !covers(w2, &screen[0], &screen[1])
+                {
+                    continue;
+                }
+
+                //perspective correct barycentrics
+                let b = [
+                    w0 / area * screen[0].inv_w,
+                    w1 / area * screen[1].inv_w,
+                    w2 / area * screen[2].inv_w,
+                ];
+                let sum = b[0] + b[1] + b[2];
+                for (i, varying) in varyings.iter_mut().enumerate() {
+                    *varying = (b[0] * vertices[0].varyings[i]
+                        + b[1] * vertices[1].varyings[i]
+                        + b[2] * vertices[2].varyings[i])
+                        / sum;
+                }
+
+                let color = shader.fragment(&varyings, uniforms, textures);
+                self.blend(px, py, &color);
+            }
+        }
+    }
+
+    /// SRC_ALPHA, ONE_MINUS_SRC_ALPHA on all four channels
+    fn blend(&mut self, x: i32, y: i32, source: &glm::Vec4) {
+        let index = ((y * self.width + x) * 4) as usize;
+        let pixel = &mut self.color[index..index + 4];
+        let source = glm::clamp(source, 0.0, 1.0);
+        let alpha = source.w;
+        let mut blended = glm::Vec4::zeros();
+        for channel in 0..4 {
+            let destination = pixel[channel] as f32 / 255.0;
+            blended[channel] = source[channel] * alpha + destination * (1.0 - alpha);
+        }
+        pixel.copy_from_slice(&to_rgba8(&blended));
+    }
+}
+
+/// twice the signed area of (a, b, p), positive when p is left of a -> b
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// top left fill rule so pixels on a shared edge are only drawn once
+fn covers(weight: f32, a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    if weight != 0.0 {
+        return weight > 0.0;
+    }
+    //y points up and the winding is counter clockwise here
+    let top = a.y == b.y && b.x < a.x;
+    let left = b.y < a.y;
+    top || left
+}
+
+fn to_rgba8(color: &glm::Vec4) -> [u8; 4] {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        channel(color.x),
+        channel(color.y),
+        channel(color.z),
+        channel(color.w),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers::vertex_buffer_layout::AttributeMode;
+    use crate::graphics::renderer_2d::{batch_layout, quad_indices, Sprite};
+
+    fn draw_quads(backend: &mut SoftwareBackend, sprites: &[Sprite], size: f32) {
+        let mut vertices = Vec::new();
+        for sprite in sprites {
+            sprite.write_vertices(-1.0, &mut vertices);
+        }
+        let vb = backend.create_vertex_buffer(&vertices, &batch_layout());
+        let ib = backend.create_index_buffer(&quad_indices(sprites.len()));
+        let program = backend.create_program("res/shaders/batch", &[]).unwrap();
+        backend.set_camera(&CameraData {
+            projection: glm::ortho(0.0, size, 0.0, size, -1.0, 1.0),
+            ..CameraData::default()
+        });
+        let count = (sprites.len() * 6) as i32;
+        backend.draw_indexed(vb, ib, program, &[], count).unwrap();
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        //a half transparent quad blended twice on the diagonal would come out brighter there
+        let mut backend = SoftwareBackend::new(16, 16);
+        backend.clear(glm::vec4(0.0, 0.0, 0.0, 1.0));
+        let sprite = Sprite::colored(
+            glm::vec2(8.0, 8.0),
+            glm::vec2(16.0, 16.0),
+            glm::vec4(1.0, 1.0, 1.0, 0.5),
+        );
+        draw_quads(&mut backend, &[sprite], 16.0);
+
+        let pixels = backend.read_pixels();
+        assert!(pixels.chunks(4).all(|pixel| pixel == pixels[..4].as_ref()));
+        assert_eq!(pixels[0], 128);
+    }
+
+    #[test]
+    fn clockwise_triangles_are_not_culled() {
+        let mut backend = SoftwareBackend::new(8, 8);
+        let mut sprite = Sprite::colored(
+            glm::vec2(4.0, 4.0),
+            glm::vec2(8.0, 8.0),
+            glm::vec4(0.0, 1.0, 0.0, 1.0),
+        );
+        //a negative width mirrors the quad which flips the winding
+        sprite.size.x = -8.0;
+        draw_quads(&mut backend, &[sprite], 8.0);
+
+        assert!(backend
+            .read_pixels()
+            .chunks(4)
+            .all(|pixel| pixel == [0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn unregistered_programs_are_errors() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        assert!(matches!(
+            backend.create_program("res/shaders/post", &[]),
+            Err(ShaderError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn layouts_the_shader_cant_read_fail_the_draw() {
+        let mut backend = SoftwareBackend::new(1, 1);
+        let program = backend.create_program("res/shaders/batch", &[]).unwrap();
+        let ib = backend.create_index_buffer(&quad_indices(1));
+        let mut draw = |layout: &VertexBufferLayout| {
+            let vb = backend.create_vertex_buffer(&[0.0; 40], layout);
+            backend.draw_indexed(vb, ib, program, &[], 6)
+        };
+
+        let mut positions = VertexBufferLayout::new();
+        positions.push::<f32>(2);
+        let mut bytes = batch_layout();
+        bytes.elements[1].type_ = gl::UNSIGNED_BYTE;
+        bytes.elements[1].mode = AttributeMode::Normalized;
+
+        assert_eq!(
+            draw(&positions),
+            Err(VertexLayoutError::MissingAttribute {
+                name: "a_Color".to_string(),
+                location: 1,
+            })
+        );
+        assert_eq!(
+            draw(&bytes),
+            Err(VertexLayoutError::UnsupportedType {
+                name: "a_Color".to_string(),
+                location: 1,
+                type_: gl::UNSIGNED_BYTE,
+            })
+        );
+    }
+
+    #[test]
+    fn texture_sampling_clamps_to_edge() {
+        let texture = SoftwareTexture {
+            width: 2,
+            height: 1,
+            layers: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 255],
+        };
+        assert_eq!(
+            texture.sample(glm::vec2(-1.0, 0.5)),
+            glm::vec4(1.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            texture.sample(glm::vec2(2.0, 0.5)),
+            glm::vec4(0.0, 0.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            texture.sample(glm::vec2(0.5, 0.5)),
+            glm::vec4(0.5, 0.0, 0.5, 1.0)
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/// how often the contents of a buffer change, the driver picks where to keep it from this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BufferUsage {
+    /// written once, drawn many times
+    #[default]
+    Static,
+    /// rewritten now and then, drawn many times
+    Dynamic,
+    /// rewritten every time it's drawn
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+/// the capacity a buffer of `capacity` bytes grows to so `needed` bytes fit, doubling keeps
+/// buffers that grow a bit every frame from being reallocated every frame
+pub(super) fn grow_capacity(capacity: usize, needed: usize) -> usize {
+    needed.max(capacity * 2)
+}
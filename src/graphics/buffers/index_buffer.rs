@@ -1,27 +1,107 @@
+use super::buffer_usage::{grow_capacity, BufferUsage};
+use crate::graphics::gl_resource;
+
 pub struct IndexBuffer {
     id: u32,
     count: i32,
+    size: usize,
+    usage: BufferUsage,
 }
 
 impl IndexBuffer {
+    #[track_caller]
     pub fn new(data: &[u32]) -> IndexBuffer {
+        Self::with_usage(data, BufferUsage::Static)
+    }
+
+    /// creates a buffer holding `data`, `usage` tells the driver how often it will change
+    #[track_caller]
+    pub fn with_usage(data: &[u32], usage: BufferUsage) -> IndexBuffer {
+        let mut buffer = Self::create(
+            std::mem::size_of_val(data),
+            data.as_ptr() as *const std::ffi::c_void,
+            usage,
+        );
+        buffer.count = data.len() as i32;
+        buffer
+    }
+
+    /// creates an empty buffer with room for `capacity` indices, filled with `set_data`
+    #[track_caller]
+    pub fn new_dynamic(capacity: usize) -> IndexBuffer {
+        Self::create(
+            capacity * std::mem::size_of::<u32>(),
+            std::ptr::null(),
+            BufferUsage::Dynamic,
+        )
+    }
+
+    #[track_caller]
+    fn create(size: usize, data: *const std::ffi::c_void, usage: BufferUsage) -> IndexBuffer {
         unsafe {
             let mut id = 0;
             gl::GenBuffers(1, &mut id);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, id);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (data.len() * std::mem::size_of::<u32>()) as isize,
-                data.as_ptr() as *const std::ffi::c_void,
-                gl::STATIC_DRAW,
-            );
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, size as isize, data, usage.to_gl());
+            gl_resource::track("IndexBuffer", id, size);
             IndexBuffer {
                 id,
-                count: data.len() as i32,
+                count: 0,
+                size,
+                usage,
             }
         }
     }
 
+    /// replaces the indices, the count becomes the length of `data`
+    ///
+    /// like `VertexBuffer::set_data` the old storage is orphaned and the buffer grows to at
+    /// least twice its size when `data` doesn't fit
+    pub fn set_data(&mut self, data: &[u32]) {
+        let bytes = std::mem::size_of_val(data);
+        if bytes > self.size {
+            self.size = grow_capacity(self.size, bytes);
+            gl_resource::retrack("IndexBuffer", self.id, self.id, self.size);
+        }
+        self.orphan();
+        self.count = 0;
+        self.set_sub_data(0, data);
+    }
+
+    /// overwrites the indices starting at index `first`, the count grows if they go past it
+    pub fn set_sub_data(&mut self, first: usize, data: &[u32]) {
+        assert!(
+            first + data.len() <= self.get_capacity(),
+            "index buffer has room for {} indices, writing {} at {}",
+            self.get_capacity(),
+            data.len(),
+            first
+        );
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (first * std::mem::size_of::<u32>()) as isize,
+                std::mem::size_of_val(data) as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+        self.count = self.count.max((first + data.len()) as i32);
+    }
+
+    /// gives the buffer fresh storage of the same size, the contents are undefined afterwards
+    pub fn orphan(&self) {
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                self.size as isize,
+                std::ptr::null(),
+                self.usage.to_gl(),
+            );
+        }
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
@@ -37,4 +117,45 @@ impl IndexBuffer {
     pub fn get_count(&self) -> i32 {
         self.count
     }
+
+    /// how many indices fit without growing
+    pub fn get_capacity(&self) -> usize {
+        self.size / std::mem::size_of::<u32>()
+    }
+
+    pub fn get_usage(&self) -> BufferUsage {
+        self.usage
+    }
+}
+
+impl Drop for IndexBuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("IndexBuffer", self.id) {
+            unsafe { gl::DeleteBuffers(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn set_data_updates_the_count() {
+        let (counts, calls) = with_mock_gl(|| {
+            let mut ib = IndexBuffer::new_dynamic(3);
+            let empty = ib.get_count();
+            ib.set_data(&[0, 1, 2, 2, 3, 0]);
+            (empty, ib.get_count(), ib.get_capacity(), ib)
+        });
+
+        assert_eq!((counts.0, counts.1, counts.2), (0, 6, 6));
+        assert!(calls.contains(&GlCall::BufferData {
+            target: gl::ELEMENT_ARRAY_BUFFER,
+            size: 24,
+            data: None,
+            usage: gl::DYNAMIC_DRAW,
+        }));
+    }
 }
@@ -1,4 +1,10 @@
+pub mod buffer_usage;
 pub mod index_buffer;
+pub mod storage_buffer;
+pub mod stream_buffer;
+pub mod uniform_buffer;
+pub mod vertex;
 pub mod vertex_array;
 pub mod vertex_buffer;
 pub mod vertex_buffer_layout;
+pub mod vertex_formats;
@@ -0,0 +1,139 @@
+use crate::graphics::gl_resource;
+
+/// a shader storage buffer, the `buffer` blocks compute shaders read and write
+///
+/// like `UniformBuffer` it's attached to a binding point, shaders pick their blocks from those
+pub struct StorageBuffer {
+    id: u32,
+    size: usize,
+    binding: u32,
+}
+
+impl StorageBuffer {
+    /// creates a zeroed buffer of `size` bytes attached to `binding`
+    #[track_caller]
+    pub fn new(size: usize, binding: u32) -> StorageBuffer {
+        Self::create(size, std::ptr::null(), binding)
+    }
+
+    /// creates a buffer holding `data` attached to `binding`
+    #[track_caller]
+    pub fn with_data(data: &[u8], binding: u32) -> StorageBuffer {
+        Self::create(
+            data.len(),
+            data.as_ptr() as *const std::ffi::c_void,
+            binding,
+        )
+    }
+
+    #[track_caller]
+    fn create(size: usize, data: *const std::ffi::c_void, binding: u32) -> StorageBuffer {
+        unsafe {
+            let mut id = 0;
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                size as isize,
+                data,
+                gl::DYNAMIC_COPY,
+            );
+            if data.is_null() {
+                //a null pointer leaves the contents undefined
+                gl::ClearBufferData(
+                    gl::SHADER_STORAGE_BUFFER,
+                    gl::R8,
+                    gl::RED,
+                    gl::UNSIGNED_BYTE,
+                    std::ptr::null(),
+                );
+            }
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, id);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            gl_resource::track("StorageBuffer", id, size);
+            StorageBuffer { id, size, binding }
+        }
+    }
+
+    pub fn set_data(&self, data: &[u8]) {
+        self.set_sub_data(0, data);
+    }
+
+    pub fn set_sub_data(&self, offset: usize, data: &[u8]) {
+        assert!(
+            offset + data.len() <= self.size,
+            "storage buffer is {} bytes, writing {} bytes at {}",
+            self.size,
+            data.len(),
+            offset
+        );
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                offset as isize,
+                data.len() as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+    }
+
+    /// copies the contents back, waits for the gpu so keep this out of the frame loop
+    ///
+    /// needs `Renderer::memory_barrier(gl::BUFFER_UPDATE_BARRIER_BIT)` after the dispatch
+    /// that wrote it
+    pub fn read_data(&self) -> Vec<u8> {
+        let mut data = vec![0u8; self.size];
+        self.bind();
+        unsafe {
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                self.size as isize,
+                data.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+        data
+    }
+
+    /// attaches the buffer to another binding point
+    pub fn set_binding(&mut self, binding: u32) {
+        self.binding = binding;
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+    }
+
+    /// the buffer can also be bound as a vertex buffer to draw what a compute shader wrote
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for StorageBuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("StorageBuffer", self.id) {
+            unsafe { gl::DeleteBuffers(1, &self.id) };
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::graphics::gl_resource;
+use std::collections::VecDeque;
+
+/// a ring buffer for geometry that is rebuilt every frame, like ui or particles
+///
+/// `push` appends data after whatever was pushed before and returns where it went, so draws
+/// of earlier pushes keep their data. when the end is reached it starts over at the front:
+/// - with gl 4.4 the buffer stays mapped and pushes are plain copies. `finish_frame` puts a
+///   fence behind the draws of the frame, a push waits for it before overwriting their data
+/// - otherwise pushes are glBufferSubData calls and the buffer is orphaned when it wraps
+pub struct StreamBuffer {
+    id: u32,
+    target: u32,
+    size: usize,
+    head: usize,
+    mapping: *mut u8,
+    /// byte ranges pushed since the last `finish_frame`
+    pending: Vec<(usize, usize)>,
+    /// ranges of earlier frames and the fence that signals when the gpu is done with them
+    in_flight: VecDeque<(Vec<(usize, usize)>, gl::types::GLsync)>,
+}
+
+impl StreamBuffer {
+    /// creates a ring of `size` bytes, `target` is gl::ARRAY_BUFFER or gl::ELEMENT_ARRAY_BUFFER
+    #[track_caller]
+    pub fn new(target: u32, size: usize) -> StreamBuffer {
+        let persistent = Self::is_persistent_mapping_supported();
+        let mut id = 0;
+        let mut mapping = std::ptr::null_mut();
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(target, id);
+            if persistent {
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+                gl::BufferStorage(target, size as isize, std::ptr::null(), flags);
+                mapping = gl::MapBufferRange(target, 0, size as isize, flags) as *mut u8;
+            } else {
+                gl::BufferData(target, size as isize, std::ptr::null(), gl::STREAM_DRAW);
+            }
+            gl::BindBuffer(target, 0);
+        }
+        gl_resource::track("StreamBuffer", id, size);
+
+        StreamBuffer {
+            id,
+            target,
+            size,
+            head: 0,
+            mapping,
+            pending: Vec::new(),
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// persistent mapping needs glBufferStorage, core since gl 4.4
+    pub fn is_persistent_mapping_supported() -> bool {
+        if !gl::BufferStorage::is_loaded() {
+            return false;
+        }
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+        (major, minor) >= (4, 4)
+    }
+
+    /// copies `data` into the ring and returns its offset in bytes, aligned to the size of `T`
+    /// so vertices can be drawn with `offset / size_of::<T>()` as the base vertex
+    pub fn push<T: Copy>(&mut self, data: &[T]) -> usize {
+        self.push_aligned(data, std::mem::size_of::<T>().max(1))
+    }
+
+    /// like `push` with the offset aligned to `alignment` bytes, e.g. the vertex stride when
+    /// pushing vertices as a slice of floats. an alignment of 0 means no alignment
+    pub fn push_aligned<T: Copy>(&mut self, data: &[T], alignment: usize) -> usize {
+        let alignment = alignment.max(1);
+        let bytes = std::mem::size_of_val(data);
+        assert!(
+            bytes <= self.size,
+            "pushing {} bytes into a stream buffer of {} bytes",
+            bytes,
+            self.size
+        );
+
+        let mut start = self.head.next_multiple_of(alignment);
+        if start + bytes > self.size {
+            start = 0;
+            if self.mapping.is_null() {
+                //the driver hands out new storage and keeps the old one until draws are done
+                self.bind();
+                unsafe {
+                    gl::BufferData(
+                        self.target,
+                        self.size as isize,
+                        std::ptr::null(),
+                        gl::STREAM_DRAW,
+                    );
+                }
+                self.pending.clear();
+            }
+        }
+        let end = start + bytes;
+
+        if self.mapping.is_null() {
+            self.bind();
+            unsafe {
+                gl::BufferSubData(
+                    self.target,
+                    start as isize,
+                    bytes as isize,
+                    data.as_ptr() as *const std::ffi::c_void,
+                );
+            }
+        } else {
+            if overlaps(&self.pending, start, end) {
+                //wrapped onto this frame's own data, its draws have been issued already
+                self.finish_frame();
+            }
+            self.wait_for(start, end);
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data.as_ptr() as *const u8,
+                    self.mapping.add(start),
+                    bytes,
+                );
+            }
+        }
+
+        self.pending.push((start, end));
+        self.head = end;
+        start
+    }
+
+    /// call once the draws using this frame's pushes have been issued
+    pub fn finish_frame(&mut self) {
+        if self.mapping.is_null() || self.pending.is_empty() {
+            self.pending.clear();
+            return;
+        }
+        let fence = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        self.in_flight
+            .push_back((std::mem::take(&mut self.pending), fence));
+    }
+
+    /// blocks until the gpu is done with every frame that used bytes `start..end`
+    fn wait_for(&mut self, start: usize, end: usize) {
+        //fences signal in order, waiting for the newest overlapping one covers the older ones
+        let Some(last) = self
+            .in_flight
+            .iter()
+            .rposition(|(ranges, _)| overlaps(ranges, start, end))
+        else {
+            return;
+        };
+        for (_, fence) in self.in_flight.drain(..=last) {
+            unsafe {
+                //one second at a time, flushing so the fence reaches the gpu
+                while gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000_000)
+                    == gl::TIMEOUT_EXPIRED
+                {}
+                gl::DeleteSync(fence);
+            }
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target, 0);
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// true if pushes write through a persistent mapping
+    pub fn is_persistent(&self) -> bool {
+        !self.mapping.is_null()
+    }
+}
+
+fn overlaps(ranges: &[(usize, usize)], start: usize, end: usize) -> bool {
+    ranges
+        .iter()
+        .any(|&(range_start, range_end)| range_start < end && start < range_end)
+}
+
+impl Drop for StreamBuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("StreamBuffer", self.id) {
+            unsafe {
+                for (_, fence) in self.in_flight.drain(..) {
+                    gl::DeleteSync(fence);
+                }
+                //deleting a mapped buffer unmaps it
+                gl::DeleteBuffers(1, &self.id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{mapped_memory, set_version, with_mock_gl, GlCall};
+
+    #[test]
+    fn pushes_are_appended_and_wrap_around() {
+        let (offsets, calls) = with_mock_gl(|| {
+            set_version(4, 3);
+            let mut stream = StreamBuffer::new(gl::ARRAY_BUFFER, 12);
+            let offsets = [
+                stream.push(&[1u8, 2, 3]),
+                stream.push(&[4u32]),
+                stream.push(&[5u32, 6]),
+            ];
+            (offsets, stream.is_persistent(), stream)
+        });
+
+        assert_eq!((offsets.0, offsets.1), ([0, 4, 0], false));
+        //orphaned before the wrapped push
+        assert_eq!(
+            calls[calls.len() - 3],
+            GlCall::BufferData {
+                target: gl::ARRAY_BUFFER,
+                size: 12,
+                data: None,
+                usage: gl::STREAM_DRAW,
+            }
+        );
+        assert_eq!(
+            calls[calls.len() - 1],
+            GlCall::BufferSubData {
+                target: gl::ARRAY_BUFFER,
+                offset: 0,
+                data: [5u32, 6].iter().flat_map(|v| v.to_ne_bytes()).collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn zero_alignment_packs_tightly() {
+        let (offsets, _) = with_mock_gl(|| {
+            set_version(4, 3);
+            let mut stream = StreamBuffer::new(gl::ARRAY_BUFFER, 8);
+            let offsets = [stream.push(&[1u8]), stream.push_aligned(&[2u8], 0)];
+            (offsets, stream)
+        });
+
+        assert_eq!(offsets.0, [0, 1]);
+    }
+
+    #[test]
+    fn persistent_pushes_wait_for_the_frame_they_overwrite() {
+        let ((memory, offset, _stream), calls) = with_mock_gl(|| {
+            let mut stream = StreamBuffer::new(gl::ARRAY_BUFFER, 8);
+            assert!(stream.is_persistent());
+            stream.push(&[1u32, 2]);
+            stream.finish_frame();
+            let offset = stream.push(&[3u32]);
+            (mapped_memory(stream.get_id()), offset, stream)
+        });
+
+        assert_eq!(offset, 0);
+        assert_eq!(memory[..4], 3u32.to_ne_bytes());
+        let fence = calls.iter().find_map(|call| match call {
+            GlCall::FenceSync(fence) => Some(*fence),
+            _ => None,
+        });
+        let fence = fence.unwrap();
+        assert!(calls.ends_with(&[GlCall::ClientWaitSync(fence), GlCall::DeleteSync(fence)]));
+        assert!(!calls
+            .iter()
+            .any(|call| matches!(call, GlCall::BufferSubData { .. })));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::graphics::gl_resource;
+
+/// packs values with the std140 rules so the bytes line up with a `layout(std140)` block
+///
+/// scalars align to 4 bytes, vec2 to 8, vec3 and vec4 to 16. array elements and matrix columns
+/// take up a full vec4 each
+#[derive(Default)]
+pub struct Std140Writer {
+    data: Vec<u8>,
+}
+
+impl Std140Writer {
+    fn align(&mut self, alignment: usize) {
+        let padded = self.data.len().next_multiple_of(alignment);
+        self.data.resize(padded, 0);
+    }
+
+    fn push(&mut self, alignment: usize, values: &[u32]) -> &mut Self {
+        self.align(alignment);
+        for value in values {
+            self.data.extend_from_slice(&value.to_ne_bytes());
+        }
+        self
+    }
+
+    pub fn float(&mut self, value: f32) -> &mut Self {
+        self.push(4, &[value.to_bits()])
+    }
+
+    pub fn int(&mut self, value: i32) -> &mut Self {
+        self.push(4, &[value as u32])
+    }
+
+    pub fn uint(&mut self, value: u32) -> &mut Self {
+        self.push(4, &[value])
+    }
+
+    /// bools are 4 bytes in glsl
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.push(4, &[value as u32])
+    }
+
+    pub fn vec2(&mut self, value: &glm::Vec2) -> &mut Self {
+        self.push(8, &[value.x.to_bits(), value.y.to_bits()])
+    }
+
+    pub fn vec3(&mut self, value: &glm::Vec3) -> &mut Self {
+        let bits: Vec<u32> = value.iter().map(|component| component.to_bits()).collect();
+        self.push(16, &bits)
+    }
+
+    pub fn vec4(&mut self, value: &glm::Vec4) -> &mut Self {
+        let bits: Vec<u32> = value.iter().map(|component| component.to_bits()).collect();
+        self.push(16, &bits)
+    }
+
+    /// three columns padded to vec4
+    pub fn mat3(&mut self, value: &glm::Mat3) -> &mut Self {
+        for column in value.column_iter() {
+            self.vec3(&column.into_owned());
+        }
+        self.align(16);
+        self
+    }
+
+    pub fn mat4(&mut self, value: &glm::Mat4) -> &mut Self {
+        let bits: Vec<u32> = value.iter().map(|component| component.to_bits()).collect();
+        self.push(16, &bits)
+    }
+
+    /// every element takes up 16 bytes
+    pub fn float_array(&mut self, values: &[f32]) -> &mut Self {
+        for value in values {
+            self.push(16, &[value.to_bits()]);
+        }
+        self.align(16);
+        self
+    }
+
+    pub fn vec4_array(&mut self, values: &[glm::Vec4]) -> &mut Self {
+        for value in values {
+            self.vec4(value);
+        }
+        self
+    }
+
+    pub fn mat4_array(&mut self, values: &[glm::Mat4]) -> &mut Self {
+        for value in values {
+            self.mat4(value);
+        }
+        self
+    }
+
+    /// offset the next value would start at if it only needed 4 byte alignment
+    pub fn get_offset(&self) -> usize {
+        self.data.len()
+    }
+
+    /// the block padded to a multiple of 16 bytes
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.align(16);
+        std::mem::take(&mut self.data)
+    }
+}
+
+/// a buffer backing a uniform block, attached to a binding point shaders pick their blocks from
+pub struct UniformBuffer {
+    id: u32,
+    size: usize,
+    binding: u32,
+}
+
+impl UniformBuffer {
+    /// creates a buffer of `size` bytes and attaches it to `binding`
+    #[track_caller]
+    pub fn new(size: usize, binding: u32) -> UniformBuffer {
+        unsafe {
+            let mut id = 0;
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                size as isize,
+                std::ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl_resource::track("UniformBuffer", id, size);
+            UniformBuffer { id, size, binding }
+        }
+    }
+
+    /// overwrites the start of the buffer, usually with `Std140Writer::finish`
+    pub fn set_data(&self, data: &[u8]) {
+        self.set_sub_data(0, data);
+    }
+
+    pub fn set_sub_data(&self, offset: usize, data: &[u8]) {
+        assert!(
+            offset + data.len() <= self.size,
+            "uniform buffer is {} bytes, writing {} bytes at {}",
+            self.size,
+            data.len(),
+            offset
+        );
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                offset as isize,
+                data.len() as isize,
+                data.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+    }
+
+    /// attaches the buffer to another binding point
+    pub fn set_binding(&mut self, binding: u32) {
+        self.binding = binding;
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    pub fn get_binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("UniformBuffer", self.id) {
+            unsafe { gl::DeleteBuffers(1, &self.id) };
+        }
+    }
+}
+
+/// contents of the per frame `Camera` block in res/shaders/include/camera.glsl
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraData {
+    pub view: glm::Mat4,
+    pub projection: glm::Mat4,
+    pub resolution: glm::Vec2,
+    /// seconds since startup
+    pub time: f32,
+}
+
+impl CameraData {
+    pub const BLOCK_NAME: &'static str = "Camera";
+    pub const BINDING: u32 = 0;
+    /// 3 mat4, a vec2 and a float rounded up to 16 bytes
+    pub const SIZE: usize = 208;
+
+    /// a buffer for the block attached to `CameraData::BINDING`
+    #[track_caller]
+    pub fn create_buffer() -> UniformBuffer {
+        UniformBuffer::new(Self::SIZE, Self::BINDING)
+    }
+
+    /// same order as the glsl block, view_projection is precomputed so shaders don't have to
+    pub fn to_std140(&self) -> Vec<u8> {
+        Std140Writer::default()
+            .mat4(&self.view)
+            .mat4(&self.projection)
+            .mat4(&(self.projection * self.view))
+            .vec2(&self.resolution)
+            .float(self.time)
+            .finish()
+    }
+}
+
+/// identity matrices, what pixel space sprites get when the projection is set to an ortho one
+impl Default for CameraData {
+    fn default() -> CameraData {
+        CameraData {
+            view: glm::Mat4::identity(),
+            projection: glm::Mat4::identity(),
+            resolution: glm::vec2(0.0, 0.0),
+            time: 0.0,
+        }
+    }
+}
+
+/// binding point of blocks every shader shares, shaders attach these on their own when linking
+pub fn shared_block_binding(block_name: &str) -> Option<u32> {
+    match block_name {
+        CameraData::BLOCK_NAME => Some(CameraData::BINDING),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn std140_offsets_follow_the_alignment_rules() {
+        let mut writer = Std140Writer::default();
+        writer.float(1.0);
+        //vec3 aligns to 16
+        writer.vec3(&glm::vec3(1.0, 2.0, 3.0));
+        assert_eq!(writer.get_offset(), 28);
+        //a float fits into the padding after a vec3
+        writer.float(4.0);
+        assert_eq!(writer.get_offset(), 32);
+        writer.vec2(&glm::vec2(0.0, 0.0));
+        //mat3 columns are padded to vec4
+        writer.mat3(&glm::Mat3::identity());
+        assert_eq!(writer.get_offset(), 96);
+        writer.float_array(&[1.0, 2.0]);
+        assert_eq!(writer.get_offset(), 128);
+        writer.float(5.0);
+        assert_eq!(writer.finish().len(), 144);
+    }
+
+    #[test]
+    fn camera_block_matches_its_size() {
+        let camera = CameraData {
+            view: glm::Mat4::identity(),
+            projection: glm::Mat4::identity(),
+            resolution: glm::vec2(960.0, 540.0),
+            time: 1.5,
+        };
+        let data = camera.to_std140();
+        assert_eq!(data.len(), CameraData::SIZE);
+        //resolution right after the three matrices, time after it
+        assert_eq!(&data[192..196], &960.0f32.to_ne_bytes());
+        assert_eq!(&data[200..204], &1.5f32.to_ne_bytes());
+    }
+
+    #[test]
+    fn buffers_are_attached_to_their_binding_point() {
+        let (buffer, calls) = with_mock_gl(|| {
+            let buffer = UniformBuffer::new(32, 2);
+            buffer.set_sub_data(16, &[1, 2, 3, 4]);
+            buffer
+        });
+        let id = buffer.id;
+
+        assert!(calls.contains(&GlCall::BindBufferBase {
+            target: gl::UNIFORM_BUFFER,
+            index: 2,
+            buffer: id,
+        }));
+        assert_eq!(
+            calls.last(),
+            Some(&GlCall::BufferSubData {
+                target: gl::UNIFORM_BUFFER,
+                offset: 16,
+                data: vec![1, 2, 3, 4],
+            })
+        );
+    }
+}
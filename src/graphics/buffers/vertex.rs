@@ -0,0 +1,264 @@
+use super::vertex_buffer_layout::VertexBufferLayout;
+
+/// a struct describing one vertex, usually declared with `vertex!`
+pub trait Vertex: Copy {
+    fn layout() -> VertexBufferLayout;
+}
+
+/// what a `VertexBuffer` can hold, raw floats with a layout built by hand or `Vertex` structs
+pub trait VertexData: Copy {}
+
+impl VertexData for f32 {}
+
+impl<V: Vertex> VertexData for V {}
+
+/// declares a `#[repr(C)]` vertex struct and implements `Vertex` for it
+///
+/// every field needs `#[location = N]` for the shader attribute it feeds and can have
+/// `#[normalized]`, `#[integer]` or `#[float]` to pick the `AttributeMode` instead of the
+/// default of the component type. they can come in any order, mixed with doc comments and
+/// other attributes which are kept on the field. `Clone` and `Copy` are derived, field types
+/// are anything implementing `VertexField`: scalars, arrays, glm vectors and the packed
+/// `vertex_formats`
+///
+/// ```ignore
+/// vertex! {
+///     pub struct ColorVertex {
+///         #[location = 0]
+///         pub position: [f32; 2],
+///         #[location = 1]
+///         #[normalized]
+///         pub color: [u8; 4],
+///         #[integer]
+///         /// indices into the bone palette
+///         #[location = 2]
+///         pub bones: glm::U16Vec4,
+///     }
+/// }
+/// va.add_buffer(&VertexBuffer::new(&vertices), &ColorVertex::layout());
+/// ```
+#[macro_export]
+macro_rules! vertex {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident { $($fields:tt)* }
+    ) => {
+        $crate::vertex!(@field [$(#[$meta])* $vis struct $name] $name [] [] [] [] [] $($fields)*);
+    };
+
+    //fields are munched one attribute at a time, the state is
+    //[struct header] name [fields so far] [layout so far] [kept attributes] [location] [mode]
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt []
+        $mode:tt #[location = $location:literal] $($rest:tt)*) => {
+        $crate::vertex!(@field $head $name $fields $layout $attrs [$location] $mode $($rest)*);
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt [$first:literal]
+        $mode:tt #[location = $location:literal] $($rest:tt)*) => {
+        compile_error!(concat!(
+            "a vertex field can't have two locations, got ",
+            stringify!($first),
+            " and ",
+            stringify!($location)
+        ));
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt $location:tt
+        $mode:tt #[normalized] $($rest:tt)*) => {
+        $crate::vertex!(@field $head $name $fields $layout $attrs $location [normalized] $($rest)*);
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt $location:tt
+        $mode:tt #[integer] $($rest:tt)*) => {
+        $crate::vertex!(@field $head $name $fields $layout $attrs $location [integer] $($rest)*);
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt $location:tt
+        $mode:tt #[float] $($rest:tt)*) => {
+        $crate::vertex!(@field $head $name $fields $layout $attrs $location [float] $($rest)*);
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt [$($attrs:tt)*] $location:tt
+        $mode:tt #[$($attr:tt)*] $($rest:tt)*) => {
+        $crate::vertex!(@field $head $name $fields $layout [$($attrs)* #[$($attr)*]] $location $mode $($rest)*);
+    };
+    (@field $head:tt $name:ident [$($fields:tt)*] [$($layout:tt)*] [$($attrs:tt)*] [$location:literal]
+        [$($mode:ident)?] $field_vis:vis $field:ident: $type_:ty $(, $($rest:tt)*)?) => {
+        $crate::vertex!(
+            @field $head $name
+            [$($fields)* $($attrs)* $field_vis $field: $type_,]
+            [$($layout)* ($field, $type_, $location, [$($mode)?])]
+            [] [] [] $($($rest)*)?
+        );
+    };
+    (@field $head:tt $name:ident $fields:tt $layout:tt $attrs:tt []
+        $mode:tt $field_vis:vis $field:ident $($rest:tt)*) => {
+        compile_error!(concat!(
+            "vertex field `",
+            stringify!($field),
+            "` needs a #[location = N]"
+        ));
+    };
+    (@field [$($head:tt)*] $name:ident [$($fields:tt)*]
+        [$(($field:ident, $type_:ty, $location:literal, [$($mode:ident)?]))*] [] [] []) => {
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        $($head)* {
+            $($fields)*
+        }
+
+        impl $crate::graphics::buffers::vertex::Vertex for $name {
+            fn layout() -> $crate::graphics::buffers::vertex_buffer_layout::VertexBufferLayout {
+                let mut layout =
+                    $crate::graphics::buffers::vertex_buffer_layout::VertexBufferLayout::with_stride(
+                        std::mem::size_of::<$name>() as i32,
+                    );
+                $(
+                    layout.push_field::<$type_>(
+                        $location,
+                        std::mem::offset_of!($name, $field) as i32,
+                        $crate::vertex!(@mode $($mode)?),
+                    );
+                )*
+                layout
+            }
+        }
+    };
+
+    (@mode) => {
+        None
+    };
+    (@mode normalized) => {
+        Some($crate::graphics::buffers::vertex_buffer_layout::AttributeMode::Normalized)
+    };
+    (@mode integer) => {
+        Some($crate::graphics::buffers::vertex_buffer_layout::AttributeMode::Integer)
+    };
+    (@mode float) => {
+        Some($crate::graphics::buffers::vertex_buffer_layout::AttributeMode::Float)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers::vertex_buffer_layout::{AttributeMode, VertexBufferElement};
+    use crate::graphics::buffers::vertex_formats::{Half, Int2101010};
+
+    crate::vertex! {
+        struct TestVertex {
+            #[location = 0]
+            position: [f32; 3],
+            /// packed rgba
+            #[location = 2]
+            #[normalized]
+            color: [u8; 4],
+            #[location = 1]
+            layer: f32,
+        }
+    }
+
+    crate::vertex! {
+        struct PackedVertex {
+            #[location = 0]
+            position: glm::Vec3,
+            #[location = 1]
+            normal: Int2101010,
+            #[location = 2]
+            uv: [Half; 2],
+            #[location = 3]
+            #[integer]
+            bones: glm::U16Vec4,
+            #[location = 4]
+            #[float]
+            weights: [u8; 4],
+        }
+    }
+
+    crate::vertex! {
+        /// attributes in any order
+        struct ShuffledVertex {
+            #[normalized]
+            /// rgba
+            #[location = 1]
+            #[allow(dead_code)]
+            color: [u8; 4],
+            /// in pixels
+            #[location = 0]
+            position: [f32; 2]
+        }
+    }
+
+    #[test]
+    fn layouts_follow_the_struct_fields() {
+        let layout = TestVertex::layout();
+
+        assert_eq!(layout.stride, 20);
+        assert_eq!(
+            layout.elements,
+            [
+                VertexBufferElement {
+                    count: 3,
+                    type_: gl::FLOAT,
+                    mode: AttributeMode::Float,
+                    location: 0,
+                    offset: 0,
+                },
+                VertexBufferElement {
+                    count: 4,
+                    type_: gl::UNSIGNED_BYTE,
+                    mode: AttributeMode::Normalized,
+                    location: 2,
+                    offset: 12,
+                },
+                VertexBufferElement {
+                    count: 1,
+                    type_: gl::FLOAT,
+                    mode: AttributeMode::Float,
+                    location: 1,
+                    offset: 16,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn glm_vectors_and_packed_formats_have_their_own_types() {
+        let elements: Vec<(u32, i32, u32, AttributeMode, i32)> = PackedVertex::layout()
+            .elements
+            .iter()
+            .map(|element| {
+                (
+                    element.location,
+                    element.count,
+                    element.type_,
+                    element.mode,
+                    element.offset,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            elements,
+            [
+                (0, 3, gl::FLOAT, AttributeMode::Float, 0),
+                (1, 4, gl::INT_2_10_10_10_REV, AttributeMode::Normalized, 12),
+                (2, 2, gl::HALF_FLOAT, AttributeMode::Float, 16),
+                (3, 4, gl::UNSIGNED_SHORT, AttributeMode::Integer, 20),
+                (4, 4, gl::UNSIGNED_BYTE, AttributeMode::Float, 28),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_can_come_in_any_order() {
+        let elements: Vec<(u32, AttributeMode, i32)> = ShuffledVertex::layout()
+            .elements
+            .iter()
+            .map(|element| (element.location, element.mode, element.offset))
+            .collect();
+
+        assert_eq!(
+            elements,
+            [
+                (1, AttributeMode::Normalized, 0),
+                (0, AttributeMode::Float, 4)
+            ]
+        );
+    }
+}
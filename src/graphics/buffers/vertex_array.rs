@@ -1,43 +1,100 @@
+use super::stream_buffer::StreamBuffer;
 use super::vertex_buffer::VertexBuffer;
-use super::vertex_buffer_layout::{VertexBufferElement, VertexBufferLayout};
-
+use super::vertex_buffer_layout::{AttributeMode, VertexBufferElement, VertexBufferLayout};
+use crate::graphics::gl_resource;
+use crate::graphics::shader::Shader;
+use crate::graphics::shader_reflection::{glsl_type_name, AttributeInfo};
+use crate::graphics::vertex_layout_error::VertexLayoutError;
+use std::cell::Cell;
 
 pub struct VertexArray {
     id: u32,
+    /// every element of the buffers added so far, to check shaders against
+    elements: Vec<VertexBufferElement>,
+    /// the last program that passed `bind_with`, 0 for none
+    checked_program: Cell<u32>,
 }
 
 impl VertexArray {
+    #[track_caller]
     pub fn new() -> VertexArray {
         unsafe {
             let mut id = 0;
             gl::GenVertexArrays(1, &mut id);
-            VertexArray { id }
+            gl_resource::track("VertexArray", id, 0);
+            VertexArray {
+                id,
+                elements: Vec::new(),
+                checked_program: Cell::new(0),
+            }
         }
     }
 
-    pub fn add_buffer(&self, buffer: &VertexBuffer, layout: &VertexBufferLayout) {
+    pub fn add_buffer(&mut self, buffer: &VertexBuffer, layout: &VertexBufferLayout) {
         buffer.bind();
+        self.set_attributes(layout);
+        buffer.unbind();
+    }
+
+    /// reads vertices from a ring buffer, draws pick their part with a base vertex
+    pub fn add_stream_buffer(&mut self, buffer: &StreamBuffer, layout: &VertexBufferLayout) {
+        buffer.bind();
+        self.set_attributes(layout);
+        buffer.unbind();
+    }
+
+    /// points the attributes at the bound GL_ARRAY_BUFFER
+    fn set_attributes(&mut self, layout: &VertexBufferLayout) {
         self.bind();
 
-        let mut offset = 0;
-        for (i, element) in layout.elements.iter().enumerate() {
+        for element in &layout.elements {
+            let offset = element.offset as usize as *const std::ffi::c_void;
             unsafe {
-                gl::EnableVertexAttribArray(i as u32);
-                gl::VertexAttribPointer(
-                    i as u32,
-                    element.count,
-                    element.type_,
-                    element.normalized as u8,
-                    layout.stride,
-                    offset as *const std::ffi::c_void,
-                );
+                gl::EnableVertexAttribArray(element.location);
+                if element.mode == AttributeMode::Integer {
+                    //glVertexAttribPointer would convert them to float
+                    gl::VertexAttribIPointer(
+                        element.location,
+                        element.count,
+                        element.type_,
+                        layout.stride,
+                        offset,
+                    );
+                } else {
+                    gl::VertexAttribPointer(
+                        element.location,
+                        element.count,
+                        element.type_,
+                        (element.mode == AttributeMode::Normalized) as u8,
+                        layout.stride,
+                        offset,
+                    );
+                }
             }
-
-            offset += element.count * VertexBufferElement::size_of_type(element.type_);
         }
+        self.elements.extend_from_slice(&layout.elements);
 
         self.unbind();
-        buffer.unbind();
+    }
+
+    /// makes sure every attribute `shader` reads is fed by one of the buffers
+    ///
+    /// buffers may have fewer components than the attribute, gl fills in the rest from (0, 0, 0, 1).
+    /// packed 2_10_10_10 elements always have 4 components but may feed a vec3, dropping the w.
+    /// int and uint attributes need `AttributeMode::Integer` elements, float ones any other mode
+    pub fn check_attributes(&self, shader: &Shader) -> Result<(), VertexLayoutError> {
+        check_layout(&self.elements, shader.get_attributes())
+    }
+
+    /// binds for drawing with `shader` after `check_attributes`, which only runs when the
+    /// program changes. nothing is bound when the check fails
+    pub fn bind_with(&self, shader: &Shader) -> Result<(), VertexLayoutError> {
+        if self.checked_program.get() != shader.get_id() {
+            self.check_attributes(shader)?;
+            self.checked_program.set(shader.get_id());
+        }
+        self.bind();
+        Ok(())
     }
 
     pub fn bind(&self) {
@@ -52,3 +109,257 @@ impl VertexArray {
         }
     }
 }
+
+/// checks `elements` against the attributes a program reads, see `VertexArray::check_attributes`
+pub fn check_layout(
+    elements: &[VertexBufferElement],
+    attributes: &[AttributeInfo],
+) -> Result<(), VertexLayoutError> {
+    for attribute in attributes {
+        //built ins like gl_VertexID have no location
+        if attribute.location < 0 {
+            continue;
+        }
+        let (components, columns) = attribute_shape(attribute.type_);
+        //matrices and arrays take up one location per column or element
+        for location in attribute.location..attribute.location + columns * attribute.size {
+            let element = elements
+                .iter()
+                .find(|element| element.location as i32 == location)
+                .ok_or_else(|| VertexLayoutError::MissingAttribute {
+                    name: attribute.name.clone(),
+                    location,
+                })?;
+            if (element.mode == AttributeMode::Integer) != is_integer_attribute(attribute.type_) {
+                return Err(VertexLayoutError::ComponentTypeMismatch {
+                    name: attribute.name.clone(),
+                    location,
+                    glsl_type: glsl_type_name(attribute.type_),
+                    mode: element.mode,
+                });
+            }
+            if used_components(element) > components {
+                return Err(VertexLayoutError::TooManyComponents {
+                    name: attribute.name.clone(),
+                    location,
+                    glsl_type: glsl_type_name(attribute.type_),
+                    count: element.count,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// components an element needs the attribute to have, the w of packed formats can be dropped
+fn used_components(element: &VertexBufferElement) -> i32 {
+    match element.type_ {
+        gl::INT_2_10_10_10_REV | gl::UNSIGNED_INT_2_10_10_10_REV => 3,
+        _ => element.count,
+    }
+}
+
+fn is_integer_attribute(type_: u32) -> bool {
+    matches!(
+        type_,
+        gl::INT
+            | gl::INT_VEC2
+            | gl::INT_VEC3
+            | gl::INT_VEC4
+            | gl::UNSIGNED_INT
+            | gl::UNSIGNED_INT_VEC2
+            | gl::UNSIGNED_INT_VEC3
+            | gl::UNSIGNED_INT_VEC4
+    )
+}
+
+/// (components per location, locations) of an attribute type
+pub fn attribute_shape(type_: u32) -> (i32, i32) {
+    match type_ {
+        gl::FLOAT | gl::INT | gl::UNSIGNED_INT => (1, 1),
+        gl::FLOAT_VEC2 | gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (2, 1),
+        gl::FLOAT_VEC3 | gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (3, 1),
+        gl::FLOAT_MAT2 => (2, 2),
+        gl::FLOAT_MAT3 => (3, 3),
+        gl::FLOAT_MAT4 => (4, 4),
+        _ => (4, 1),
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        if gl_resource::release("VertexArray", self.id) {
+            unsafe { gl::DeleteVertexArrays(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers::vertex_formats::Int2101010;
+    use crate::testing::mock_gl::{set_active_attributes, with_mock_gl, GlCall};
+
+    #[test]
+    fn add_buffer_sets_up_interleaved_attributes() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut va = VertexArray::new();
+            let vb = VertexBuffer::new(&[0.0; 12]);
+            let mut layout = VertexBufferLayout::new();
+            layout.push::<f32>(2);
+            layout.push::<u8>(4);
+            va.add_buffer(&vb, &layout);
+        });
+
+        let attributes: Vec<&GlCall> = calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    GlCall::EnableVertexAttribArray(_) | GlCall::VertexAttribPointer { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                &GlCall::EnableVertexAttribArray(0),
+                &GlCall::VertexAttribPointer {
+                    index: 0,
+                    size: 2,
+                    type_: gl::FLOAT,
+                    normalized: false,
+                    stride: 12,
+                    offset: 0,
+                },
+                &GlCall::EnableVertexAttribArray(1),
+                &GlCall::VertexAttribPointer {
+                    index: 1,
+                    size: 4,
+                    type_: gl::UNSIGNED_BYTE,
+                    normalized: true,
+                    stride: 12,
+                    offset: 8,
+                },
+            ]
+        );
+    }
+
+    fn shader_reading(attributes: &[(&str, u32, i32)]) -> Shader {
+        set_active_attributes(attributes);
+        Shader::new("res/shaders").unwrap()
+    }
+
+    #[test]
+    fn attributes_the_buffers_dont_feed_are_reported() {
+        let (result, _) = with_mock_gl(|| {
+            let mut va = VertexArray::new();
+            let vb = VertexBuffer::new(&[0.0; 12]);
+            let mut layout = VertexBufferLayout::new();
+            layout.push::<f32>(2);
+            va.add_buffer(&vb, &layout);
+            let shader = shader_reading(&[
+                ("a_Position", gl::FLOAT_VEC4, 1),
+                ("a_TexCoord", gl::FLOAT_VEC2, 1),
+            ]);
+            va.check_attributes(&shader)
+        });
+
+        assert_eq!(
+            result,
+            Err(VertexLayoutError::MissingAttribute {
+                name: "a_TexCoord".to_string(),
+                location: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn attributes_can_have_more_components_than_the_buffer() {
+        let (results, _) = with_mock_gl(|| {
+            let mut va = VertexArray::new();
+            let vb = VertexBuffer::new(&[0.0; 12]);
+            let mut layout = VertexBufferLayout::new();
+            layout.push::<f32>(3);
+            va.add_buffer(&vb, &layout);
+            let wider = shader_reading(&[("a_Position", gl::FLOAT_VEC4, 1)]);
+            let narrower = shader_reading(&[("a_Position", gl::FLOAT_VEC2, 1)]);
+            (va.check_attributes(&wider), va.check_attributes(&narrower))
+        });
+
+        assert_eq!(results.0, Ok(()));
+        assert_eq!(
+            results.1,
+            Err(VertexLayoutError::TooManyComponents {
+                name: "a_Position".to_string(),
+                location: 0,
+                glsl_type: "vec2",
+                count: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn packed_normals_can_feed_a_vec3() {
+        let (results, _) = with_mock_gl(|| {
+            let mut va = VertexArray::new();
+            let vb = VertexBuffer::new(&[0.0; 12]);
+            let mut layout = VertexBufferLayout::new();
+            layout.push::<f32>(3);
+            layout.push::<Int2101010>(1);
+            va.add_buffer(&vb, &layout);
+            let vec3 = shader_reading(&[
+                ("a_Position", gl::FLOAT_VEC3, 1),
+                ("a_Normal", gl::FLOAT_VEC3, 1),
+            ]);
+            let vec2 = shader_reading(&[
+                ("a_Position", gl::FLOAT_VEC3, 1),
+                ("a_Normal", gl::FLOAT_VEC2, 1),
+            ]);
+            (va.check_attributes(&vec3), va.check_attributes(&vec2))
+        });
+
+        assert_eq!(results.0, Ok(()));
+        assert_eq!(
+            results.1,
+            Err(VertexLayoutError::TooManyComponents {
+                name: "a_Normal".to_string(),
+                location: 1,
+                glsl_type: "vec2",
+                count: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn integer_attributes_stay_integers() {
+        let (result, calls) = with_mock_gl(|| {
+            let mut va = VertexArray::new();
+            let vb = VertexBuffer::new(&[0.0; 12]);
+            let mut layout = VertexBufferLayout::new();
+            layout.push::<f32>(2);
+            layout.push::<u32>(1);
+            va.add_buffer(&vb, &layout);
+            let shader =
+                shader_reading(&[("a_Position", gl::FLOAT_VEC2, 1), ("a_Id", gl::FLOAT, 1)]);
+            va.check_attributes(&shader)
+        });
+
+        assert!(calls.contains(&GlCall::VertexAttribIPointer {
+            index: 1,
+            size: 1,
+            type_: gl::UNSIGNED_INT,
+            stride: 12,
+            offset: 8,
+        }));
+        assert_eq!(
+            result,
+            Err(VertexLayoutError::ComponentTypeMismatch {
+                name: "a_Id".to_string(),
+                location: 1,
+                glsl_type: "float",
+                mode: AttributeMode::Integer,
+            })
+        );
+    }
+}
@@ -1,22 +1,94 @@
 
 
+use super::buffer_usage::{grow_capacity, BufferUsage};
+use super::vertex::VertexData;
+use crate::graphics::gl_resource;
+
 pub struct VertexBuffer {
     id: u32,
+    size: usize,
+    usage: BufferUsage,
 }
 
 impl VertexBuffer {
-    pub fn new(data: &[f32]) -> VertexBuffer {
+    /// creates a buffer holding `data`, floats or structs declared with `vertex!`
+    #[track_caller]
+    pub fn new<V: VertexData>(data: &[V]) -> VertexBuffer {
+        Self::with_usage(data, BufferUsage::Static)
+    }
+
+    /// creates a buffer holding `data`, `usage` tells the driver how often it will change
+    #[track_caller]
+    pub fn with_usage<V: VertexData>(data: &[V], usage: BufferUsage) -> VertexBuffer {
+        Self::create(
+            std::mem::size_of_val(data),
+            data.as_ptr() as *const std::ffi::c_void,
+            usage,
+        )
+    }
+
+    /// creates an empty buffer of `size` bytes for data that is rewritten every frame
+    #[track_caller]
+    pub fn new_dynamic(size: usize) -> VertexBuffer {
+        Self::create(size, std::ptr::null(), BufferUsage::Dynamic)
+    }
+
+    #[track_caller]
+    fn create(size: usize, data: *const std::ffi::c_void, usage: BufferUsage) -> VertexBuffer {
         unsafe {
             let mut id = 0;
             gl::GenBuffers(1, &mut id);
             gl::BindBuffer(gl::ARRAY_BUFFER, id);
-            gl::BufferData(
+            gl::BufferData(gl::ARRAY_BUFFER, size as isize, data, usage.to_gl());
+            gl_resource::track("VertexBuffer", id, size);
+            VertexBuffer { id, size, usage }
+        }
+    }
+
+    /// replaces the contents with `data`
+    ///
+    /// the old storage is orphaned so draws still reading it don't stall the upload, data that
+    /// doesn't fit grows the buffer to at least twice its size
+    pub fn set_data<V: VertexData>(&mut self, data: &[V]) {
+        let bytes = std::mem::size_of_val(data);
+        if bytes > self.size {
+            self.size = grow_capacity(self.size, bytes);
+            gl_resource::retrack("VertexBuffer", self.id, self.id, self.size);
+        }
+        self.orphan();
+        self.set_sub_data(0, data);
+    }
+
+    /// uploads `data` into the buffer starting at `offset` bytes
+    pub fn set_sub_data<V: VertexData>(&self, offset: usize, data: &[V]) {
+        assert!(
+            offset + std::mem::size_of_val(data) <= self.size,
+            "vertex buffer is {} bytes, writing {} bytes at {}",
+            self.size,
+            std::mem::size_of_val(data),
+            offset
+        );
+        self.bind();
+        unsafe {
+            gl::BufferSubData(
                 gl::ARRAY_BUFFER,
-                (data.len() * std::mem::size_of::<f32>()) as isize,
+                offset as isize,
+                std::mem::size_of_val(data) as isize,
                 data.as_ptr() as *const std::ffi::c_void,
-                gl::STATIC_DRAW,
             );
-            VertexBuffer { id }
+        }
+    }
+
+    /// gives the buffer fresh storage of the same size, the contents are undefined afterwards
+    pub fn orphan(&self) {
+        self.bind();
+        unsafe {
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                self.size as isize,
+                std::ptr::null(),
+                self.usage.to_gl(),
+            );
         }
     }
 
@@ -31,4 +103,87 @@ impl VertexBuffer {
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
     }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// capacity in bytes
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get_usage(&self) -> BufferUsage {
+        self.usage
+    }
+}
+
+impl Drop for VertexBuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("VertexBuffer", self.id) {
+            unsafe { gl::DeleteBuffers(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn set_data_orphans_before_uploading() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut vb = VertexBuffer::new_dynamic(16);
+            vb.set_data(&[1.0, 2.0]);
+            vb
+        });
+
+        let id = calls.iter().find_map(|call| match call {
+            GlCall::GenBuffers(ids) => Some(ids[0]),
+            _ => None,
+        });
+        assert_eq!(
+            calls[calls.len() - 3..],
+            [
+                GlCall::BufferData {
+                    target: gl::ARRAY_BUFFER,
+                    size: 16,
+                    data: None,
+                    usage: gl::DYNAMIC_DRAW,
+                },
+                GlCall::BindBuffer(gl::ARRAY_BUFFER, id.unwrap()),
+                GlCall::BufferSubData {
+                    target: gl::ARRAY_BUFFER,
+                    offset: 0,
+                    data: [1.0f32, 2.0].iter().flat_map(|v| v.to_ne_bytes()).collect(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn set_data_grows_buffers_that_are_too_small() {
+        let (sizes, calls) = with_mock_gl(|| {
+            let mut vb = VertexBuffer::new_dynamic(16);
+            vb.set_data(&[0.0; 5]);
+            let grown = vb.get_size();
+            vb.set_data(&[0.0; 20]);
+            (grown, vb.get_size(), vb)
+        });
+
+        assert_eq!((sizes.0, sizes.1), (32, 80));
+        assert!(calls.contains(&GlCall::BufferData {
+            target: gl::ARRAY_BUFFER,
+            size: 80,
+            data: None,
+            usage: gl::DYNAMIC_DRAW,
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "vertex buffer is 16 bytes, writing 8 bytes at 12")]
+    fn sub_data_has_to_fit() {
+        with_mock_gl(|| VertexBuffer::new_dynamic(16).set_sub_data(12, &[0.0; 2]));
+    }
 }
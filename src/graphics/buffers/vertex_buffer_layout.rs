@@ -1,24 +1,62 @@
 
+/// how the shader sees the values of an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeMode {
+    /// converted to float as they are, for `float` and `vec` attributes
+    Float,
+    /// integers mapped to 0..1 (unsigned) or -1..1 (signed), for `float` and `vec` attributes
+    Normalized,
+    /// kept as integers with glVertexAttribIPointer, for `int`, `uint`, `ivec` and `uvec`
+    Integer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct VertexBufferElement {
     pub count: i32,
     pub type_: u32,
-    pub normalized: bool,
+    pub mode: AttributeMode,
+    /// the attribute location the element feeds
+    pub location: u32,
+    /// bytes from the start of the vertex
+    pub offset: i32,
 }
 
 impl VertexBufferElement {
     pub fn size_of_type(type_: u32) -> i32 {
         match type_ {
             gl::FLOAT => std::mem::size_of::<f32>() as i32,
+            gl::HALF_FLOAT => std::mem::size_of::<u16>() as i32,
+            gl::INT => std::mem::size_of::<i32>() as i32,
             gl::UNSIGNED_INT => std::mem::size_of::<u32>() as i32,
+            gl::SHORT => std::mem::size_of::<i16>() as i32,
+            gl::UNSIGNED_SHORT => std::mem::size_of::<u16>() as i32,
+            gl::BYTE => std::mem::size_of::<i8>() as i32,
             gl::UNSIGNED_BYTE => std::mem::size_of::<u8>() as i32,
+            //the four components share a u32
+            gl::INT_2_10_10_10_REV | gl::UNSIGNED_INT_2_10_10_10_REV => 1,
             _ => 0,
         }
     }
+
+    /// types glVertexAttribIPointer accepts
+    pub fn is_integer_type(type_: u32) -> bool {
+        matches!(
+            type_,
+            gl::BYTE
+                | gl::UNSIGNED_BYTE
+                | gl::SHORT
+                | gl::UNSIGNED_SHORT
+                | gl::INT
+                | gl::UNSIGNED_INT
+        )
+    }
 }
 
 pub struct VertexBufferLayout {
     pub elements: Vec<VertexBufferElement>,
     pub stride: i32,
+    /// the first byte after the elements, where `push` puts the next one
+    end: i32,
 }
 
 impl VertexBufferLayout {
@@ -26,50 +64,175 @@ impl VertexBufferLayout {
         VertexBufferLayout {
             elements: Vec::new(),
             stride: 0,
+            end: 0,
+        }
+    }
+
+    /// an empty layout for vertices of `stride` bytes, filled with `push_field` or `push`
+    pub fn with_stride(stride: i32) -> VertexBufferLayout {
+        VertexBufferLayout {
+            elements: Vec::new(),
+            stride,
+            end: 0,
         }
     }
 
+    /// appends `count` values of `T` right after the previous element, feeding the next location.
+    /// the stride grows to fit it
     pub fn push<T: VertexAttrib>(&mut self, count: i32) {
-        self.elements.push(VertexBufferElement {
-            type_: T::get_type(),
-            count,
-            normalized: T::is_normalized(),
+        self.push_with_mode::<T>(count, T::get_default_mode());
+    }
+
+    /// like `push` with an explicit mode, e.g. `Float` for u16 positions that aren't normalized
+    pub fn push_with_mode<T: VertexAttrib>(&mut self, count: i32, mode: AttributeMode) {
+        let element = Self::element(T::get_type(), count * T::COMPONENTS, mode);
+        let offset = self.end;
+        self.add(VertexBufferElement {
+            location: self.elements.len() as u32,
+            offset,
+            ..element
+        });
+    }
+
+    /// adds a struct field of type `F` at `offset` bytes, `mode` defaults to the one of the
+    /// component type
+    pub fn push_field<F: VertexField>(
+        &mut self,
+        location: u32,
+        offset: i32,
+        mode: Option<AttributeMode>,
+    ) {
+        let mode = mode.unwrap_or_else(F::Component::get_default_mode);
+        self.add(VertexBufferElement {
+            location,
+            offset,
+            ..Self::element(F::Component::get_type(), F::COUNT, mode)
         });
-        self.stride += VertexBufferElement::size_of_type(T::get_type()) * count;
+    }
+
+    fn add(&mut self, element: VertexBufferElement) {
+        let size = VertexBufferElement::size_of_type(element.type_) * element.count;
+        self.end = self.end.max(element.offset + size);
+        self.stride = self.stride.max(self.end);
+        self.elements.push(element);
+    }
+
+    fn element(type_: u32, count: i32, mode: AttributeMode) -> VertexBufferElement {
+        assert!(
+            mode != AttributeMode::Integer || VertexBufferElement::is_integer_type(type_),
+            "0x{:X} can't be read as an integer attribute",
+            type_
+        );
+        VertexBufferElement {
+            count,
+            type_,
+            mode,
+            location: 0,
+            offset: 0,
+        }
     }
 }
 
+/// a component type of vertex data
 pub trait VertexAttrib {
+    /// how many components one value holds, only packed formats have more than one
+    const COMPONENTS: i32 = 1;
+
     fn get_type() -> u32;
-    fn is_normalized() -> bool;
+    fn get_default_mode() -> AttributeMode;
 }
 
-impl VertexAttrib for f32 {
-    fn get_type() -> u32 {
-        gl::FLOAT
-    }
+/// a type a field of a `Vertex` can have, `COUNT` components of `Component`
+pub trait VertexField {
+    type Component: VertexAttrib;
+    const COUNT: i32;
+}
 
-    fn is_normalized() -> bool {
-        false
-    }
+impl<T: VertexAttrib> VertexField for T {
+    type Component = T;
+    const COUNT: i32 = T::COMPONENTS;
 }
 
-impl VertexAttrib for u32 {
-    fn get_type() -> u32 {
-        gl::UNSIGNED_INT
-    }
+impl<T: VertexAttrib, const N: usize> VertexField for [T; N] {
+    type Component = T;
+    const COUNT: i32 = N as i32;
+}
 
-    fn is_normalized() -> bool {
-        false
-    }
+impl<T: VertexAttrib, const N: usize> VertexField for glm::TVec<T, N> {
+    type Component = T;
+    const COUNT: i32 = N as i32;
 }
 
-impl VertexAttrib for u8 {
-    fn get_type() -> u32 {
-        gl::UNSIGNED_BYTE
+macro_rules! vertex_attrib {
+    ($($type_:ty => $gl_type:expr, $mode:ident;)*) => {
+        $(
+            impl VertexAttrib for $type_ {
+                fn get_type() -> u32 {
+                    $gl_type
+                }
+
+                fn get_default_mode() -> AttributeMode {
+                    AttributeMode::$mode
+                }
+            }
+        )*
+    };
+}
+
+//small integers are usually colors, normals or uvs, 32 bit ones ids and indices
+vertex_attrib! {
+    f32 => gl::FLOAT, Float;
+    i32 => gl::INT, Integer;
+    u32 => gl::UNSIGNED_INT, Integer;
+    i16 => gl::SHORT, Normalized;
+    u16 => gl::UNSIGNED_SHORT, Normalized;
+    i8 => gl::BYTE, Normalized;
+    u8 => gl::UNSIGNED_BYTE, Normalized;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers::vertex_formats::Int2101010;
+
+    #[test]
+    fn push_uses_the_default_mode_of_the_type() {
+        let mut layout = VertexBufferLayout::new();
+        layout.push::<f32>(3);
+        layout.push::<i16>(2);
+        layout.push::<u32>(1);
+        layout.push::<Int2101010>(1);
+
+        let elements: Vec<(i32, AttributeMode, i32)> = layout
+            .elements
+            .iter()
+            .map(|element| (element.count, element.mode, element.offset))
+            .collect();
+        assert_eq!(
+            elements,
+            [
+                (3, AttributeMode::Float, 0),
+                (2, AttributeMode::Normalized, 12),
+                (1, AttributeMode::Integer, 16),
+                (4, AttributeMode::Normalized, 20),
+            ]
+        );
+        assert_eq!(layout.stride, 24);
+    }
+
+    #[test]
+    fn push_goes_after_fields_and_keeps_a_fixed_stride() {
+        let mut layout = VertexBufferLayout::with_stride(32);
+        layout.push_field::<[f32; 2]>(0, 0, None);
+        layout.push::<f32>(3);
+
+        assert_eq!(layout.elements[1].offset, 8);
+        assert_eq!(layout.stride, 32);
     }
 
-    fn is_normalized() -> bool {
-        true
+    #[test]
+    #[should_panic(expected = "can't be read as an integer attribute")]
+    fn floats_cant_be_integer_attributes() {
+        VertexBufferLayout::new().push_with_mode::<f32>(2, AttributeMode::Integer);
     }
 }
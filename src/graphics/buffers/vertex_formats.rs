@@ -0,0 +1,193 @@
+//! compact vertex component types gl reads directly
+//!
+//! - `Half` is a 16 bit float, half the size of an f32 for uvs or colors that don't need the
+//!   precision
+//! - `Int2101010` and `UInt2101010` pack four components into a u32, 10 bits for xyz and 2 for w.
+//!   the signed one is the usual choice for normals and tangents
+
+use super::vertex_buffer_layout::{AttributeMode, VertexAttrib};
+
+/// an IEEE 754 half precision float, read as GL_HALF_FLOAT
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Half(u16);
+
+impl Half {
+    /// rounds to the nearest half with ties to even like a cast would, values out of range
+    /// become infinity
+    pub fn from_f32(value: f32) -> Half {
+        let bits = value.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exponent = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x7f_ffff;
+
+        if exponent == 0xff {
+            //infinity stays infinity, nan keeps a mantissa bit so it stays nan
+            let nan = if mantissa != 0 { 0x200 } else { 0 };
+            return Half(sign | 0x7c00 | nan);
+        }
+        let exponent = exponent - 127 + 15;
+        if exponent >= 0x1f {
+            return Half(sign | 0x7c00);
+        }
+        if exponent <= 0 {
+            //subnormal, the implicit leading bit has to be shifted in
+            if exponent < -10 {
+                return Half(sign);
+            }
+            let mantissa = mantissa | 0x80_0000;
+            return Half(sign | round_shift(mantissa, (14 - exponent) as u32) as u16);
+        }
+        //a carry out of the mantissa correctly bumps the exponent
+        let half = ((exponent as u32) << 10) + round_shift(mantissa, 13);
+        Half(sign | half as u16)
+    }
+
+    pub fn from_bits(bits: u16) -> Half {
+        Half(bits)
+    }
+
+    pub fn to_bits(self) -> u16 {
+        self.0
+    }
+}
+
+/// `value >> shift` rounded to the nearest integer, ties go to the even one
+fn round_shift(value: u32, shift: u32) -> u32 {
+    let shifted = value >> shift;
+    let rest = value & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if rest > half || (rest == half && shifted & 1 == 1) {
+        shifted + 1
+    } else {
+        shifted
+    }
+}
+
+impl From<f32> for Half {
+    fn from(value: f32) -> Half {
+        Half::from_f32(value)
+    }
+}
+
+impl VertexAttrib for Half {
+    fn get_type() -> u32 {
+        gl::HALF_FLOAT
+    }
+
+    fn get_default_mode() -> AttributeMode {
+        AttributeMode::Float
+    }
+}
+
+/// four signed components in GL_INT_2_10_10_10_REV, x in the lowest bits
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Int2101010(u32);
+
+impl Int2101010 {
+    /// packs values in -1..1, values outside are clamped
+    pub fn from_normalized(value: [f32; 4]) -> Int2101010 {
+        let [x, y, z, w] = value.map(|component| component.clamp(-1.0, 1.0));
+        let pack = |value: f32, max: f32, bits: u32| {
+            ((value * max).round() as i32 as u32) & ((1 << bits) - 1)
+        };
+        Int2101010(
+            pack(x, 511.0, 10)
+                | pack(y, 511.0, 10) << 10
+                | pack(z, 511.0, 10) << 20
+                | pack(w, 1.0, 2) << 30,
+        )
+    }
+
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl VertexAttrib for Int2101010 {
+    const COMPONENTS: i32 = 4;
+
+    fn get_type() -> u32 {
+        gl::INT_2_10_10_10_REV
+    }
+
+    fn get_default_mode() -> AttributeMode {
+        AttributeMode::Normalized
+    }
+}
+
+/// four unsigned components in GL_UNSIGNED_INT_2_10_10_10_REV, x in the lowest bits
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UInt2101010(u32);
+
+impl UInt2101010 {
+    /// packs values in 0..1, values outside are clamped
+    pub fn from_normalized(value: [f32; 4]) -> UInt2101010 {
+        let [x, y, z, w] = value.map(|component| component.clamp(0.0, 1.0));
+        let pack = |value: f32, max: f32| (value * max).round() as u32;
+        UInt2101010(
+            pack(x, 1023.0) | pack(y, 1023.0) << 10 | pack(z, 1023.0) << 20 | pack(w, 3.0) << 30,
+        )
+    }
+
+    pub fn to_bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl VertexAttrib for UInt2101010 {
+    const COMPONENTS: i32 = 4;
+
+    fn get_type() -> u32 {
+        gl::UNSIGNED_INT_2_10_10_10_REV
+    }
+
+    fn get_default_mode() -> AttributeMode {
+        AttributeMode::Normalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn halves_round_to_the_nearest_value() {
+        let halves: Vec<u16> = [1.0, -2.0, 0.5, 65504.0, 1e6, 2f32.powi(-24), 0.0, f32::NAN]
+            .iter()
+            .map(|&value| Half::from_f32(value).to_bits())
+            .collect();
+
+        assert_eq!(
+            halves,
+            [0x3c00, 0xc000, 0x3800, 0x7bff, 0x7c00, 0x0001, 0x0000, 0x7e00]
+        );
+    }
+
+    #[test]
+    fn halfway_values_round_to_even() {
+        //1 + 2^-11 is halfway between 1 and the next half, 1 + 3 * 2^-11 between two odd and even ones
+        let halves: Vec<u16> = [
+            2f32.powi(-25),
+            3.0 * 2f32.powi(-25),
+            1.0 + 2f32.powi(-11),
+            1.0 + 3.0 * 2f32.powi(-11),
+        ]
+        .iter()
+        .map(|&value| Half::from_f32(value).to_bits())
+        .collect();
+
+        assert_eq!(halves, [0x0000, 0x0002, 0x3c00, 0x3c02]);
+    }
+
+    #[test]
+    fn packed_components_start_at_the_lowest_bits() {
+        let signed = Int2101010::from_normalized([1.0, -1.0, 0.0, -1.0]).to_bits();
+        let unsigned = UInt2101010::from_normalized([1.0, 0.0, 0.5, 1.0]).to_bits();
+
+        assert_eq!(signed, 0x1ff | 0x201 << 10 | 0b11 << 30);
+        assert_eq!(unsigned, 0x3ff | 512 << 20 | 0b11 << 30);
+    }
+}
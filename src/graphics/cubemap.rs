@@ -0,0 +1,176 @@
+use super::gl_resource;
+use super::texture::{load_rgba, texture_bytes, Texture, TextureFormat};
+use super::texture_error::TextureError;
+use super::texture_spec::TextureSpec;
+
+/// file names `Cubemap::from_directory` looks for, in the +X, -X, +Y, -Y, +Z, -Z order gl
+/// numbers the faces in
+pub const FACE_NAMES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+
+/// a GL_TEXTURE_CUBE_MAP for skyboxes and reflections, sampled as `samplerCube` with a direction
+pub struct Cubemap {
+    id: u32,
+    size: i32,
+}
+
+impl Cubemap {
+    /// loads six square RGBA faces in the order of `FACE_NAMES`
+    ///
+    /// cubemap faces start at the top left, so unlike `Texture` they aren't flipped
+    #[track_caller]
+    pub fn new(faces: [&str; 6], spec: &TextureSpec) -> Result<Cubemap, TextureError> {
+        let mut size = None;
+        let mut pixels = Vec::with_capacity(6);
+        for path in faces {
+            let (width, height, face) = load_rgba(path, false)?;
+            let expected = *size.get_or_insert(width);
+            if width != expected || height != expected {
+                return Err(TextureError::SizeMismatch {
+                    path: path.to_string(),
+                    width,
+                    height,
+                    expected_width: expected,
+                    expected_height: expected,
+                });
+            }
+            pixels.push(face);
+        }
+        let size = size.unwrap();
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+            //RGBA rows are always 4 byte aligned
+            for (face, pixels) in pixels.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    0,
+                    gl::RGBA8 as i32,
+                    size,
+                    size,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    pixels.as_ptr() as *const std::ffi::c_void,
+                );
+            }
+            Texture::apply_spec(gl::TEXTURE_CUBE_MAP, spec, 1.0);
+            //directions between faces would otherwise pick up the wrap mode of the spec
+            for wrap in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE as i32);
+            }
+            //filter across face edges instead of clamping at each face
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+        let bytes = texture_bytes(size, size, TextureFormat::RGBA8, spec) * 6;
+        gl_resource::track("Cubemap", id, bytes);
+
+        Ok(Cubemap { id, size })
+    }
+
+    /// loads `<directory>/<face>.<extension>` for every name in `FACE_NAMES`
+    #[track_caller]
+    pub fn from_directory(
+        directory: &str,
+        extension: &str,
+        spec: &TextureSpec,
+    ) -> Result<Cubemap, TextureError> {
+        let paths = FACE_NAMES.map(|face| format!("{}/{}.{}", directory, face, extension));
+        Self::new(paths.each_ref().map(String::as_str), spec)
+    }
+
+    pub fn bind(&self, slot: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// width and height of every face
+    pub fn get_size(&self) -> i32 {
+        self.size
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        if gl_resource::release("Cubemap", self.id) {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+    use crate::testing::scratch_dir;
+    use crate::utils::png_writer;
+
+    fn scratch_faces(name: &str, sizes: [u32; 6]) -> String {
+        let directory = scratch_dir(name);
+        for (face, size) in FACE_NAMES.iter().zip(sizes) {
+            let path = directory.join(format!("{}.png", face));
+            let pixels = vec![128; (size * size * 4) as usize];
+            png_writer::write_png(path.to_str().unwrap(), size, size, &pixels).unwrap();
+        }
+        directory.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn faces_are_uploaded_in_gl_order() {
+        let directory = scratch_faces("cubemap_faces", [4; 6]);
+        let (size, calls) = with_mock_gl(|| {
+            Cubemap::from_directory(&directory, "png", &TextureSpec::default())
+                .unwrap()
+                .get_size()
+        });
+
+        assert_eq!(size, 4);
+        let targets: Vec<u32> = calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::TexImage2D { target, .. } => Some(*target),
+                _ => None,
+            })
+            .collect();
+        let expected: Vec<u32> = (0..6)
+            .map(|face| gl::TEXTURE_CUBE_MAP_POSITIVE_X + face)
+            .collect();
+        assert_eq!(targets, expected);
+        assert!(calls.contains(&GlCall::TexParameteri(
+            gl::TEXTURE_CUBE_MAP,
+            gl::TEXTURE_WRAP_R,
+            gl::CLAMP_TO_EDGE as i32
+        )));
+    }
+
+    #[test]
+    fn faces_have_to_be_the_same_size() {
+        let directory = scratch_faces("cubemap_mismatch", [4, 4, 4, 2, 4, 4]);
+        let (error, _) = with_mock_gl(|| {
+            Cubemap::from_directory(&directory, "png", &TextureSpec::default()).err()
+        });
+
+        assert!(matches!(
+            error,
+            Some(TextureError::SizeMismatch {
+                width: 2,
+                expected_width: 4,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,641 @@
+use super::framebuffer_error::FramebufferError;
+use super::gl_resource;
+use super::texture::{Texture, TextureFormat};
+use super::texture_spec::TextureSpec;
+
+/// depth (and stencil) buffer of a framebuffer, kept in a renderbuffer since it's never sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFormat {
+    Depth24,
+    Depth24Stencil8,
+    Depth32F,
+}
+
+impl DepthFormat {
+    /// (internal format, attachment point)
+    fn gl_formats(&self) -> (u32, u32) {
+        match self {
+            DepthFormat::Depth24 => (gl::DEPTH_COMPONENT24, gl::DEPTH_ATTACHMENT),
+            DepthFormat::Depth24Stencil8 => (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL_ATTACHMENT),
+            DepthFormat::Depth32F => (gl::DEPTH_COMPONENT32F, gl::DEPTH_ATTACHMENT),
+        }
+    }
+}
+
+/// what a framebuffer is made of, built like `FramebufferSpec::new(960, 540).samples(4)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramebufferSpec {
+    pub width: i32,
+    pub height: i32,
+    /// one color texture per format, fragment shader output `n` is written to texture `n`
+    pub color_formats: Vec<TextureFormat>,
+    pub depth: Option<DepthFormat>,
+    /// above 1 draws into multisampled renderbuffers that `Framebuffer::resolve` copies into
+    /// the color textures
+    pub samples: i32,
+}
+
+impl FramebufferSpec {
+    /// a single RGBA8 color texture without depth or msaa
+    pub fn new(width: i32, height: i32) -> FramebufferSpec {
+        FramebufferSpec {
+            width,
+            height,
+            color_formats: vec![TextureFormat::RGBA8],
+            depth: None,
+            samples: 1,
+        }
+    }
+
+    /// replaces the color attachments, an empty list makes a depth only framebuffer
+    pub fn color_formats(self, color_formats: &[TextureFormat]) -> FramebufferSpec {
+        FramebufferSpec {
+            color_formats: color_formats.to_vec(),
+            ..self
+        }
+    }
+
+    pub fn depth(self, depth: DepthFormat) -> FramebufferSpec {
+        FramebufferSpec {
+            depth: Some(depth),
+            ..self
+        }
+    }
+
+    pub fn samples(self, samples: i32) -> FramebufferSpec {
+        FramebufferSpec { samples, ..self }
+    }
+}
+
+/// offscreen render target with color textures and an optional depth/stencil buffer
+pub struct Framebuffer {
+    id: u32,
+    spec: FramebufferSpec,
+    color_attachments: Vec<Texture>,
+    /// multisampled color renderbuffers drawn into instead of the textures, empty without msaa
+    msaa_attachments: Vec<u32>,
+    /// 0 without depth
+    depth_attachment: u32,
+    /// holds the color textures when msaa is on, 0 otherwise
+    resolve_id: u32,
+}
+
+fn query(name: u32) -> i32 {
+    let mut value = 0;
+    unsafe { gl::GetIntegerv(name, &mut value) };
+    value
+}
+
+fn color_attachment(index: usize) -> u32 {
+    gl::COLOR_ATTACHMENT0 + index as u32
+}
+
+/// points the draw buffers of the bound framebuffer at its `count` color attachments
+unsafe fn set_draw_buffers(count: usize) {
+    if count == 0 {
+        gl::DrawBuffer(gl::NONE);
+        gl::ReadBuffer(gl::NONE);
+        return;
+    }
+    let buffers: Vec<u32> = (0..count).map(color_attachment).collect();
+    gl::DrawBuffers(count as i32, buffers.as_ptr());
+}
+
+unsafe fn check_status() -> Result<(), FramebufferError> {
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        return Err(FramebufferError::Incomplete { status });
+    }
+    Ok(())
+}
+
+impl Framebuffer {
+    /// a single RGBA8 color texture, what headless rendering and screenshots need
+    #[track_caller]
+    pub fn new(width: i32, height: i32) -> Result<Framebuffer, FramebufferError> {
+        Self::with_spec(FramebufferSpec::new(width, height))
+    }
+
+    #[track_caller]
+    pub fn with_spec(spec: FramebufferSpec) -> Result<Framebuffer, FramebufferError> {
+        let max_attachments = query(gl::MAX_COLOR_ATTACHMENTS).min(query(gl::MAX_DRAW_BUFFERS));
+        if spec.color_formats.len() > max_attachments as usize {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: spec.color_formats.len(),
+                max: max_attachments,
+            });
+        }
+        let max_samples = query(gl::MAX_SAMPLES);
+        if spec.samples > 1 && spec.samples > max_samples {
+            return Err(FramebufferError::TooManySamples {
+                requested: spec.samples,
+                max: max_samples,
+            });
+        }
+
+        let mut framebuffer = Framebuffer {
+            id: 0,
+            spec,
+            color_attachments: Vec::new(),
+            msaa_attachments: Vec::new(),
+            depth_attachment: 0,
+            resolve_id: 0,
+        };
+        framebuffer.create()?;
+        gl_resource::track(
+            "Framebuffer",
+            framebuffer.id,
+            framebuffer.renderbuffer_bytes(),
+        );
+        Ok(framebuffer)
+    }
+
+    /// creates the gl objects for the current spec, nothing is left behind when it fails
+    #[track_caller]
+    fn create(&mut self) -> Result<(), FramebufferError> {
+        let (width, height) = (self.spec.width, self.spec.height);
+        if width <= 0 || height <= 0 {
+            return Err(FramebufferError::InvalidSize { width, height });
+        }
+
+        //creating a framebuffer shouldn't change where the caller is drawing or reading
+        let previous_draw = query(gl::DRAW_FRAMEBUFFER_BINDING) as u32;
+        let previous_read = query(gl::READ_FRAMEBUFFER_BINDING) as u32;
+        let result = unsafe { self.create_objects() };
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous_draw);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read);
+        }
+        if result.is_err() {
+            self.delete();
+        }
+        result
+    }
+
+    #[track_caller]
+    unsafe fn create_objects(&mut self) -> Result<(), FramebufferError> {
+        let (width, height) = (self.spec.width, self.spec.height);
+        let samples = self.spec.samples;
+        let multisampled = samples > 1;
+
+        //each color texture is a regular Texture so it can be drawn or bound like one
+        let texture_spec = TextureSpec::default();
+        for &format in &self.spec.color_formats {
+            let texture = Texture::blank(width, height, format, &texture_spec);
+            self.color_attachments.push(texture);
+        }
+
+        gl::GenFramebuffers(1, &mut self.id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        if multisampled {
+            for (index, format) in self.spec.color_formats.iter().enumerate() {
+                let (internal_format, _, _) = format.gl_formats();
+                let renderbuffer =
+                    Self::create_renderbuffer(samples, internal_format, width, height);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    color_attachment(index),
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+                self.msaa_attachments.push(renderbuffer);
+            }
+        } else {
+            self.attach_textures();
+        }
+
+        if let Some(depth) = self.spec.depth {
+            let (internal_format, attachment) = depth.gl_formats();
+            self.depth_attachment =
+                Self::create_renderbuffer(samples, internal_format, width, height);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                attachment,
+                gl::RENDERBUFFER,
+                self.depth_attachment,
+            );
+        }
+        set_draw_buffers(self.color_attachments.len());
+        check_status()?;
+
+        if multisampled {
+            gl::GenFramebuffers(1, &mut self.resolve_id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.resolve_id);
+            self.attach_textures();
+            set_draw_buffers(self.color_attachments.len());
+            check_status()?;
+        }
+        Ok(())
+    }
+
+    unsafe fn attach_textures(&self) {
+        for (index, texture) in self.color_attachments.iter().enumerate() {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                color_attachment(index),
+                gl::TEXTURE_2D,
+                texture.get_id(),
+                0,
+            );
+        }
+    }
+
+    unsafe fn create_renderbuffer(
+        samples: i32,
+        internal_format: u32,
+        width: i32,
+        height: i32,
+    ) -> u32 {
+        let mut renderbuffer = 0;
+        gl::GenRenderbuffers(1, &mut renderbuffer);
+        gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+        if samples > 1 {
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                samples,
+                internal_format,
+                width,
+                height,
+            );
+        } else {
+            gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width, height);
+        }
+        gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        renderbuffer
+    }
+
+    /// memory of the renderbuffers, the color textures are counted on their own
+    fn renderbuffer_bytes(&self) -> usize {
+        let pixels = (self.spec.width * self.spec.height * self.spec.samples.max(1)) as usize;
+        let mut bytes_per_pixel = 0;
+        if !self.msaa_attachments.is_empty() {
+            for format in &self.spec.color_formats {
+                bytes_per_pixel += format.bytes_per_pixel() as usize;
+            }
+        }
+        if self.depth_attachment != 0 {
+            //every depth format takes 4 bytes
+            bytes_per_pixel += 4;
+        }
+        pixels * bytes_per_pixel
+    }
+
+    /// deletes every gl object, `create` makes them again
+    fn delete(&mut self) {
+        //the textures delete themselves
+        self.color_attachments.clear();
+        let mut renderbuffers = std::mem::take(&mut self.msaa_attachments);
+        renderbuffers.push(self.depth_attachment);
+        renderbuffers.retain(|&id| id != 0);
+        let framebuffers: Vec<u32> = [self.id, self.resolve_id]
+            .into_iter()
+            .filter(|&id| id != 0)
+            .collect();
+        unsafe {
+            if !renderbuffers.is_empty() {
+                gl::DeleteRenderbuffers(renderbuffers.len() as i32, renderbuffers.as_ptr());
+            }
+            if !framebuffers.is_empty() {
+                gl::DeleteFramebuffers(framebuffers.len() as i32, framebuffers.as_ptr());
+            }
+        }
+        self.depth_attachment = 0;
+        self.id = 0;
+        self.resolve_id = 0;
+    }
+
+    /// recreates the attachments at the new size, e.g. when the window is resized
+    ///
+    /// the old color textures are deleted, anything holding on to their ids has to fetch them
+    /// again. when the new attachments fail the old ones are kept
+    #[track_caller]
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), FramebufferError> {
+        if (width, height) == (self.spec.width, self.spec.height) {
+            return Ok(());
+        }
+        let mut resized = Framebuffer {
+            id: 0,
+            spec: FramebufferSpec {
+                width,
+                height,
+                ..self.spec.clone()
+            },
+            color_attachments: Vec::new(),
+            msaa_attachments: Vec::new(),
+            depth_attachment: 0,
+            resolve_id: 0,
+        };
+        resized.create()?;
+
+        let old_id = self.id;
+        std::mem::swap(self, &mut resized);
+        //the old objects, what is left to drop isn't tracked
+        resized.delete();
+        gl_resource::retrack("Framebuffer", old_id, self.id, self.renderbuffer_bytes());
+        Ok(())
+    }
+
+    /// binds the framebuffer and sets the viewport to cover it
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.spec.width, self.spec.height);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// copies the multisampled attachments into the color textures, does nothing without msaa
+    ///
+    /// has to run after drawing and before the textures are sampled or read back
+    pub fn resolve(&self) {
+        if self.resolve_id == 0 {
+            return;
+        }
+        let (width, height) = (self.spec.width, self.spec.height);
+        let previous_draw = query(gl::DRAW_FRAMEBUFFER_BINDING) as u32;
+        let previous_read = query(gl::READ_FRAMEBUFFER_BINDING) as u32;
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.resolve_id);
+            //a blit copies one read buffer, so every attachment is its own blit
+            for index in 0..self.color_attachments.len() {
+                let attachment = color_attachment(index);
+                gl::ReadBuffer(attachment);
+                gl::DrawBuffers(1, &attachment);
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+            set_draw_buffers(self.color_attachments.len());
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, previous_draw);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read);
+        }
+    }
+
+    /// reads the first color attachment back as RGBA8, top row first so it can be saved directly
+    ///
+    /// multisampled framebuffers are resolved first, depth only ones have nothing to read
+    pub fn read_pixels(&self) -> Result<Vec<u8>, FramebufferError> {
+        if self.color_attachments.is_empty() {
+            return Err(FramebufferError::NoColorAttachment);
+        }
+        self.resolve();
+        let (width, height) = (self.spec.width, self.spec.height);
+        let row_len = (width * 4) as usize;
+        let mut pixels = vec![0u8; row_len * height as usize];
+        let source = if self.resolve_id != 0 {
+            self.resolve_id
+        } else {
+            self.id
+        };
+
+        let previous_read = query(gl::READ_FRAMEBUFFER_BINDING) as u32;
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width,
+                height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous_read);
+        }
+
+        //opengl starts at the bottom left, images start at the top left
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks(row_len).rev() {
+            flipped.extend_from_slice(row);
+        }
+        Ok(flipped)
+    }
+
+    /// the color texture of fragment shader output `index`, resolve msaa framebuffers first
+    pub fn get_color_attachment(&self, index: usize) -> &Texture {
+        &self.color_attachments[index]
+    }
+
+    pub fn get_color_attachments(&self) -> &[Texture] {
+        &self.color_attachments
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_spec(&self) -> &FramebufferSpec {
+        &self.spec
+    }
+
+    pub fn get_width(&self) -> i32 {
+        self.spec.width
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.spec.height
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        if gl_resource::release("Framebuffer", self.id) {
+            self.delete();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{set_framebuffer_status, with_mock_gl, GlCall};
+
+    #[test]
+    fn attachments_follow_the_spec() {
+        let spec = FramebufferSpec::new(64, 32)
+            .color_formats(&[TextureFormat::RGBA8, TextureFormat::RGBA16F])
+            .depth(DepthFormat::Depth24Stencil8);
+        let (framebuffer, calls) = with_mock_gl(|| Framebuffer::with_spec(spec).unwrap());
+
+        let formats: Vec<TextureFormat> = framebuffer
+            .get_color_attachments()
+            .iter()
+            .map(Texture::get_format)
+            .collect();
+        assert_eq!(formats, [TextureFormat::RGBA8, TextureFormat::RGBA16F]);
+        assert!(calls.contains(&GlCall::RenderbufferStorage(gl::DEPTH24_STENCIL8, 64, 32)));
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::FramebufferRenderbuffer {
+                attachment: gl::DEPTH_STENCIL_ATTACHMENT,
+                ..
+            }
+        )));
+        assert!(calls.contains(&GlCall::DrawBuffers(vec![
+            gl::COLOR_ATTACHMENT0,
+            gl::COLOR_ATTACHMENT1
+        ])));
+        //whatever was bound before is bound again
+        assert_eq!(
+            calls[calls.len() - 2..],
+            [
+                GlCall::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0),
+                GlCall::BindFramebuffer(gl::READ_FRAMEBUFFER, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_framebuffers_are_errors() {
+        let (error, calls) = with_mock_gl(|| {
+            set_framebuffer_status(gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT);
+            Framebuffer::new(16, 16).err()
+        });
+
+        let error = error.unwrap();
+        assert_eq!(
+            error,
+            FramebufferError::Incomplete {
+                status: gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT
+            }
+        );
+        assert!(error
+            .to_string()
+            .contains("GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT"));
+        //the half made framebuffer is cleaned up
+        assert!(calls
+            .iter()
+            .any(|call| matches!(call, GlCall::DeleteFramebuffers(ids) if ids.len() == 1)));
+
+        let (errors, _) = with_mock_gl(|| {
+            let formats = [TextureFormat::RGBA8; 9];
+            (
+                Framebuffer::with_spec(FramebufferSpec::new(16, 16).color_formats(&formats)).err(),
+                Framebuffer::with_spec(FramebufferSpec::new(16, 16).samples(16)).err(),
+                Framebuffer::new(0, 16).err(),
+            )
+        });
+        assert_eq!(
+            errors,
+            (
+                Some(FramebufferError::TooManyColorAttachments {
+                    requested: 9,
+                    max: 8
+                }),
+                Some(FramebufferError::TooManySamples {
+                    requested: 16,
+                    max: 8
+                }),
+                Some(FramebufferError::InvalidSize {
+                    width: 0,
+                    height: 16
+                }),
+            )
+        );
+    }
+
+    #[test]
+    fn msaa_resolves_into_the_color_textures() {
+        let (_, calls) = with_mock_gl(|| {
+            let spec = FramebufferSpec::new(32, 32).samples(4);
+            let framebuffer = Framebuffer::with_spec(spec).unwrap();
+            framebuffer.read_pixels().unwrap();
+        });
+
+        assert!(calls.contains(&GlCall::RenderbufferStorageMultisample(
+            4,
+            gl::RGBA8,
+            32,
+            32
+        )));
+        assert!(calls.contains(&GlCall::BlitFramebuffer {
+            source: [0, 0, 32, 32],
+            destination: [0, 0, 32, 32],
+            mask: gl::COLOR_BUFFER_BIT,
+            filter: gl::NEAREST,
+        }));
+        let blit = calls
+            .iter()
+            .position(|call| matches!(call, GlCall::BlitFramebuffer { .. }))
+            .unwrap();
+        let read = calls
+            .iter()
+            .position(|call| matches!(call, GlCall::ReadPixels { .. }))
+            .unwrap();
+        assert!(blit < read);
+    }
+
+    #[test]
+    fn resizing_recreates_the_attachments() {
+        let ((framebuffer, old_texture), calls) = with_mock_gl(|| {
+            let mut framebuffer = Framebuffer::new(16, 16).unwrap();
+            let old_texture = framebuffer.get_color_attachment(0).get_id();
+            framebuffer.resize(16, 16).unwrap();
+            framebuffer.resize(40, 20).unwrap();
+            (framebuffer, old_texture)
+        });
+
+        assert_eq!(framebuffer.get_color_attachment(0).get_width(), 40);
+        assert_eq!(framebuffer.get_height(), 20);
+        //resizing to the same size does nothing
+        let deletes: Vec<_> = calls
+            .iter()
+            .filter(|call| matches!(call, GlCall::DeleteTextures(..)))
+            .collect();
+        assert_eq!(deletes, [&GlCall::DeleteTextures(vec![old_texture])]);
+    }
+
+    #[test]
+    fn failed_resizes_keep_the_old_attachments() {
+        let ((framebuffer, old), calls) = with_mock_gl(|| {
+            let mut framebuffer = Framebuffer::new(16, 16).unwrap();
+            let old = (
+                framebuffer.get_id(),
+                framebuffer.get_color_attachment(0).get_id(),
+            );
+            set_framebuffer_status(gl::FRAMEBUFFER_UNSUPPORTED);
+            assert!(framebuffer.resize(40, 20).is_err());
+            (framebuffer, old)
+        });
+
+        assert_eq!(
+            (
+                framebuffer.get_id(),
+                framebuffer.get_color_attachment(0).get_id()
+            ),
+            old
+        );
+        assert_eq!(framebuffer.get_width(), 16);
+        assert!(!calls.contains(&GlCall::DeleteFramebuffers(vec![old.0])));
+        assert!(!calls.contains(&GlCall::DeleteTextures(vec![old.1])));
+    }
+
+    #[test]
+    fn depth_only_framebuffers_cant_be_read() {
+        let (result, _) = with_mock_gl(|| {
+            let spec = FramebufferSpec::new(16, 16)
+                .color_formats(&[])
+                .depth(DepthFormat::Depth24);
+            Framebuffer::with_spec(spec).unwrap().read_pixels()
+        });
+
+        assert_eq!(result, Err(FramebufferError::NoColorAttachment));
+    }
+}
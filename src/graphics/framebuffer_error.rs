@@ -0,0 +1,90 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FramebufferError {
+    /// glCheckFramebufferStatus didn't return GL_FRAMEBUFFER_COMPLETE
+    Incomplete { status: u32 },
+    /// the driver allows fewer color attachments (or draw buffers) than requested
+    TooManyColorAttachments { requested: usize, max: i32 },
+    /// the driver allows fewer msaa samples than requested
+    TooManySamples { requested: i32, max: i32 },
+    /// 0 or negative width or height
+    InvalidSize { width: i32, height: i32 },
+    /// pixels were read back from a depth only framebuffer
+    NoColorAttachment,
+}
+
+/// the name of a glCheckFramebufferStatus result and what usually causes it
+fn describe_status(status: u32) -> (&'static str, &'static str) {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => (
+            "GL_FRAMEBUFFER_UNDEFINED",
+            "the default framebuffer doesn't exist",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => (
+            "GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+            "an attachment has no storage or a format that can't be rendered to",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => (
+            "GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT",
+            "nothing is attached",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => (
+            "GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+            "a draw buffer points at a missing attachment",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => (
+            "GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+            "the read buffer points at a missing attachment",
+        ),
+        gl::FRAMEBUFFER_UNSUPPORTED => (
+            "GL_FRAMEBUFFER_UNSUPPORTED",
+            "the driver doesn't support this combination of formats",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => (
+            "GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+            "the attachments have different sample counts",
+        ),
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => (
+            "GL_FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+            "layered and non layered attachments are mixed",
+        ),
+        _ => (
+            "unknown status",
+            "the driver returned a status gl doesn't define",
+        ),
+    }
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramebufferError::Incomplete { status } => {
+                let (name, reason) = describe_status(*status);
+                write!(
+                    f,
+                    "Framebuffer is incomplete: {} (0x{:X}), {}",
+                    name, status, reason
+                )
+            }
+            FramebufferError::TooManyColorAttachments { requested, max } => write!(
+                f,
+                "Framebuffer has {} color attachments, the driver allows {}",
+                requested, max
+            ),
+            FramebufferError::TooManySamples { requested, max } => write!(
+                f,
+                "Framebuffer asks for {} samples, the driver allows {}",
+                requested, max
+            ),
+            FramebufferError::InvalidSize { width, height } => {
+                write!(f, "Framebuffer can't be {}x{}", width, height)
+            }
+            FramebufferError::NoColorAttachment => {
+                write!(f, "Framebuffer has no color attachment to read from")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramebufferError {}
@@ -0,0 +1,186 @@
+//! bookkeeping shared by every type that owns a gl object
+//!
+//! - `ContextGuard` marks the context of this thread as alive, objects dropped after it is gone
+//!   skip their glDelete* call instead of calling into a destroyed context
+//! - debug builds keep a registry of live objects with the type, size and the place they were
+//!   created at, `report_leaks` prints whatever is still alive at shutdown
+
+use colored::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::panic::Location;
+
+thread_local! {
+    static CONTEXT_ALIVE: Cell<bool> = const { Cell::new(false) };
+    static LIVE: RefCell<HashMap<(&'static str, u32), LiveResource>> = RefCell::new(HashMap::new());
+}
+
+/// a gl object that hasn't been deleted yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveResource {
+    pub kind: &'static str,
+    pub id: u32,
+    /// gpu memory in bytes, 0 for objects that don't own storage like vertex arrays
+    pub bytes: usize,
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for LiveResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} created at {}", self.kind, self.id, self.location)?;
+        if self.bytes > 0 {
+            write!(f, " ({} bytes)", self.bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// keeps the gl context of this thread marked as alive until it's dropped
+///
+/// create it right after the function pointers are loaded and drop it before the window, so
+/// objects that outlive the window don't call into a destroyed context
+pub struct ContextGuard {
+    //not Send, the context belongs to the thread that made it current
+    _thread: std::marker::PhantomData<*const ()>,
+}
+
+impl ContextGuard {
+    /// a new context has no objects yet, anything still registered belonged to an old one
+    #[allow(clippy::new_without_default)] //making one has side effects
+    pub fn new() -> ContextGuard {
+        LIVE.with(|live| live.borrow_mut().clear());
+        CONTEXT_ALIVE.with(|alive| alive.set(true));
+        ContextGuard {
+            _thread: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        //destroying the context deletes everything, so nothing counts as alive after this
+        CONTEXT_ALIVE.with(|alive| alive.set(false));
+        LIVE.with(|live| live.borrow_mut().clear());
+    }
+}
+
+/// false before the first `ContextGuard` and after it is dropped
+pub fn is_context_alive() -> bool {
+    CONTEXT_ALIVE.with(|alive| alive.get())
+}
+
+/// registers a freshly created object, the location is the caller of the first function up
+/// the stack without `#[track_caller]`
+#[track_caller]
+pub(crate) fn track(kind: &'static str, id: u32, bytes: usize) {
+    if cfg!(debug_assertions) {
+        let resource = LiveResource {
+            kind,
+            id,
+            bytes,
+            location: Location::caller(),
+        };
+        LIVE.with(|live| live.borrow_mut().insert((kind, id), resource));
+    }
+}
+
+/// moves the entry of an object whose gl name changed, e.g. a relinked program, keeping the
+/// place it was first created at
+pub(crate) fn retrack(kind: &'static str, old_id: u32, new_id: u32, bytes: usize) {
+    if cfg!(debug_assertions) {
+        LIVE.with(|live| {
+            let mut live = live.borrow_mut();
+            if let Some(mut resource) = live.remove(&(kind, old_id)) {
+                resource.id = new_id;
+                resource.bytes = bytes;
+                live.insert((kind, new_id), resource);
+            }
+        });
+    }
+}
+
+/// unregisters an object that is being dropped, true if its gl name should be deleted
+pub(crate) fn release(kind: &'static str, id: u32) -> bool {
+    if cfg!(debug_assertions) {
+        LIVE.with(|live| live.borrow_mut().remove(&(kind, id)));
+    }
+    is_context_alive()
+}
+
+/// every object that hasn't been dropped yet, oldest creation site first. always empty in
+/// release builds
+pub fn live_resources() -> Vec<LiveResource> {
+    let mut resources: Vec<LiveResource> =
+        LIVE.with(|live| live.borrow().values().cloned().collect());
+    resources.sort_by_key(|resource| {
+        (
+            resource.location.file(),
+            resource.location.line(),
+            resource.kind,
+            resource.id,
+        )
+    });
+    resources
+}
+
+/// prints the objects that are still alive, call it after everything owning gl objects has
+/// been dropped. returns how many there were
+pub fn report_leaks() -> usize {
+    let leaks = live_resources();
+    if leaks.is_empty() {
+        return 0;
+    }
+    let bytes: usize = leaks.iter().map(|resource| resource.bytes).sum();
+    println!(
+        "{}",
+        format!("{} gl objects leaked ({} bytes):", leaks.len(), bytes).yellow()
+    );
+    for resource in &leaks {
+        println!("{}", format!("    {}", resource).yellow());
+    }
+    leaks.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::buffers::vertex_buffer::VertexBuffer;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn live_objects_remember_where_they_were_made() {
+        let ((live, line), calls) = with_mock_gl(|| {
+            let line = line!() + 1;
+            let buffer = VertexBuffer::new(&[0.0; 12]);
+            let live = live_resources();
+            drop(buffer);
+            assert!(live_resources().is_empty());
+            (live, line)
+        });
+
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].kind, "VertexBuffer");
+        assert_eq!(live[0].bytes, 48);
+        assert_eq!(
+            (live[0].location.file(), live[0].location.line()),
+            (file!(), line)
+        );
+        assert!(calls.contains(&GlCall::DeleteBuffers(vec![live[0].id])));
+    }
+
+    #[test]
+    fn objects_outliving_the_context_skip_the_delete() {
+        let (_, calls) = with_mock_gl(|| {
+            let buffer = VertexBuffer::new(&[0.0; 4]);
+            //stands in for the window going away before the buffer
+            drop(ContextGuard::new());
+            assert!(!is_context_alive());
+            drop(buffer);
+        });
+
+        assert!(!calls
+            .iter()
+            .any(|call| matches!(call, GlCall::DeleteBuffers(..))));
+    }
+}
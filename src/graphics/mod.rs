@@ -1,5 +1,24 @@
+pub mod animation;
+pub mod backend;
 pub mod buffers;
+pub mod cubemap;
 
+pub mod framebuffer;
+pub mod framebuffer_error;
+pub mod gl_resource;
+pub mod post_process;
 pub mod renderer;
+pub mod renderer_2d;
 pub mod shader;
+pub mod shader_cache;
+pub mod shader_error;
+pub mod shader_preprocessor;
+pub mod shader_reflection;
+pub mod shader_variants;
+pub mod sprite_sheet;
 pub mod texture;
+pub mod texture_array;
+pub mod texture_atlas;
+pub mod texture_error;
+pub mod texture_spec;
+pub mod vertex_layout_error;
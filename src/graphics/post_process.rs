@@ -0,0 +1,615 @@
+use super::buffers::index_buffer::IndexBuffer;
+use super::buffers::vertex::Vertex;
+use super::buffers::vertex_array::VertexArray;
+use super::buffers::vertex_buffer::VertexBuffer;
+use super::framebuffer::{Framebuffer, FramebufferSpec};
+use super::framebuffer_error::FramebufferError;
+use super::renderer::Renderer;
+use super::shader::Shader;
+use super::shader_error::{ShaderError, UniformError};
+use super::texture::{Texture, TextureFormat};
+use super::vertex_layout_error::VertexLayoutError;
+use colored::*;
+
+/// where the built in passes live, every effect is a single file using `common.glsl`
+const SHADER_DIRECTORY: &str = "res/shaders/post";
+/// u_Source and u_Scene take the first two slots, textures added to a pass come after them
+const FIRST_TEXTURE_SLOT: u32 = 2;
+
+/// a uniform value a pass uploads every time it runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PassParam {
+    Float(f32),
+    Int(i32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+}
+
+impl PassParam {
+    /// expects the shader to be bound
+    fn upload(&self, shader: &mut Shader, name: &str) -> Result<(), UniformError> {
+        match self {
+            PassParam::Float(value) => shader.set_uniform(name, value),
+            PassParam::Int(value) => shader.set_uniform(name, value),
+            PassParam::Vec2(value) => shader.set_uniform(name, value),
+            PassParam::Vec3(value) => shader.set_uniform(name, value),
+            PassParam::Vec4(value) => shader.set_uniform(name, value),
+        }
+    }
+}
+
+impl From<f32> for PassParam {
+    fn from(value: f32) -> PassParam {
+        PassParam::Float(value)
+    }
+}
+
+impl From<i32> for PassParam {
+    fn from(value: i32) -> PassParam {
+        PassParam::Int(value)
+    }
+}
+
+impl From<glm::Vec2> for PassParam {
+    fn from(value: glm::Vec2) -> PassParam {
+        PassParam::Vec2(value)
+    }
+}
+
+impl From<glm::Vec3> for PassParam {
+    fn from(value: glm::Vec3) -> PassParam {
+        PassParam::Vec3(value)
+    }
+}
+
+impl From<glm::Vec4> for PassParam {
+    fn from(value: glm::Vec4) -> PassParam {
+        PassParam::Vec4(value)
+    }
+}
+
+/// one fullscreen draw of a post process stack, a shader plus the uniforms it runs with
+///
+/// the shader gets the inputs declared in res/shaders/post/common.glsl on top of its params
+pub struct PostPass {
+    name: String,
+    shader: Shader,
+    params: Vec<(String, PassParam)>,
+    textures: Vec<(String, Texture)>,
+    enabled: bool,
+}
+
+impl PostPass {
+    /// a pass running the shader at `file_path`, `name` is how the stack finds it again
+    pub fn new(name: &str, file_path: &str) -> Result<PostPass, ShaderError> {
+        Ok(PostPass {
+            name: name.to_string(),
+            shader: Shader::new(file_path)?,
+            params: Vec::new(),
+            textures: Vec::new(),
+            enabled: true,
+        })
+    }
+
+    fn builtin(name: &str, effect: &str) -> Result<PostPass, ShaderError> {
+        Self::new(name, &format!("{}/{}.glsl", SHADER_DIRECTORY, effect))
+    }
+
+    /// stores a param of a built in pass without checking it, the shaders are known to have them
+    fn with_param(mut self, name: &str, value: impl Into<PassParam>) -> PostPass {
+        self.params.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// `strength` 0 keeps the colors, 1 is fully gray
+    pub fn grayscale(strength: f32) -> Result<PostPass, ShaderError> {
+        Ok(Self::builtin("grayscale", "grayscale")?.with_param("u_Strength", strength))
+    }
+
+    /// half of a separable gaussian blur along `direction`, `radius` is the tap spacing in pixels
+    pub fn blur(direction: glm::Vec2, radius: f32) -> Result<PostPass, ShaderError> {
+        Ok(Self::builtin("blur", "blur")?
+            .with_param("u_Direction", direction)
+            .with_param("u_Radius", radius))
+    }
+
+    /// threshold, horizontal blur, vertical blur and combine passes, all called "bloom"
+    ///
+    /// the combine adds the glow to u_Scene, so bloom should come before passes that change
+    /// the colors
+    pub fn bloom(threshold: f32, intensity: f32) -> Result<Vec<PostPass>, ShaderError> {
+        let mut passes = vec![
+            Self::builtin("bloom", "bloom_threshold")?.with_param("u_Threshold", threshold),
+            Self::blur(glm::vec2(1.0, 0.0), 2.0)?,
+            Self::blur(glm::vec2(0.0, 1.0), 2.0)?,
+            Self::builtin("bloom", "bloom_combine")?.with_param("u_Intensity", intensity),
+        ];
+        for pass in &mut passes {
+            pass.name = "bloom".to_string();
+        }
+        Ok(passes)
+    }
+
+    /// darkens the corners, `radius` is where it starts with 0.5 reaching the screen edges
+    pub fn vignette(radius: f32, softness: f32) -> Result<PostPass, ShaderError> {
+        Ok(Self::builtin("vignette", "vignette")?
+            .with_param("u_Radius", radius)
+            .with_param("u_Softness", softness))
+    }
+
+    /// curved screen with scanlines
+    pub fn crt(curvature: f32, scanline_strength: f32) -> Result<PostPass, ShaderError> {
+        Ok(Self::builtin("crt", "crt")?
+            .with_param("u_Curvature", curvature)
+            .with_param("u_ScanlineStrength", scanline_strength))
+    }
+
+    /// maps colors through `lut`, a strip of N slices of N x N pixels (e.g. 256 x 16)
+    pub fn color_grade(lut: Texture) -> Result<PostPass, ShaderError> {
+        let size = lut.get_height() as f32;
+        let mut pass = Self::builtin("color_grade", "color_grade")?
+            .with_param("u_LutSize", size)
+            .with_param("u_Intensity", 1.0);
+        pass.textures.push(("u_Lut".to_string(), lut));
+        Ok(pass)
+    }
+
+    /// sets a uniform the pass uploads every time it runs
+    ///
+    /// the value is checked against the shader right away, so typos show up here and not
+    /// as a black screen
+    pub fn set_param(
+        &mut self,
+        name: &str,
+        value: impl Into<PassParam>,
+    ) -> Result<(), UniformError> {
+        let value = value.into();
+        self.shader.bind();
+        let result = value.upload(&mut self.shader, name);
+        self.shader.unbind();
+        result?;
+
+        match self.params.iter_mut().find(|(param, _)| param == name) {
+            Some((_, param)) => *param = value,
+            None => self.params.push((name.to_string(), value)),
+        }
+        Ok(())
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<PassParam> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// binds `texture` to the sampler `name` whenever the pass runs, replacing what was there
+    pub fn set_texture(&mut self, name: &str, texture: Texture) -> Option<Texture> {
+        match self
+            .textures
+            .iter_mut()
+            .find(|(sampler, _)| sampler == name)
+        {
+            Some((_, existing)) => Some(std::mem::replace(existing, texture)),
+            None => {
+                self.textures.push((name.to_string(), texture));
+                None
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// for hot reloading the pass shader
+    pub fn get_shader_mut(&mut self) -> &mut Shader {
+        &mut self.shader
+    }
+
+    /// draws the pass into whatever is bound, reading `source` and `scene`
+    fn draw(
+        &mut self,
+        quad: &FullscreenQuad,
+        renderer: &Renderer,
+        source: &Texture,
+        scene: &Texture,
+    ) -> Result<(), VertexLayoutError> {
+        self.shader.bind();
+        source.bind(0);
+        scene.bind(1);
+        let resolution = glm::vec2(source.get_width() as f32, source.get_height() as f32);
+        let mut inputs = vec![
+            ("u_Source", PassParam::Int(0)),
+            ("u_Scene", PassParam::Int(1)),
+            ("u_Resolution", PassParam::Vec2(resolution)),
+        ];
+        for (slot, (name, texture)) in self.textures.iter().enumerate() {
+            let slot = FIRST_TEXTURE_SLOT + slot as u32;
+            texture.bind(slot);
+            inputs.push((name, PassParam::Int(slot as i32)));
+        }
+        //shaders only declare the inputs they use, the rest are optimized out
+        for (name, value) in inputs {
+            if self.shader.get_uniform(name).is_some() {
+                let _ = value.upload(&mut self.shader, name);
+            }
+        }
+        for (name, value) in &self.params {
+            match value.upload(&mut self.shader, name) {
+                //a param the shader doesn't use anymore, e.g. after editing it
+                Ok(()) | Err(UniformError::Missing { .. }) => {}
+                Err(error) => println!(
+                    "{}",
+                    format!("Warning: post pass '{}': {}", self.name, error).yellow()
+                ),
+            }
+        }
+        renderer.draw(&quad.va, &quad.ib, &self.shader)
+    }
+}
+
+crate::vertex! {
+    /// the inputs of `fullscreen.glsl`
+    struct QuadVertex {
+        #[location = 0]
+        position: [f32; 2],
+        #[location = 1]
+        tex_coord: [f32; 2],
+    }
+}
+
+/// two triangles covering clip space, with uvs going from 0 to 1
+struct FullscreenQuad {
+    va: VertexArray,
+    _vb: VertexBuffer,
+    ib: IndexBuffer,
+}
+
+impl FullscreenQuad {
+    fn new() -> FullscreenQuad {
+        let corner = |x: f32, y: f32| QuadVertex {
+            position: [x, y],
+            tex_coord: [(x + 1.0) * 0.5, (y + 1.0) * 0.5],
+        };
+        let vertices = [
+            corner(-1.0, -1.0),
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+        ];
+        let mut va = VertexArray::new();
+        va.bind();
+        let vb = VertexBuffer::new(&vertices);
+        va.add_buffer(&vb, &QuadVertex::layout());
+        let ib = IndexBuffer::new(&[0, 1, 2, 2, 3, 0]);
+        va.unbind();
+        FullscreenQuad { va, _vb: vb, ib }
+    }
+}
+
+/// runs a list of fullscreen passes over a frame, each one reading the output of the one before
+///
+/// usage per frame is `begin` -> draw the scene -> `end`. passes alternate between two targets
+/// and the last one draws into whatever was bound before `begin`
+pub struct PostProcessStack {
+    scene: Framebuffer,
+    targets: [Framebuffer; 2],
+    passes: Vec<PostPass>,
+    quad: FullscreenQuad,
+    in_frame: bool,
+}
+
+impl PostProcessStack {
+    /// the targets are RGBA16F so bright pixels survive until the bloom threshold
+    pub fn new(width: i32, height: i32) -> Result<PostProcessStack, FramebufferError> {
+        let spec = FramebufferSpec::new(width, height).color_formats(&[TextureFormat::RGBA16F]);
+        Ok(PostProcessStack {
+            scene: Framebuffer::with_spec(spec.clone())?,
+            targets: [
+                Framebuffer::with_spec(spec.clone())?,
+                Framebuffer::with_spec(spec)?,
+            ],
+            passes: Vec::new(),
+            quad: FullscreenQuad::new(),
+            in_frame: false,
+        })
+    }
+
+    /// adds a pass after the others
+    pub fn push(&mut self, pass: PostPass) {
+        self.passes.push(pass);
+    }
+
+    /// adds a pass so it runs as the `index`th one
+    pub fn insert(&mut self, index: usize, pass: PostPass) {
+        self.passes.insert(index, pass);
+    }
+
+    /// removes every pass called `name` and returns them in order
+    pub fn remove(&mut self, name: &str) -> Vec<PostPass> {
+        let (removed, kept) = std::mem::take(&mut self.passes)
+            .into_iter()
+            .partition(|pass| pass.name == name);
+        self.passes = kept;
+        removed
+    }
+
+    /// moves the pass at `from` so it runs as the `to`th one
+    pub fn move_pass(&mut self, from: usize, to: usize) {
+        let pass = self.passes.remove(from);
+        self.passes.insert(to, pass);
+    }
+
+    /// turns every pass called `name` on or off, returns false if there is none
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for pass in self.passes.iter_mut().filter(|pass| pass.name == name) {
+            pass.enabled = enabled;
+            found = true;
+        }
+        found
+    }
+
+    /// the first pass called `name`
+    pub fn get_pass_mut(&mut self, name: &str) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn get_passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    pub fn get_passes_mut(&mut self) -> &mut [PostPass] {
+        &mut self.passes
+    }
+
+    /// resizes the targets, call it when the window size changes
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), FramebufferError> {
+        self.scene.resize(width, height)?;
+        for target in &mut self.targets {
+            target.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    /// redirects drawing into the scene target until `end`
+    pub fn begin(&mut self, renderer: &mut Renderer) {
+        assert!(!self.in_frame, "begin called twice without end");
+        self.in_frame = true;
+        renderer.push_render_target(&self.scene);
+    }
+
+    /// runs the enabled passes, the last one draws into the target that was active at `begin`
+    ///
+    /// blending and depth testing are off while the passes draw so each one replaces its whole
+    /// target, they are restored afterwards. a pass whose shader reads attributes other than the
+    /// position and uv of the fullscreen quad fails, the first failure is returned once every
+    /// pass has run
+    pub fn end(&mut self, renderer: &mut Renderer) -> Result<(), VertexLayoutError> {
+        assert!(self.in_frame, "end called without begin");
+        self.in_frame = false;
+        renderer.pop_render_target();
+
+        let enabled: Vec<usize> = (0..self.passes.len())
+            .filter(|&index| self.passes[index].enabled)
+            .collect();
+        if enabled.is_empty() {
+            self.blit_scene();
+            return Ok(());
+        }
+
+        let blend = unsafe { gl::IsEnabled(gl::BLEND) } == gl::TRUE;
+        let depth_test = unsafe { gl::IsEnabled(gl::DEPTH_TEST) } == gl::TRUE;
+        unsafe {
+            gl::Disable(gl::BLEND);
+            gl::Disable(gl::DEPTH_TEST);
+        }
+
+        let scene = self.scene.get_color_attachment(0);
+        let mut source = scene;
+        let mut result = Ok(());
+        for (step, &index) in enabled.iter().enumerate() {
+            let last = step + 1 == enabled.len();
+            let target = &self.targets[step % 2];
+            if !last {
+                renderer.push_render_target(target);
+            }
+            let drawn = self.passes[index].draw(&self.quad, renderer, source, scene);
+            result = result.and(drawn);
+            if !last {
+                renderer.pop_render_target();
+                source = target.get_color_attachment(0);
+            }
+        }
+
+        unsafe {
+            if blend {
+                gl::Enable(gl::BLEND);
+            }
+            if depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+        result
+    }
+
+    /// copies the scene to the current target when no pass is enabled
+    fn blit_scene(&self) {
+        let mut viewport = [0; 4];
+        let mut read_framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+            let [x, y, width, height] = viewport;
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.scene.get_id());
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.scene.get_width(),
+                self.scene.get_height(),
+                x,
+                y,
+                x + width,
+                y + height,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, read_framebuffer as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{set_active_uniforms, with_mock_gl, GlCall};
+
+    fn draw_targets(calls: &[GlCall]) -> Vec<u32> {
+        let mut bound = 0;
+        let mut targets = Vec::new();
+        for call in calls {
+            match call {
+                GlCall::BindFramebuffer(gl::FRAMEBUFFER | gl::DRAW_FRAMEBUFFER, id) => bound = *id,
+                GlCall::DrawElements { .. } => targets.push(bound),
+                _ => {}
+            }
+        }
+        targets
+    }
+
+    #[test]
+    fn passes_ping_pong_and_end_in_the_outer_target() {
+        let (ids, calls) = with_mock_gl(|| {
+            let mut renderer = Renderer::new();
+            let mut stack = PostProcessStack::new(64, 32).unwrap();
+            stack.push(PostPass::grayscale(1.0).unwrap());
+            stack.push(PostPass::vignette(0.5, 0.2).unwrap());
+            stack.push(PostPass::crt(0.1, 0.3).unwrap());
+
+            let outer = Framebuffer::new(64, 32).unwrap();
+            renderer.push_render_target(&outer);
+            stack.begin(&mut renderer);
+            stack.end(&mut renderer).unwrap();
+            (
+                outer.get_id(),
+                stack.targets[0].get_id(),
+                stack.targets[1].get_id(),
+            )
+        });
+
+        let (outer, first, second) = ids;
+        assert_eq!(draw_targets(&calls), [first, second, outer]);
+    }
+
+    #[test]
+    fn passes_draw_without_blending_and_restore_it() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut renderer = Renderer::new();
+            unsafe { gl::Enable(gl::BLEND) };
+            let mut stack = PostProcessStack::new(64, 32).unwrap();
+            stack.push(PostPass::grayscale(1.0).unwrap());
+            stack.push(PostPass::vignette(0.5, 0.2).unwrap());
+
+            stack.begin(&mut renderer);
+            stack.end(&mut renderer).unwrap();
+            (renderer, stack)
+        });
+
+        let first_draw = calls
+            .iter()
+            .position(|call| matches!(call, GlCall::DrawElements { .. }))
+            .unwrap();
+        assert!(calls[..first_draw].contains(&GlCall::Disable(gl::BLEND)));
+        assert!(calls[..first_draw].contains(&GlCall::Disable(gl::DEPTH_TEST)));
+        //depth testing was off to begin with
+        assert_eq!(calls.last(), Some(&GlCall::Enable(gl::BLEND)));
+    }
+
+    #[test]
+    fn disabled_passes_are_skipped() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut renderer = Renderer::new();
+            unsafe { gl::Viewport(0, 0, 960, 540) };
+            let mut stack = PostProcessStack::new(64, 32).unwrap();
+            stack.push(PostPass::grayscale(1.0).unwrap());
+            assert!(stack.set_enabled("grayscale", false));
+            assert!(!stack.set_enabled("sepia", false));
+
+            stack.begin(&mut renderer);
+            stack.end(&mut renderer).unwrap();
+        });
+
+        //nothing to run, the scene is stretched over the outer viewport
+        assert!(draw_targets(&calls).is_empty());
+        assert!(calls.contains(&GlCall::BlitFramebuffer {
+            source: [0, 0, 64, 32],
+            destination: [0, 0, 960, 540],
+            mask: gl::COLOR_BUFFER_BIT,
+            filter: gl::LINEAR,
+        }));
+    }
+
+    #[test]
+    fn passes_can_be_reordered_and_removed() {
+        let (names, _) = with_mock_gl(|| {
+            let mut stack = PostProcessStack::new(16, 16).unwrap();
+            stack.push(PostPass::grayscale(1.0).unwrap());
+            for pass in PostPass::bloom(0.8, 1.0).unwrap() {
+                stack.push(pass);
+            }
+            stack.push(PostPass::vignette(0.5, 0.2).unwrap());
+            stack.move_pass(0, 5);
+
+            let names = |stack: &PostProcessStack| {
+                stack
+                    .get_passes()
+                    .iter()
+                    .map(PostPass::get_name)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let moved = names(&stack);
+            assert_eq!(stack.remove("bloom").len(), 4);
+            (moved, names(&stack))
+        });
+
+        assert_eq!(names.0, "bloom bloom bloom bloom vignette grayscale");
+        assert_eq!(names.1, "vignette grayscale");
+    }
+
+    #[test]
+    fn params_are_checked_against_the_shader() {
+        let (results, calls) = with_mock_gl(|| {
+            set_active_uniforms(&[("u_Strength", gl::FLOAT, 1)]);
+            let mut pass = PostPass::grayscale(1.0).unwrap();
+            (
+                pass.set_param("u_Strength", 0.25),
+                pass.set_param("u_Strength", glm::vec2(0.0, 1.0)).is_err(),
+                pass.set_param("u_Strenght", 0.25).is_err(),
+                pass.get_param("u_Strength"),
+            )
+        });
+
+        assert_eq!(results.0, Ok(()));
+        assert!(results.1 && results.2);
+        assert_eq!(results.3, Some(PassParam::Float(0.25)));
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::Uniformfv {
+                components: 1,
+                values,
+                ..
+            } if values == &[0.25]
+        )));
+    }
+}
@@ -1,8 +1,14 @@
 
 
+use super::backend::opengl::OpenGLBackend;
+use super::backend::{
+    IndexBufferHandle, ProgramHandle, RenderBackend, TextureHandle, VertexBufferHandle,
+};
 use super::buffers::index_buffer;
 use super::buffers::vertex_array;
+use super::framebuffer::Framebuffer;
 use super::shader;
+use super::vertex_layout_error::VertexLayoutError;
 
 use colored::*;
 
@@ -70,35 +76,238 @@ pub extern "system" fn debug_message_callback(
     // );
 }
 
-pub struct Renderer {}
+/// where drawing went and reading came from before a `push_render_target`
+struct RenderTarget {
+    draw_framebuffer: u32,
+    read_framebuffer: u32,
+    viewport: [i32; 4],
+}
+
+/// issues draws through a `RenderBackend`, with gl it also keeps the stack of render targets
+pub struct Renderer<B: RenderBackend = OpenGLBackend> {
+    backend: B,
+    target_stack: Vec<RenderTarget>,
+}
+
+impl<B: RenderBackend> Renderer<B> {
+    pub fn with_backend(backend: B) -> Renderer<B> {
+        Renderer {
+            backend,
+            target_stack: Vec::new(),
+        }
+    }
+
+    pub fn get_backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// for creating buffers, textures and programs to draw with
+    pub fn get_backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// draws the first `count` indices, `textures[i]` is bound to texture slot i
+    pub fn draw_buffers(
+        &mut self,
+        vertices: VertexBufferHandle,
+        indices: IndexBufferHandle,
+        program: ProgramHandle,
+        textures: &[TextureHandle],
+        count: i32,
+    ) -> Result<(), VertexLayoutError> {
+        self.backend
+            .draw_indexed(vertices, indices, program, textures, count)
+    }
+
+    /// fills the color buffer of the current render target with `color`
+    pub fn clear(&mut self, color: glm::Vec4) {
+        self.backend.clear(color);
+    }
+}
 
 impl Renderer {
     pub fn new() -> Renderer {
-        Renderer {}
+        Renderer::with_backend(OpenGLBackend::new())
+    }
+
+    /// draws into `framebuffer` until the matching `pop_render_target`
+    ///
+    /// targets nest, e.g. a post process pass pushed while a scene target is active
+    pub fn push_render_target(&mut self, framebuffer: &Framebuffer) {
+        let (mut draw, mut read) = (0, 0);
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw);
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        self.target_stack.push(RenderTarget {
+            draw_framebuffer: draw as u32,
+            read_framebuffer: read as u32,
+            viewport,
+        });
+        framebuffer.bind();
+    }
+
+    /// goes back to the framebuffers and viewport that were active before the last push
+    pub fn pop_render_target(&mut self) {
+        let target = self
+            .target_stack
+            .pop()
+            .expect("pop_render_target without a matching push_render_target");
+        let [x, y, width, height] = target.viewport;
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.draw_framebuffer);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, target.read_framebuffer);
+            gl::Viewport(x, y, width, height);
+        }
+    }
+
+    /// how many render targets are pushed, 0 when drawing to whatever was bound originally
+    pub fn get_render_target_depth(&self) -> usize {
+        self.target_stack.len()
     }
 
+    /// nothing is drawn when `va` doesn't feed every attribute `shader` reads, see
+    /// `VertexArray::bind_with`
     pub fn draw(
         &self,
         va: &vertex_array::VertexArray,
         ib: &index_buffer::IndexBuffer,
         shader: &shader::Shader,
-    ) {
-        shader.bind();
-        va.bind();
-        ib.bind();
-        unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES,
-                ib.get_count(),
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
-        }
+    ) -> Result<(), VertexLayoutError> {
+        self.draw_indexed(va, ib, shader, ib.get_count())
     }
 
-    pub fn clear(&self) {
+    /// draws only the first `count` indices of the index buffer
+    pub fn draw_indexed(
+        &self,
+        va: &vertex_array::VertexArray,
+        ib: &index_buffer::IndexBuffer,
+        shader: &shader::Shader,
+        count: i32,
+    ) -> Result<(), VertexLayoutError> {
+        self.backend.draw_elements(va, ib, shader, count)
+    }
+
+    /// draws `count` indices with `base_vertex` added to each, for vertices pushed into a
+    /// `StreamBuffer` that's the offset `push` returned divided by the vertex size
+    pub fn draw_indexed_base_vertex(
+        &self,
+        va: &vertex_array::VertexArray,
+        ib: &index_buffer::IndexBuffer,
+        shader: &shader::Shader,
+        count: i32,
+        base_vertex: i32,
+    ) -> Result<(), VertexLayoutError> {
+        self.backend
+            .draw_elements_base_vertex(va, ib, shader, count, base_vertex)
+    }
+
+    /// makes writes of earlier compute dispatches visible, `barriers` says to what
+    /// (e.g. gl::SHADER_STORAGE_BARRIER_BIT | gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT)
+    pub fn memory_barrier(&self, barriers: gl::types::GLbitfield) {
         unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::MemoryBarrier(barriers);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::software::SoftwareBackend;
+    use crate::graphics::framebuffer::Framebuffer;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn draw_binds_everything_before_drawing() {
+        //returned so dropping them doesn't end up in the log
+        let (_objects, calls) = with_mock_gl(|| {
+            let va = vertex_array::VertexArray::new();
+            let ib = index_buffer::IndexBuffer::new(&[0, 1, 2]);
+            let shader = shader::Shader::new("res/shaders").unwrap();
+            Renderer::new().draw(&va, &ib, &shader).unwrap();
+            (va, ib, shader)
+        });
+
+        let vao = calls.iter().find_map(|call| match call {
+            GlCall::GenVertexArrays(names) => Some(names[0]),
+            _ => None,
+        });
+        let ibo = calls.iter().find_map(|call| match call {
+            GlCall::GenBuffers(names) => Some(names[0]),
+            _ => None,
+        });
+        let program = calls.iter().find_map(|call| match call {
+            GlCall::CreateProgram(id) => Some(*id),
+            _ => None,
+        });
+
+        assert_eq!(
+            calls[calls.len() - 4..],
+            [
+                GlCall::UseProgram(program.unwrap()),
+                GlCall::BindVertexArray(vao.unwrap()),
+                GlCall::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo.unwrap()),
+                GlCall::DrawElements {
+                    mode: gl::TRIANGLES,
+                    count: 3,
+                    type_: gl::UNSIGNED_INT,
+                    offset: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_targets_restore_what_was_bound() {
+        let ((depth, source, scene, blur), calls) = with_mock_gl(|| {
+            unsafe { gl::Viewport(0, 0, 960, 540) };
+            let source = Framebuffer::new(16, 16).unwrap();
+            //reading from a different framebuffer than drawing, e.g. in the middle of a blit
+            unsafe { gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source.get_id()) };
+            let scene = Framebuffer::new(320, 180).unwrap();
+            let blur = Framebuffer::new(160, 90).unwrap();
+            let mut renderer = Renderer::new();
+            renderer.push_render_target(&scene);
+            renderer.push_render_target(&blur);
+            let depth = renderer.get_render_target_depth();
+            renderer.pop_render_target();
+            renderer.pop_render_target();
+            (depth, source, scene, blur)
+        });
+
+        let (source, scene, blur) = (source.get_id(), scene.get_id(), blur.get_id());
+        assert_eq!(depth, 2);
+        assert_eq!(
+            calls[calls.len() - 10..],
+            [
+                GlCall::BindFramebuffer(gl::FRAMEBUFFER, scene),
+                GlCall::Viewport(0, 0, 320, 180),
+                GlCall::BindFramebuffer(gl::FRAMEBUFFER, blur),
+                GlCall::Viewport(0, 0, 160, 90),
+                GlCall::BindFramebuffer(gl::DRAW_FRAMEBUFFER, scene),
+                GlCall::BindFramebuffer(gl::READ_FRAMEBUFFER, scene),
+                GlCall::Viewport(0, 0, 320, 180),
+                GlCall::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0),
+                GlCall::BindFramebuffer(gl::READ_FRAMEBUFFER, source),
+                GlCall::Viewport(0, 0, 960, 540),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "without a matching push_render_target")]
+    fn popping_without_a_push_panics() {
+        with_mock_gl(|| Renderer::new().pop_render_target());
+    }
+
+    #[test]
+    fn clear_goes_through_the_backend() {
+        let mut renderer = Renderer::with_backend(SoftwareBackend::new(1, 1));
+        renderer.clear(glm::vec4(1.0, 0.0, 0.0, 1.0));
+
+        assert_eq!(renderer.get_backend().read_pixels(), [255, 0, 0, 255]);
+    }
+}
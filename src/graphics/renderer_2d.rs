@@ -0,0 +1,591 @@
+use super::backend::opengl::OpenGLBackend;
+use super::backend::{
+    IndexBufferHandle, ProgramHandle, RenderBackend, TextureHandle, Uniform, VertexBufferHandle,
+};
+use super::buffers::uniform_buffer::CameraData;
+use super::buffers::vertex_buffer_layout::VertexBufferLayout;
+use super::renderer::Renderer;
+use super::shader_error::ShaderError;
+use super::vertex_layout_error::VertexLayoutError;
+
+const MAX_QUADS: usize = 10_000;
+const MAX_VERTICES: usize = MAX_QUADS * 4;
+/// has to match the size of the u_Textures array in res/shaders/batch
+const MAX_TEXTURE_SLOTS: usize = 16;
+/// position (2) + color (4) + tex coord (2) + texture index (1) + layer (1)
+pub const FLOATS_PER_VERTEX: usize = 10;
+
+/// a single quad submitted to the batch renderer
+pub struct Sprite {
+    /// center of the quad in world space
+    pub position: glm::Vec2,
+    pub size: glm::Vec2,
+    /// rotation around the center in radians (counter clockwise)
+    pub rotation: f32,
+    /// (u0, v0, u1, v1) sub rectangle of the texture
+    pub uv_rect: glm::Vec4,
+    pub tint: glm::Vec4,
+    /// when None the quad is filled with the tint color
+    pub texture: Option<TextureHandle>,
+    /// the layer shown when `texture` is a texture array
+    pub layer: u32,
+}
+
+impl Sprite {
+    /// creates a sprite that shows the whole texture untinted, with gl that's
+    /// `TextureHandle::from(&texture)`
+    pub fn new(texture: TextureHandle, position: glm::Vec2, size: glm::Vec2) -> Sprite {
+        Sprite {
+            position,
+            size,
+            rotation: 0.0,
+            uv_rect: glm::vec4(0.0, 0.0, 1.0, 1.0),
+            tint: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            texture: Some(texture),
+            layer: 0,
+        }
+    }
+
+    /// creates an untextured quad filled with `color`
+    pub fn colored(position: glm::Vec2, size: glm::Vec2, color: glm::Vec4) -> Sprite {
+        Sprite {
+            position,
+            size,
+            rotation: 0.0,
+            uv_rect: glm::vec4(0.0, 0.0, 1.0, 1.0),
+            tint: color,
+            texture: None,
+            layer: 0,
+        }
+    }
+
+    /// appends the 4 corners in the batch vertex format, `texture_index` is -1 for untextured quads
+    pub fn write_vertices(&self, texture_index: f32, vertices: &mut Vec<f32>) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let half = self.size * 0.5;
+        let uv = self.uv_rect;
+        //corners in counter clockwise order starting bottom left, matching the index pattern
+        let corners = [
+            (-half.x, -half.y, uv.x, uv.y),
+            (half.x, -half.y, uv.z, uv.y),
+            (half.x, half.y, uv.z, uv.w),
+            (-half.x, half.y, uv.x, uv.w),
+        ];
+
+        for (x, y, u, v) in corners {
+            vertices.extend_from_slice(&[
+                self.position.x + x * cos - y * sin,
+                self.position.y + x * sin + y * cos,
+                self.tint.x,
+                self.tint.y,
+                self.tint.z,
+                self.tint.w,
+                u,
+                v,
+                texture_index,
+                self.layer as f32,
+            ]);
+        }
+    }
+}
+
+/// layout of the vertices written by `Sprite::write_vertices`
+pub fn batch_layout() -> VertexBufferLayout {
+    let mut layout = VertexBufferLayout::new();
+    layout.push::<f32>(2);
+    layout.push::<f32>(4);
+    layout.push::<f32>(2);
+    layout.push::<f32>(1);
+    layout.push::<f32>(1);
+    layout
+}
+
+/// indices for `quads` quads, every quad uses the same pattern so this never changes
+pub fn quad_indices(quads: usize) -> Vec<u32> {
+    let mut indices: Vec<u32> = Vec::with_capacity(quads * 6);
+    for quad in 0..quads as u32 {
+        let offset = quad * 4;
+        indices.extend_from_slice(&[
+            offset,
+            offset + 1,
+            offset + 2,
+            offset + 2,
+            offset + 3,
+            offset,
+        ]);
+    }
+    indices
+}
+
+/// counters for the last scene, useful to check how well things are batching
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BatchStats {
+    pub draw_calls: u32,
+    pub quad_count: u32,
+}
+
+/// collects quads into one big vertex buffer and draws them with as few draw calls as possible
+///
+/// usage per frame is `begin_scene` -> any number of `draw_*` calls -> `end_scene`.
+/// a flush happens when the vertex buffer is full, when every texture slot is taken or when a
+/// texture array follows plain textures in a batch (or the other way around).
+/// uploads, texture binds and draws go through the backend of the renderer, gl by default
+pub struct Renderer2D<B: RenderBackend = OpenGLBackend> {
+    renderer: Renderer<B>,
+    vb: VertexBufferHandle,
+    ib: IndexBufferHandle,
+    textured: ProgramHandle,
+    untextured: ProgramHandle,
+    /// samples texture arrays instead of plain textures
+    layered: ProgramHandle,
+    vertices: Vec<f32>,
+    texture_slots: Vec<TextureHandle>,
+    max_texture_slots: usize,
+    in_scene: bool,
+    stats: BatchStats,
+    /// the first failed draw of the scene, `end_scene` returns it
+    draw_error: Option<VertexLayoutError>,
+}
+
+impl Renderer2D {
+    /// draws with gl, needs a current context
+    pub fn new() -> Result<Renderer2D, ShaderError> {
+        Self::with_backend(OpenGLBackend::new())
+    }
+}
+
+impl<B: RenderBackend> Renderer2D<B> {
+    pub fn with_backend(backend: B) -> Result<Renderer2D<B>, ShaderError> {
+        let mut renderer = Renderer::with_backend(backend);
+        let backend = renderer.get_backend_mut();
+
+        let vb = backend.create_dynamic_vertex_buffer(
+            MAX_VERTICES * FLOATS_PER_VERTEX * std::mem::size_of::<f32>(),
+            &batch_layout(),
+        );
+        let ib = backend.create_index_buffer(&quad_indices(MAX_QUADS));
+
+        //the driver might support fewer slots than the shader declares
+        let max_texture_slots = backend.get_max_texture_slots().clamp(1, MAX_TEXTURE_SLOTS);
+
+        //batches without textures skip the sampler switch. both are compiled up front so
+        //broken shaders show up here and not in the middle of a frame
+        let textured = backend.create_program("res/shaders/batch", &[])?;
+        let untextured = backend.create_program("res/shaders/batch", &[("UNTEXTURED", "1")])?;
+        let layered = backend.create_program("res/shaders/batch", &[("LAYERED", "1")])?;
+        Self::set_samplers(backend, textured, max_texture_slots);
+        Self::set_samplers(backend, layered, max_texture_slots);
+
+        Ok(Renderer2D {
+            renderer,
+            vb,
+            ib,
+            textured,
+            untextured,
+            layered,
+            vertices: Vec::with_capacity(MAX_VERTICES * FLOATS_PER_VERTEX),
+            texture_slots: Vec::with_capacity(max_texture_slots),
+            max_texture_slots,
+            in_scene: false,
+            stats: BatchStats::default(),
+            draw_error: None,
+        })
+    }
+
+    /// picks up edits to the batch shader, see `Shader::reload_if_changed`
+    ///
+    /// the variants share the files so only the first error is returned
+    pub fn reload_shaders(&mut self) -> Result<bool, ShaderError> {
+        let backend = self.renderer.get_backend_mut();
+        let textured = backend.reload_program(self.textured);
+        let untextured = backend.reload_program(self.untextured);
+        let layered = backend.reload_program(self.layered);
+        //new programs with samplers need them even when another variant failed
+        for (program, reloaded) in [(self.textured, &textured), (self.layered, &layered)] {
+            if let Ok(true) = reloaded {
+                Self::set_samplers(backend, program, self.max_texture_slots);
+            }
+        }
+        Ok(textured? | untextured? | layered?)
+    }
+
+    /// points u_Textures[i] at texture slot i
+    fn set_samplers(backend: &mut B, program: ProgramHandle, max_texture_slots: usize) {
+        let samplers: Vec<i32> = (0..max_texture_slots as i32).collect();
+        backend.set_uniform(program, "u_Textures", Uniform::IntArray(samplers));
+    }
+
+    pub fn get_backend(&self) -> &B {
+        self.renderer.get_backend()
+    }
+
+    /// for creating the textures sprites are drawn with
+    pub fn get_backend_mut(&mut self) -> &mut B {
+        self.renderer.get_backend_mut()
+    }
+
+    /// starts collecting quads, the batch shader reads `camera` from the shared Camera block
+    pub fn begin_scene(&mut self, camera: &CameraData) {
+        assert!(!self.in_scene, "begin_scene called twice without end_scene");
+        self.in_scene = true;
+        self.renderer.get_backend_mut().set_camera(camera);
+        self.stats = BatchStats::default();
+        self.draw_error = None;
+        self.start_batch();
+    }
+
+    /// draws whatever is left in the batch
+    ///
+    /// fails when the batch shader reads attributes the batch vertices don't have, e.g. after
+    /// editing it. the batches drawn with it are skipped
+    pub fn end_scene(&mut self) -> Result<(), VertexLayoutError> {
+        assert!(self.in_scene, "end_scene called without begin_scene");
+        self.flush();
+        self.in_scene = false;
+        match self.draw_error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    pub fn draw_quad(&mut self, position: glm::Vec2, size: glm::Vec2, color: glm::Vec4) {
+        self.draw_sprite(&Sprite::colored(position, size, color));
+    }
+
+    pub fn draw_texture(&mut self, texture: TextureHandle, position: glm::Vec2, size: glm::Vec2) {
+        self.draw_sprite(&Sprite::new(texture, position, size));
+    }
+
+    /// draws one layer of a texture array, with gl that's `TextureHandle::from(&array)`
+    pub fn draw_texture_layer(
+        &mut self,
+        texture: TextureHandle,
+        layer: u32,
+        position: glm::Vec2,
+        size: glm::Vec2,
+    ) {
+        self.draw_sprite(&Sprite {
+            layer,
+            ..Sprite::new(texture, position, size)
+        });
+    }
+
+    pub fn draw_sprite(&mut self, sprite: &Sprite) {
+        assert!(
+            self.in_scene,
+            "draw called outside of begin_scene/end_scene"
+        );
+
+        if self.vertices.len() >= MAX_VERTICES * FLOATS_PER_VERTEX {
+            self.next_batch();
+        }
+
+        let texture_index = match sprite.texture {
+            Some(texture) => self.texture_slot(texture) as f32,
+            None => -1.0,
+        };
+
+        sprite.write_vertices(texture_index, &mut self.vertices);
+
+        self.stats.quad_count += 1;
+    }
+
+    /// stats of the current (or last finished) scene
+    pub fn get_stats(&self) -> BatchStats {
+        self.stats
+    }
+
+    /// returns the slot the texture is bound to in this batch, flushing if all slots are used or
+    /// the batch samples the other kind of texture
+    fn texture_slot(&mut self, texture: TextureHandle) -> usize {
+        if let Some(slot) = self
+            .texture_slots
+            .iter()
+            .position(|&bound| bound == texture)
+        {
+            return slot;
+        }
+
+        let other_kind = self
+            .texture_slots
+            .first()
+            .is_some_and(|bound| bound.is_layered() != texture.is_layered());
+        if other_kind || self.texture_slots.len() >= self.max_texture_slots {
+            self.next_batch();
+        }
+
+        self.texture_slots.push(texture);
+        self.texture_slots.len() - 1
+    }
+
+    fn start_batch(&mut self) {
+        self.vertices.clear();
+        self.texture_slots.clear();
+    }
+
+    fn next_batch(&mut self) {
+        self.flush();
+        self.start_batch();
+    }
+
+    fn flush(&mut self) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let backend = self.renderer.get_backend_mut();
+        backend.set_vertex_data(self.vb, &self.vertices);
+
+        let program = match self.texture_slots.first() {
+            None => self.untextured,
+            Some(texture) if texture.is_layered() => self.layered,
+            Some(_) => self.textured,
+        };
+
+        let quads = self.vertices.len() / (FLOATS_PER_VERTEX * 4);
+        match self.renderer.draw_buffers(
+            self.vb,
+            self.ib,
+            program,
+            &self.texture_slots,
+            (quads * 6) as i32,
+        ) {
+            Ok(()) => self.stats.draw_calls += 1,
+            //the rest of the scene fails the same way
+            Err(error) => {
+                self.draw_error.get_or_insert(error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::software::SoftwareBackend;
+    use crate::graphics::shader_error::ShaderError;
+    use crate::graphics::texture::Texture;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    /// the program bound for each draw call
+    fn programs_per_draw(calls: &[GlCall]) -> Vec<u32> {
+        let mut program = 0;
+        let mut draws = Vec::new();
+        for call in calls {
+            match call {
+                GlCall::UseProgram(id) => program = *id,
+                GlCall::DrawElements { .. } => draws.push(program),
+                _ => {}
+            }
+        }
+        draws
+    }
+
+    #[test]
+    fn batches_without_textures_use_the_untextured_variant() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut renderer = Renderer2D::new().unwrap();
+            let texture = Texture::from_rgba(1, 1, &[255; 4]);
+            let position = glm::vec2(0.0, 0.0);
+            let size = glm::vec2(1.0, 1.0);
+
+            renderer.begin_scene(&CameraData::default());
+            renderer.draw_quad(position, size, glm::vec4(1.0, 0.0, 0.0, 1.0));
+            renderer.end_scene().unwrap();
+
+            renderer.begin_scene(&CameraData::default());
+            renderer.draw_quad(position, size, glm::vec4(1.0, 0.0, 0.0, 1.0));
+            renderer.draw_texture(TextureHandle::from(&texture), position, size);
+            renderer.end_scene().unwrap();
+        });
+
+        //the textured variant is compiled first
+        let programs: Vec<u32> = calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::CreateProgram(id) => Some(*id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(programs_per_draw(&calls), [programs[1], programs[0]]);
+    }
+
+    /// software rendering where reloads swap the textured variant and fail the untextured one,
+    /// like after an edit inside `#ifdef UNTEXTURED`
+    struct BrokenUntextured {
+        backend: SoftwareBackend,
+        untextured: Vec<ProgramHandle>,
+        uniforms: Vec<(ProgramHandle, String)>,
+    }
+
+    impl RenderBackend for BrokenUntextured {
+        fn create_vertex_buffer(
+            &mut self,
+            data: &[f32],
+            layout: &VertexBufferLayout,
+        ) -> VertexBufferHandle {
+            self.backend.create_vertex_buffer(data, layout)
+        }
+
+        fn create_dynamic_vertex_buffer(
+            &mut self,
+            size: usize,
+            layout: &VertexBufferLayout,
+        ) -> VertexBufferHandle {
+            self.backend.create_dynamic_vertex_buffer(size, layout)
+        }
+
+        fn set_vertex_data(&mut self, buffer: VertexBufferHandle, data: &[f32]) {
+            self.backend.set_vertex_data(buffer, data)
+        }
+
+        fn create_index_buffer(&mut self, data: &[u32]) -> IndexBufferHandle {
+            self.backend.create_index_buffer(data)
+        }
+
+        fn create_texture(&mut self, width: i32, height: i32, pixels: &[u8]) -> TextureHandle {
+            self.backend.create_texture(width, height, pixels)
+        }
+
+        fn create_texture_array(
+            &mut self,
+            width: i32,
+            height: i32,
+            layers: i32,
+            pixels: &[u8],
+        ) -> TextureHandle {
+            self.backend
+                .create_texture_array(width, height, layers, pixels)
+        }
+
+        fn get_max_texture_slots(&self) -> usize {
+            self.backend.get_max_texture_slots()
+        }
+
+        fn create_program(
+            &mut self,
+            path: &str,
+            defines: &[(&str, &str)],
+        ) -> Result<ProgramHandle, ShaderError> {
+            let program = self.backend.create_program(path, defines)?;
+            if defines.iter().any(|&(name, _)| name == "UNTEXTURED") {
+                self.untextured.push(program);
+            }
+            Ok(program)
+        }
+
+        fn reload_program(&mut self, program: ProgramHandle) -> Result<bool, ShaderError> {
+            if self.untextured.contains(&program) {
+                return Err(ShaderError::Link {
+                    log: "error: oops".to_string(),
+                });
+            }
+            Ok(true)
+        }
+
+        fn set_uniform(&mut self, program: ProgramHandle, name: &str, value: Uniform) {
+            self.uniforms.push((program, name.to_string()));
+            self.backend.set_uniform(program, name, value)
+        }
+
+        fn set_camera(&mut self, camera: &CameraData) {
+            self.backend.set_camera(camera)
+        }
+
+        fn clear(&mut self, color: glm::Vec4) {
+            self.backend.clear(color)
+        }
+
+        fn draw_indexed(
+            &mut self,
+            vertices: VertexBufferHandle,
+            indices: IndexBufferHandle,
+            program: ProgramHandle,
+            textures: &[TextureHandle],
+            count: i32,
+        ) -> Result<(), VertexLayoutError> {
+            self.backend
+                .draw_indexed(vertices, indices, program, textures, count)
+        }
+
+        fn read_pixels(&self) -> Vec<u8> {
+            self.backend.read_pixels()
+        }
+    }
+
+    #[test]
+    fn samplers_are_set_again_when_only_the_untextured_reload_fails() {
+        let mut renderer = Renderer2D::with_backend(BrokenUntextured {
+            backend: SoftwareBackend::new(1, 1),
+            untextured: Vec::new(),
+            uniforms: Vec::new(),
+        })
+        .unwrap();
+        renderer.get_backend_mut().uniforms.clear();
+
+        assert!(matches!(
+            renderer.reload_shaders(),
+            Err(ShaderError::Link { .. })
+        ));
+        let samplers = "u_Textures".to_string();
+        assert_eq!(
+            renderer.get_backend().uniforms,
+            [
+                (renderer.textured, samplers.clone()),
+                (renderer.layered, samplers)
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_run_on_the_software_backend() {
+        let mut renderer = Renderer2D::with_backend(SoftwareBackend::new(2, 1)).unwrap();
+        let backend = renderer.get_backend_mut();
+        //one texture more than there are slots
+        let mut textures: Vec<TextureHandle> = (0..MAX_TEXTURE_SLOTS)
+            .map(|_| backend.create_texture(1, 1, &[255, 0, 0, 255]))
+            .collect();
+        textures.push(backend.create_texture(1, 1, &[0, 255, 0, 255]));
+
+        renderer.begin_scene(&CameraData {
+            projection: glm::ortho(0.0, 2.0, 0.0, 1.0, -1.0, 1.0),
+            ..CameraData::default()
+        });
+        let size = glm::vec2(1.0, 1.0);
+        for &texture in &textures[..MAX_TEXTURE_SLOTS] {
+            renderer.draw_texture(texture, glm::vec2(0.5, 0.5), size);
+        }
+        renderer.draw_texture(textures[MAX_TEXTURE_SLOTS], glm::vec2(1.5, 0.5), size);
+        renderer.end_scene().unwrap();
+
+        assert_eq!(renderer.get_stats().draw_calls, 2);
+        assert_eq!(
+            renderer.get_backend().read_pixels(),
+            [255, 0, 0, 255, 0, 255, 0, 255]
+        );
+    }
+
+    #[test]
+    fn texture_arrays_get_their_own_batches() {
+        let mut renderer = Renderer2D::with_backend(SoftwareBackend::new(3, 1)).unwrap();
+        let backend = renderer.get_backend_mut();
+        let texture = backend.create_texture(1, 1, &[0, 0, 255, 255]);
+        let array = backend.create_texture_array(1, 1, 2, &[255, 0, 0, 255, 0, 255, 0, 255]);
+
+        renderer.begin_scene(&CameraData {
+            projection: glm::ortho(0.0, 3.0, 0.0, 1.0, -1.0, 1.0),
+            ..CameraData::default()
+        });
+        let size = glm::vec2(1.0, 1.0);
+        renderer.draw_texture_layer(array, 1, glm::vec2(0.5, 0.5), size);
+        renderer.draw_texture_layer(array, 0, glm::vec2(1.5, 0.5), size);
+        renderer.draw_texture(texture, glm::vec2(2.5, 0.5), size);
+        renderer.end_scene().unwrap();
+
+        assert_eq!(renderer.get_stats().draw_calls, 2);
+        assert_eq!(
+            renderer.get_backend().read_pixels(),
+            [0, 255, 0, 255, 255, 0, 0, 255, 0, 0, 255, 255]
+        );
+    }
+}
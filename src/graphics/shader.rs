@@ -1,73 +1,350 @@
 
+use super::buffers::uniform_buffer::shared_block_binding;
+use super::gl_resource;
+use super::shader_cache;
+use super::shader_error::{parse_info_log, ShaderError, ShaderStage, UniformError};
+use super::shader_preprocessor::{load_stages, modified_time, PreprocessedSource};
+use super::shader_reflection::{
+    glsl_type_name, reflect, reflect_blocks, AttributeInfo, UniformBlockInfo, UniformInfo,
+    UniformValue,
+};
 use colored::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 pub struct Shader {
     m_renderer_id: u32,
     m_unfirom_location_cache: std::collections::HashMap<std::string::String, i32>,
+    m_file_path: String,
+    m_defines: Vec<(String, String)>,
+    m_watched_files: Vec<WatchedFile>,
+    m_uniforms: std::collections::HashMap<String, UniformInfo>,
+    m_attributes: Vec<AttributeInfo>,
+    m_uniform_blocks: Vec<UniformBlockInfo>,
+    m_block_bindings: std::collections::HashMap<String, u32>,
+    m_storage_bindings: std::collections::HashMap<String, u32>,
+    m_compute: bool,
+    m_work_group_size: [i32; 3],
 }
 
+/// a source file and its modification time when it was last read
+struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    /// a missing file counts as a change too
+    fn changed(&self) -> bool {
+        modified_time(&self.path) != self.modified
+    }
+}
+
+/// preprocessed sources of every stage in pipeline order and the files they were read from
+struct ShaderSource {
+    stages: Vec<(ShaderStage, PreprocessedSource)>,
+    files: Vec<WatchedFile>,
+}
+
+/// stages a graphics program is built from, in pipeline order
+const GRAPHICS_STAGES: [ShaderStage; 3] = [
+    ShaderStage::Vertex,
+    ShaderStage::Geometry,
+    ShaderStage::Fragment,
+];
+
 impl Shader {
     /// creates a new shader object
-    pub fn new(file_path: &str) -> Shader {
-        let source: (std::string::String, std::string::String) = Self::parse_shader(file_path);
-        Shader {
-            m_renderer_id: Self::create_shader(&source.0, &source.1),
+    ///
+    /// `file_path` is a directory with .vert and .frag files or a single file with
+    /// `#shader vertex` and `#shader fragment` sections, see `shader_preprocessor`.
+    /// a geometry stage is linked in when there is one, compute stages are left out
+    #[track_caller]
+    pub fn new(file_path: &str) -> Result<Shader, ShaderError> {
+        Self::with_defines(file_path, &[])
+    }
+
+    /// same as `new` with `#define name value` injected into every stage after `#version`
+    #[track_caller]
+    pub fn with_defines(file_path: &str, defines: &[(&str, &str)]) -> Result<Shader, ShaderError> {
+        Self::build(file_path, defines, false)
+    }
+
+    /// creates a compute program from the .comp file or `#shader compute` section at `file_path`,
+    /// other stages next to it are left out so a directory can hold a compute and a graphics program
+    #[track_caller]
+    pub fn new_compute(file_path: &str) -> Result<Shader, ShaderError> {
+        Self::compute_with_defines(file_path, &[])
+    }
+
+    /// same as `new_compute` with `#define name value` injected after `#version`
+    #[track_caller]
+    pub fn compute_with_defines(
+        file_path: &str,
+        defines: &[(&str, &str)],
+    ) -> Result<Shader, ShaderError> {
+        if !Self::is_compute_supported() {
+            return Err(ShaderError::Unsupported {
+                path: file_path.to_string(),
+                stage: ShaderStage::Compute,
+            });
+        }
+        Self::build(file_path, defines, true)
+    }
+
+    /// compute shaders are core since gl 4.3
+    pub fn is_compute_supported() -> bool {
+        if !gl::DispatchCompute::is_loaded() {
+            return false;
+        }
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+        (major, minor) >= (4, 3)
+    }
+
+    #[track_caller]
+    fn build(
+        file_path: &str,
+        defines: &[(&str, &str)],
+        compute: bool,
+    ) -> Result<Shader, ShaderError> {
+        let defines: Vec<(String, String)> = defines
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let source = Self::parse_shader(file_path, &defines, compute)?;
+        let mut shader = Shader {
+            m_renderer_id: Self::create_shader(&source.stages)?,
             m_unfirom_location_cache: std::collections::HashMap::new(),
+            m_file_path: file_path.to_string(),
+            m_defines: defines,
+            m_watched_files: source.files,
+            m_uniforms: std::collections::HashMap::new(),
+            m_attributes: Vec::new(),
+            m_uniform_blocks: Vec::new(),
+            m_block_bindings: std::collections::HashMap::new(),
+            m_storage_bindings: std::collections::HashMap::new(),
+            m_compute: compute,
+            m_work_group_size: [0; 3],
+        };
+        gl_resource::track("Shader", shader.m_renderer_id, 0);
+        shader.reflect();
+        Ok(shader)
+    }
+
+    /// records the active uniforms, attributes and uniform blocks, the uniform locations go
+    /// straight into the cache and the blocks are attached to their binding points
+    fn reflect(&mut self) {
+        let (uniforms, attributes) = reflect(self.m_renderer_id);
+        self.m_uniforms = uniforms
+            .into_iter()
+            .map(|uniform| (uniform.name.clone(), uniform))
+            .collect();
+        self.m_attributes = attributes;
+        for uniform in self.m_uniforms.values() {
+            self.m_unfirom_location_cache
+                .insert(uniform.name.clone(), uniform.location);
+        }
+
+        //shared blocks like Camera have a fixed binding, the rest keep what bind_uniform_block set
+        self.m_uniform_blocks = reflect_blocks(self.m_renderer_id);
+        for block in &self.m_uniform_blocks {
+            let binding = self
+                .m_block_bindings
+                .get(&block.name)
+                .copied()
+                .or_else(|| shared_block_binding(&block.name));
+            if let Some(binding) = binding {
+                unsafe {
+                    gl::UniformBlockBinding(self.m_renderer_id, block.index, binding);
+                }
+            }
+        }
+        for (name, &binding) in &self.m_storage_bindings {
+            Self::storage_block_binding(self.m_renderer_id, name, binding);
+        }
+
+        if self.m_compute {
+            unsafe {
+                gl::GetProgramiv(
+                    self.m_renderer_id,
+                    gl::COMPUTE_WORK_GROUP_SIZE,
+                    self.m_work_group_size.as_mut_ptr(),
+                );
+            }
+        }
+    }
+
+    /// recompiles the shader if any of its source files changed since they were read
+    ///
+    /// returns true if the program was swapped. if the new version fails the current program
+    /// stays in use and the error is only returned once, until the files change again
+    pub fn reload_if_changed(&mut self) -> Result<bool, ShaderError> {
+        if !self.m_watched_files.iter().any(WatchedFile::changed) {
+            return Ok(false);
+        }
+        for file in &mut self.m_watched_files {
+            file.modified = modified_time(&file.path);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// rebuilds the program from the source files, keeps the current program on failure
+    ///
+    /// uniform values don't carry over to the new program and have to be set again
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        let source = Self::parse_shader(&self.m_file_path, &self.m_defines, self.m_compute)?;
+        let program = Self::create_shader(&source.stages)?;
+        unsafe {
+            gl::DeleteProgram(self.m_renderer_id);
         }
+        gl_resource::retrack("Shader", self.m_renderer_id, program, 0);
+        self.m_renderer_id = program;
+        self.m_watched_files = source.files;
+
+        //cached locations belong to the old program, look the same names up again
+        let names: Vec<String> = self
+            .m_unfirom_location_cache
+            .drain()
+            .map(|(name, _)| name)
+            .collect();
+        self.reflect();
+        for name in names {
+            self.get_uniform_location(&name);
+        }
+        Ok(())
     }
 
-    /// parses the shader files and returns the source code tuple
-    fn parse_shader(file_path: &str) -> (std::string::String, std::string::String) {
-        let mut fragment_shader = String::new();
-        let mut vertex_shader = String::new();
+    /// preprocesses the shader files and returns the source of each stage the program needs
+    fn parse_shader(
+        file_path: &str,
+        defines: &[(String, String)],
+        compute: bool,
+    ) -> Result<ShaderSource, ShaderError> {
+        let (wanted, required): (&[ShaderStage], &[ShaderStage]) = if compute {
+            (&[ShaderStage::Compute], &[ShaderStage::Compute])
+        } else {
+            (
+                &GRAPHICS_STAGES,
+                &[ShaderStage::Vertex, ShaderStage::Fragment],
+            )
+        };
 
-        for file in std::fs::read_dir(file_path).unwrap() {
-            let file = file.unwrap();
-            match file.path().extension().unwrap().to_str().unwrap() {
-                "frag" => fragment_shader = std::fs::read_to_string(file.path()).unwrap(),
-                "vert" => vertex_shader = std::fs::read_to_string(file.path()).unwrap(),
-                _ => {}
+        let mut stages: Vec<(ShaderStage, PreprocessedSource)> = Vec::new();
+        let mut files: Vec<WatchedFile> = Vec::new();
+
+        for (stage, source) in load_stages(Path::new(file_path), wanted, defines)? {
+            //includes shared between stages only need to be watched once
+            for file in &source.files {
+                if !files.iter().any(|watched| watched.path == file.path) {
+                    files.push(WatchedFile {
+                        path: file.path.clone(),
+                        modified: file.modified,
+                    });
+                }
             }
+            stages.push((stage, source));
         }
 
-        (vertex_shader, fragment_shader)
+        for &stage in required {
+            if !stages.iter().any(|(found, _)| *found == stage) {
+                return Err(ShaderError::MissingStage {
+                    path: file_path.to_string(),
+                    stage,
+                });
+            }
+        }
+        stages.sort_by_key(|(stage, _)| wanted.iter().position(|known| known == stage));
+        Ok(ShaderSource { stages, files })
     }
 
-    /// compiles and binds shader programs
-    fn create_shader(vertex_shader: &str, fragment_shader: &str) -> u32 {
-        let program = unsafe { gl::CreateProgram() };
-        let vs = Self::compile_shader(gl::VERTEX_SHADER, vertex_shader);
-        let fs = Self::compile_shader(gl::FRAGMENT_SHADER, fragment_shader);
+    /// compiles and links the shader program, or loads it from the binary cache when that's enabled
+    fn create_shader(stages: &[(ShaderStage, PreprocessedSource)]) -> Result<u32, ShaderError> {
+        let cache = shader_cache::get_directory().map(|directory| {
+            let sources: Vec<&str> = stages
+                .iter()
+                .map(|(_, source)| source.code.as_str())
+                .collect();
+            (directory, shader_cache::cache_key(&sources))
+        });
+        if let Some((directory, key)) = &cache {
+            if let Some(program) = shader_cache::load(directory, *key) {
+                return Ok(program);
+            }
+        }
 
+        let mut shaders = Vec::with_capacity(stages.len());
+        for (stage, source) in stages {
+            match Self::compile_shader(*stage, source) {
+                Ok(id) => shaders.push(id),
+                Err(error) => {
+                    for id in shaders {
+                        unsafe { gl::DeleteShader(id) };
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        let program = unsafe { gl::CreateProgram() };
+        let mut result = gl::FALSE as i32;
         unsafe {
-            gl::AttachShader(program, vs);
-            gl::AttachShader(program, fs);
+            for &id in &shaders {
+                gl::AttachShader(program, id);
+            }
+            if cache.is_some() {
+                gl::ProgramParameteri(
+                    program,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as i32,
+                );
+            }
             gl::LinkProgram(program);
-            gl::ValidateProgram(program);
 
-            gl::DeleteShader(vs);
-            gl::DeleteShader(fs);
+            //the shaders aren't needed after linking either way
+            for id in shaders {
+                gl::DeleteShader(id);
+            }
+
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut result);
+        }
+        if result == gl::FALSE as i32 {
+            let log = Self::info_log(program, gl::GetProgramiv, gl::GetProgramInfoLog);
+            unsafe { gl::DeleteProgram(program) };
+            return Err(ShaderError::Link { log });
         }
 
-        program
+        unsafe { gl::ValidateProgram(program) };
+        if let Some((directory, key)) = &cache {
+            shader_cache::store(directory, *key, program);
+        }
+        Ok(program)
     }
 
-    /// binds the shader program
-    fn compile_shader(type_: u32, source: &str) -> u32 {
+    /// compiles a single stage, the info log is parsed into messages on failure
+    fn compile_shader(stage: ShaderStage, source: &PreprocessedSource) -> Result<u32, ShaderError> {
         println!(
             "{}",
-            format!(
-                "Compiling shader: {:?} shader...",
-                if type_ == gl::VERTEX_SHADER {
-                    "Vertex"
-                } else {
-                    "Fragment"
-                }
-            )
-            .cyan()
+            format!("Compiling shader: {} shader...", stage).cyan()
         );
-        let id = unsafe { gl::CreateShader(type_) };
-        let c_str = std::ffi::CString::new(source).unwrap();
+        let c_str = std::ffi::CString::new(source.code.as_str()).map_err(|error| {
+            //gl strings end at the first NUL, point at the line it is on
+            let line = source.code[..error.nul_position()].matches('\n').count() as u32 + 1;
+            let (path, line) = match source.locate(line) {
+                Some((file, line)) => (file.path.display().to_string(), line),
+                None => (String::new(), line),
+            };
+            ShaderError::Preprocess {
+                path,
+                line,
+                message: "NUL byte in shader source".to_string(),
+            }
+        })?;
+        let id = unsafe { gl::CreateShader(stage.gl_type()) };
         unsafe {
             gl::ShaderSource(id, 1, &c_str.as_ptr(), std::ptr::null());
             gl::CompileShader(id);
@@ -75,36 +352,39 @@ impl Shader {
 
         let mut result = gl::FALSE as i32;
         //get the status for shader error checking
-        unsafe {
-            gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut result);
-            if result == gl::FALSE as i32 {
-                let mut length = 0;
-                gl::GetShaderiv(id, gl::INFO_LOG_LENGTH, &mut length);
-                let mut message = Vec::with_capacity(length as usize);
-                message.set_len(length as usize);
-                gl::GetShaderInfoLog(
-                    id,
-                    length,
-                    std::ptr::null_mut(),
-                    message.as_mut_ptr() as *mut i8,
-                );
-                println!(
-                    "Failed to compile {:?} shader!",
-                    if type_ == gl::VERTEX_SHADER {
-                        "Vertex"
-                    } else {
-                        "Fragment"
-                    }
-                );
-                println!(
-                    "{:?}",
-                    std::str::from_utf8(&message).expect("Shader info log is not valid utf8")
-                );
-                gl::DeleteShader(id);
-                return 0;
-            }
+        unsafe { gl::GetShaderiv(id, gl::COMPILE_STATUS, &mut result) };
+        if result == gl::FALSE as i32 {
+            let log = Self::info_log(id, gl::GetShaderiv, gl::GetShaderInfoLog);
+            unsafe { gl::DeleteShader(id) };
+            return Err(ShaderError::Compile {
+                stage,
+                messages: parse_info_log(&log, source),
+                log,
+            });
         }
-        return id;
+        Ok(id)
+    }
+
+    /// reads the info log of a shader or program
+    fn info_log(
+        id: u32,
+        get_iv: unsafe fn(u32, u32, *mut i32),
+        get_log: unsafe fn(u32, i32, *mut i32, *mut gl::types::GLchar),
+    ) -> String {
+        let mut length = 0;
+        unsafe { get_iv(id, gl::INFO_LOG_LENGTH, &mut length) };
+        let mut message = vec![0u8; length.max(0) as usize];
+        let mut written = 0;
+        unsafe {
+            get_log(
+                id,
+                length,
+                &mut written,
+                message.as_mut_ptr() as *mut gl::types::GLchar,
+            )
+        };
+        message.truncate(written.max(0) as usize);
+        String::from_utf8_lossy(&message).into_owned()
     }
 
     pub fn bind(&self) {
@@ -119,18 +399,180 @@ impl Shader {
         }
     }
 
+    /// sets a uniform after checking `value` against the type the driver reported for it
+    ///
+    /// arrays take a slice, single elements can be set with names like `u_Lights[2]`.
+    /// like the other setters this expects the shader to be bound
+    pub fn set_uniform<T: UniformValue + ?Sized>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) -> Result<(), UniformError> {
+        let (info, index) = match self.m_uniforms.get(name) {
+            Some(info) => (info, 0),
+            None => {
+                let element = name
+                    .strip_suffix(']')
+                    .and_then(|name| name.split_once('['))
+                    //only plain digits, `parse` would take `-1` or `+1` too
+                    .filter(|(_, index)| index.bytes().all(|byte| byte.is_ascii_digit()))
+                    .and_then(|(base, index)| {
+                        Some((self.m_uniforms.get(base)?, index.parse().ok()?))
+                    });
+                element.ok_or_else(|| UniformError::Missing {
+                    name: name.to_string(),
+                })?
+            }
+        };
+
+        if !value.accepts(info.type_) {
+            return Err(UniformError::TypeMismatch {
+                name: name.to_string(),
+                expected: glsl_type_name(info.type_),
+                found: value.glsl_type(),
+            });
+        }
+        let available = (info.size - index).max(0);
+        if value.element_count() > available as usize {
+            return Err(UniformError::TooManyElements {
+                name: name.to_string(),
+                size: available,
+                found: value.element_count(),
+            });
+        }
+
+        let location = match index {
+            0 => info.location,
+            _ => self.get_uniform_location(name),
+        };
+        unsafe { value.upload(location) };
+        Ok(())
+    }
+
+    /// the program name, changes when a reload relinks
+    pub fn get_id(&self) -> u32 {
+        self.m_renderer_id
+    }
+
+    /// an active uniform, array names don't have the `[0]`
+    pub fn get_uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.m_uniforms.get(name)
+    }
+
+    pub fn get_uniforms(&self) -> impl Iterator<Item = &UniformInfo> {
+        self.m_uniforms.values()
+    }
+
+    /// active vertex attributes sorted by location
+    pub fn get_attributes(&self) -> &[AttributeInfo] {
+        &self.m_attributes
+    }
+
+    /// makes the uniform block `name` read from the `UniformBuffer` at `binding`, kept across reloads
+    pub fn bind_uniform_block(&mut self, name: &str, binding: u32) -> Result<(), UniformError> {
+        let block = self
+            .m_uniform_blocks
+            .iter()
+            .find(|block| block.name == name)
+            .ok_or_else(|| UniformError::Missing {
+                name: name.to_string(),
+            })?;
+        unsafe {
+            gl::UniformBlockBinding(self.m_renderer_id, block.index, binding);
+        }
+        self.m_block_bindings.insert(name.to_string(), binding);
+        Ok(())
+    }
+
+    pub fn get_uniform_blocks(&self) -> &[UniformBlockInfo] {
+        &self.m_uniform_blocks
+    }
+
+    /// makes the `buffer` block `name` read and write the `StorageBuffer` at `binding`,
+    /// kept across reloads. not needed for blocks with a `binding = n` layout qualifier
+    pub fn bind_storage_block(&mut self, name: &str, binding: u32) -> Result<(), UniformError> {
+        if !Self::is_compute_supported() {
+            return Err(UniformError::Unsupported {
+                name: name.to_string(),
+            });
+        }
+        if !Self::storage_block_binding(self.m_renderer_id, name, binding) {
+            return Err(UniformError::Missing {
+                name: name.to_string(),
+            });
+        }
+        self.m_storage_bindings.insert(name.to_string(), binding);
+        Ok(())
+    }
+
+    /// false if the program has no storage block called `name`
+    fn storage_block_binding(program: u32, name: &str, binding: u32) -> bool {
+        let c_str = std::ffi::CString::new(name).unwrap();
+        let index = unsafe {
+            gl::GetProgramResourceIndex(program, gl::SHADER_STORAGE_BLOCK, c_str.as_ptr())
+        };
+        if index == gl::INVALID_INDEX {
+            return false;
+        }
+        unsafe { gl::ShaderStorageBlockBinding(program, index, binding) };
+        true
+    }
+
+    /// runs a compute shader on `x` * `y` * `z` work groups
+    ///
+    /// writes only show up in later draws and dispatches after `Renderer::memory_barrier`
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        assert!(
+            self.m_compute,
+            "{} isn't a compute shader",
+            self.m_file_path
+        );
+        self.bind();
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+        }
+    }
+
+    /// dispatches enough work groups to cover `width` * `height` * `depth` invocations,
+    /// the shader has to skip the ones past the end itself
+    pub fn dispatch_for(&self, width: u32, height: u32, depth: u32) {
+        let [x, y, z] = self.m_work_group_size.map(|size| size.max(1) as u32);
+        self.dispatch(width.div_ceil(x), height.div_ceil(y), depth.div_ceil(z));
+    }
+
+    /// the `local_size_x/y/z` of a compute shader, zeros for graphics programs
+    pub fn get_work_group_size(&self) -> [i32; 3] {
+        self.m_work_group_size
+    }
+
     pub fn set_uniform1i(&mut self, name: &str, value: i32) {
         unsafe {
             gl::Uniform1i(self.get_uniform_location(name), value);
         }
     }
 
+    pub fn set_uniform1iv(&mut self, name: &str, values: &[i32]) {
+        unsafe {
+            gl::Uniform1iv(
+                self.get_uniform_location(name),
+                values.len() as i32,
+                values.as_ptr(),
+            );
+        }
+    }
+
     pub fn set_uniform1f(&mut self, name: &str, value: f32) {
         unsafe {
             gl::Uniform1f(self.get_uniform_location(name), value);
         }
     }
 
+    pub fn set_uniform2f(&mut self, name: &str, v0: f32, v1: f32) {
+        unsafe {
+            gl::Uniform2f(self.get_uniform_location(name), v0, v1);
+        }
+    }
+
     pub fn set_uniform4f(&mut self, name: &str, v0: f32, v1: f32, v2: f32, v3: f32) {
         unsafe {
             gl::Uniform4f(self.get_uniform_location(name), v0, v1, v2, v3);
@@ -172,3 +614,504 @@ impl Shader {
         location
     }
 }
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        if gl_resource::release("Shader", self.m_renderer_id) {
+            unsafe { gl::DeleteProgram(self.m_renderer_id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{
+        fail_compile, fail_link, set_active_attributes, set_active_storage_blocks,
+        set_active_uniform_blocks, set_active_uniforms, set_version, with_mock_gl, GlCall,
+    };
+    use crate::testing::scratch_dir;
+
+    #[test]
+    fn uniform_locations_are_cached() {
+        let (_, calls) = with_mock_gl(|| {
+            let mut shader = Shader::new("res/shaders").unwrap();
+            shader.set_uniform1i("u_Texture", 0);
+            shader.set_uniform1i("u_Texture", 1);
+            shader.set_uniform_mat4f("u_MVP", &glm::Mat4::identity());
+        });
+
+        let lookups: Vec<&str> = calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::GetUniformLocation(_, name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lookups, ["u_Texture", "u_MVP"]);
+
+        let locations: Vec<i32> = calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::Uniform1i(location, _) => Some(*location),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0], locations[1]);
+    }
+
+    #[test]
+    fn compile_errors_point_at_the_source_line() {
+        let (result, calls) = with_mock_gl(|| {
+            fail_compile(gl::FRAGMENT_SHADER, "0:3(1): error: syntax error\n");
+            Shader::new("res/shaders")
+        });
+
+        match result {
+            Err(ShaderError::Compile {
+                stage, messages, ..
+            }) => {
+                assert_eq!(stage, ShaderStage::Fragment);
+                assert_eq!(messages[0].line, Some(3));
+                let snippet = messages[0].snippet.as_deref().unwrap();
+                assert!(snippet.contains(">    3 | layout(location = 0) out vec4 color;"));
+            }
+            _ => panic!("expected a compile error"),
+        }
+        //nothing is left behind
+        assert!(!calls
+            .iter()
+            .any(|call| matches!(call, GlCall::CreateProgram(_))));
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| matches!(call, GlCall::DeleteShader(_)))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn link_errors_delete_the_program() {
+        let (result, calls) = with_mock_gl(|| {
+            fail_link("error: v_TexCoord not written by vertex shader");
+            Shader::new("res/shaders")
+        });
+
+        match result {
+            Err(ShaderError::Link { log }) => {
+                assert_eq!(log, "error: v_TexCoord not written by vertex shader")
+            }
+            _ => panic!("expected a link error"),
+        }
+        let program = calls.iter().find_map(|call| match call {
+            GlCall::CreateProgram(program) => Some(*program),
+            _ => None,
+        });
+        assert!(calls.contains(&GlCall::DeleteProgram(program.unwrap())));
+    }
+
+    #[test]
+    fn missing_files_are_io_errors() {
+        assert!(matches!(
+            Shader::new("res/shaders/does_not_exist"),
+            Err(ShaderError::Io { .. })
+        ));
+        //the textures directory has no .vert or .frag files
+        assert!(matches!(
+            Shader::new("res/textures"),
+            Err(ShaderError::MissingStage {
+                stage: ShaderStage::Vertex,
+                ..
+            })
+        ));
+    }
+
+    /// copies res/shaders into a fresh directory the test can edit
+    fn scratch_shader(name: &str) -> PathBuf {
+        let directory = scratch_dir(name);
+        for file in ["vertexShader.vert", "fragmentShader.frag"] {
+            std::fs::copy(Path::new("res/shaders").join(file), directory.join(file)).unwrap();
+        }
+        directory
+    }
+
+    /// pushes the modification time forward, a rewrite within the same tick wouldn't be noticed
+    fn touch(path: &Path, seconds: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn reload_swaps_the_program_when_files_change() {
+        let directory = scratch_shader("reload_swaps");
+        let ((old, new, unchanged), calls) = with_mock_gl(|| {
+            let mut shader = Shader::new(directory.to_str().unwrap()).unwrap();
+            shader.set_uniform1i("u_Texture", 0);
+            let old = shader.m_renderer_id;
+
+            touch(&directory.join("fragmentShader.frag"), 10);
+            assert!(shader.reload_if_changed().unwrap());
+            let new = shader.m_renderer_id;
+            (old, new, shader.reload_if_changed().unwrap())
+        });
+
+        assert_ne!(old, new);
+        assert!(!unchanged);
+        assert!(calls.contains(&GlCall::DeleteProgram(old)));
+        //the cache is filled again for the new program
+        assert!(calls.contains(&GlCall::GetUniformLocation(new, "u_Texture".to_string())));
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_old_program() {
+        let directory = scratch_shader("failed_reload");
+        let (_shader, calls) = with_mock_gl(|| {
+            let mut shader = Shader::new(directory.to_str().unwrap()).unwrap();
+            let old = shader.m_renderer_id;
+
+            fail_compile(gl::FRAGMENT_SHADER, "0:1(1): error: oops");
+            touch(&directory.join("fragmentShader.frag"), 20);
+            assert!(matches!(
+                shader.reload_if_changed(),
+                Err(ShaderError::Compile { .. })
+            ));
+            assert_eq!(shader.m_renderer_id, old);
+            //the error is reported once, not every frame
+            assert!(!shader.reload_if_changed().unwrap());
+            shader
+        });
+
+        assert!(!calls
+            .iter()
+            .any(|call| matches!(call, GlCall::DeleteProgram(_))));
+    }
+
+    #[test]
+    fn nul_bytes_are_preprocess_errors() {
+        let directory = scratch_shader("nul_byte");
+        std::fs::write(
+            directory.join("fragmentShader.frag"),
+            "#version 330 core\nvoid main() {}\0\n",
+        )
+        .unwrap();
+
+        let (result, calls) = with_mock_gl(|| Shader::new(directory.to_str().unwrap()));
+
+        match result {
+            Err(ShaderError::Preprocess { path, line, .. }) => {
+                assert!(path.ends_with("fragmentShader.frag"));
+                assert_eq!(line, 2);
+            }
+            _ => panic!("expected a preprocess error"),
+        }
+        //the vertex stage compiled before is cleaned up
+        assert!(calls
+            .iter()
+            .any(|call| matches!(call, GlCall::DeleteShader(_))));
+    }
+
+    #[test]
+    fn compile_errors_in_includes_name_the_included_file() {
+        let directory = scratch_dir("include_errors");
+        std::fs::write(
+            directory.join("shader.glsl"),
+            "#shader vertex\n#version 330 core\nvoid main() {}\n#shader fragment\n#version 330 core\n#include \"common.glsl\"\nvoid main() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("common.glsl"),
+            "#pragma once\nvec4 broken(\n",
+        )
+        .unwrap();
+
+        //line 3 of what the driver sees is line 2 of common.glsl, the define sits on line 2
+        let (result, calls) = with_mock_gl(|| {
+            fail_compile(gl::FRAGMENT_SHADER, "0:3(1): error: syntax error");
+            Shader::with_defines(
+                directory.join("shader.glsl").to_str().unwrap(),
+                &[("FOG", "1")],
+            )
+        });
+
+        let fragment = calls.iter().find_map(|call| match call {
+            GlCall::ShaderSource(_, source) if source.contains("broken") => Some(source.clone()),
+            _ => None,
+        });
+        assert_eq!(
+            fragment.as_deref(),
+            Some("#version 330 core\n#define FOG 1\nvec4 broken(\nvoid main() {}\n")
+        );
+        match result {
+            Err(ShaderError::Compile { messages, .. }) => {
+                assert!(messages[0].file.as_ref().unwrap().ends_with("common.glsl"));
+                assert_eq!(messages[0].line, Some(2));
+            }
+            _ => panic!("expected a compile error"),
+        }
+    }
+
+    #[test]
+    fn set_uniform_checks_reflected_types() {
+        let (results, calls) = with_mock_gl(|| {
+            set_active_uniforms(&[
+                ("u_Color", gl::FLOAT_VEC4, 1),
+                ("u_Offsets[0]", gl::FLOAT_VEC2, 4),
+                ("u_Texture", gl::SAMPLER_2D, 1),
+            ]);
+            let mut shader = Shader::new("res/shaders").unwrap();
+            let offsets = [glm::vec2(1.0, 2.0), glm::vec2(3.0, 4.0)];
+            vec![
+                shader.set_uniform("u_Color", &glm::vec4(1.0, 0.5, 0.0, 1.0)),
+                shader.set_uniform("u_Offsets", &offsets[..]),
+                shader.set_uniform("u_Offsets[3]", &glm::vec2(5.0, 6.0)),
+                shader.set_uniform("u_Texture", &2),
+                shader.set_uniform("u_Color", &1.0),
+                shader.set_uniform("u_Offsets[3]", &offsets[..]),
+                shader.set_uniform("u_Missing", &1.0),
+            ]
+        });
+
+        assert_eq!(results[..4], [Ok(()), Ok(()), Ok(()), Ok(())]);
+        assert_eq!(
+            results[4],
+            Err(UniformError::TypeMismatch {
+                name: "u_Color".to_string(),
+                expected: "vec4",
+                found: "float".to_string(),
+            })
+        );
+        assert!(matches!(
+            results[5],
+            Err(UniformError::TooManyElements {
+                size: 1,
+                found: 2,
+                ..
+            })
+        ));
+        assert!(matches!(results[6], Err(UniformError::Missing { .. })));
+
+        let uploads: Vec<&GlCall> = calls
+            .iter()
+            .filter(|call| {
+                matches!(
+                    call,
+                    GlCall::Uniformfv { .. } | GlCall::Uniform1iv(..) | GlCall::Uniform1i(..)
+                )
+            })
+            .collect();
+        assert_eq!(uploads.len(), 4);
+        assert!(matches!(
+            uploads[1],
+            GlCall::Uniformfv {
+                components: 2,
+                values,
+                ..
+            } if values == &[1.0, 2.0, 3.0, 4.0]
+        ));
+    }
+
+    #[test]
+    fn unsigned_and_bool_vectors_and_integer_samplers_can_be_set() {
+        let (results, calls) = with_mock_gl(|| {
+            set_active_uniforms(&[
+                ("u_Size", gl::UNSIGNED_INT_VEC2, 1),
+                ("u_Mask[0]", gl::BOOL_VEC3, 2),
+                ("u_Ids", gl::UNSIGNED_INT_SAMPLER_BUFFER, 1),
+                ("u_Layers", gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY, 1),
+            ]);
+            let mut shader = Shader::new("res/shaders").unwrap();
+            vec![
+                shader.set_uniform("u_Size", &glm::vec2(640u32, 480)),
+                shader.set_uniform("u_Mask", &[glm::vec3(true, false, true)][..]),
+                shader.set_uniform("u_Ids", &3),
+                shader.set_uniform("u_Layers", &4),
+                shader.set_uniform("u_Mask[-1]", &glm::vec3(true, true, true)),
+                shader.set_uniform("u_Size", &glm::vec2(1, 2)),
+            ]
+        });
+
+        assert_eq!(results[..4], [Ok(()), Ok(()), Ok(()), Ok(())]);
+        assert!(matches!(results[4], Err(UniformError::Missing { .. })));
+        assert_eq!(
+            results[5],
+            Err(UniformError::TypeMismatch {
+                name: "u_Size".to_string(),
+                expected: "uvec2",
+                found: "ivec2".to_string(),
+            })
+        );
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::Uniformuiv {
+                components: 2,
+                values,
+                ..
+            } if values == &[640, 480]
+        )));
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::Uniformiv {
+                components: 3,
+                values,
+                ..
+            } if values == &[1, 0, 1]
+        )));
+    }
+
+    #[test]
+    fn attributes_are_reflected_in_location_order() {
+        let (shader, _) = with_mock_gl(|| {
+            set_active_attributes(&[
+                ("a_Position", gl::FLOAT_VEC4, 1),
+                ("a_TexCoord", gl::FLOAT_VEC2, 1),
+            ]);
+            Shader::new("res/shaders").unwrap()
+        });
+
+        let attributes: Vec<(&str, i32, u32)> = shader
+            .get_attributes()
+            .iter()
+            .map(|attribute| (attribute.name.as_str(), attribute.location, attribute.type_))
+            .collect();
+        assert_eq!(
+            attributes,
+            [
+                ("a_Position", 0, gl::FLOAT_VEC4),
+                ("a_TexCoord", 1, gl::FLOAT_VEC2)
+            ]
+        );
+    }
+
+    #[test]
+    fn uniform_blocks_stay_bound_across_reloads() {
+        let ((old, new, missing), calls) = with_mock_gl(|| {
+            set_active_uniform_blocks(&[("Lights", 64), ("Camera", 208)]);
+            let mut shader = Shader::new("res/shaders").unwrap();
+            let old = shader.m_renderer_id;
+            shader.bind_uniform_block("Lights", 3).unwrap();
+            shader.reload().unwrap();
+            let missing = shader.bind_uniform_block("Fog", 1);
+            (old, shader.m_renderer_id, missing)
+        });
+
+        assert!(matches!(missing, Err(UniformError::Missing { .. })));
+        let bindings: Vec<(u32, u32, u32)> = calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::UniformBlockBinding {
+                    program,
+                    block,
+                    binding,
+                } => Some((*program, *block, *binding)),
+                _ => None,
+            })
+            .collect();
+        //Camera is shared and bound on its own, Lights only after it was asked for
+        assert_eq!(
+            bindings,
+            [(old, 1, 0), (old, 0, 3), (new, 0, 3), (new, 1, 0)]
+        );
+    }
+
+    /// a shader directory with every stage, the compute stage belongs to its own program
+    fn scratch_all_stages(name: &str) -> PathBuf {
+        let directory = scratch_shader(name);
+        std::fs::write(
+            directory.join("points.geom"),
+            "#version 330 core\nlayout(points) in;\nlayout(points, max_vertices = 1) out;\nvoid main() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("simulate.comp"),
+            "#version 430 core\nlayout(local_size_x = 64) in;\nvoid main() {}\n",
+        )
+        .unwrap();
+        directory
+    }
+
+    fn created_stages(calls: &[GlCall]) -> Vec<u32> {
+        calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::CreateShader(type_, _) => Some(*type_),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn geometry_stages_are_linked_between_vertex_and_fragment() {
+        let directory = scratch_all_stages("geometry_stage");
+        let (_, calls) = with_mock_gl(|| Shader::new(directory.to_str().unwrap()).unwrap());
+
+        assert_eq!(
+            created_stages(&calls),
+            [gl::VERTEX_SHADER, gl::GEOMETRY_SHADER, gl::FRAGMENT_SHADER]
+        );
+    }
+
+    #[test]
+    fn compute_shaders_dispatch_enough_work_groups() {
+        let directory = scratch_all_stages("compute_stage");
+        let ((shader, missing), calls) = with_mock_gl(|| {
+            set_active_storage_blocks(&["Particles"]);
+            let mut shader = Shader::new_compute(directory.to_str().unwrap()).unwrap();
+            shader.bind_storage_block("Particles", 2).unwrap();
+            let missing = shader.bind_storage_block("Grid", 3);
+            shader.dispatch_for(1000, 1, 1);
+            (shader, missing)
+        });
+        let shader = shader.m_renderer_id;
+
+        assert!(matches!(missing, Err(UniformError::Missing { .. })));
+        assert_eq!(created_stages(&calls), [gl::COMPUTE_SHADER]);
+        assert!(calls.contains(&GlCall::ShaderStorageBlockBinding {
+            program: shader,
+            block: 0,
+            binding: 2,
+        }));
+        //1000 invocations in groups of 64
+        assert_eq!(
+            calls[calls.len() - 2..],
+            [
+                GlCall::UseProgram(shader),
+                GlCall::DispatchCompute(16, 1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn compute_shaders_need_gl_4_3() {
+        let directory = scratch_all_stages("compute_unsupported");
+        let (result, calls) = with_mock_gl(|| {
+            set_version(3, 3);
+            Shader::new_compute(directory.to_str().unwrap())
+        });
+
+        assert!(matches!(
+            result,
+            Err(ShaderError::Unsupported {
+                stage: ShaderStage::Compute,
+                ..
+            })
+        ));
+        assert!(created_stages(&calls).is_empty());
+    }
+
+    #[test]
+    fn storage_blocks_need_gl_4_3() {
+        let (result, calls) = with_mock_gl(|| {
+            set_version(3, 3);
+            let mut shader = Shader::new("res/shaders").unwrap();
+            shader.bind_storage_block("Particles", 0)
+        });
+
+        assert!(matches!(result, Err(UniformError::Unsupported { .. })));
+        assert!(!calls
+            .iter()
+            .any(|call| matches!(call, GlCall::ShaderStorageBlockBinding { .. })));
+    }
+}
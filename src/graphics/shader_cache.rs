@@ -0,0 +1,217 @@
+//! on disk cache of linked program binaries so startup doesn't have to compile every shader
+//!
+//! the cache is off until `enable` is called. binaries are keyed by a hash of the preprocessed
+//! sources and the driver vendor/renderer/version, a driver update just misses the cache.
+//! drivers are allowed to reject binaries they wrote themselves, those files are deleted and
+//! the program is compiled from source again
+use colored::*;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// first bytes of every cache file
+const MAGIC: &[u8; 4] = b"LRPB";
+
+thread_local! {
+    //gl contexts are current on one thread, so is the cache
+    static DIRECTORY: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// programs linked on this thread from now on are cached in `directory`
+pub fn enable(directory: impl Into<PathBuf>) {
+    DIRECTORY.with(|cache| *cache.borrow_mut() = Some(directory.into()));
+}
+
+pub fn disable() {
+    DIRECTORY.with(|cache| *cache.borrow_mut() = None);
+}
+
+/// the cache directory if caching is enabled and the driver can hand out program binaries
+pub fn get_directory() -> Option<PathBuf> {
+    let directory = DIRECTORY.with(|cache| cache.borrow().clone())?;
+    if !gl::GetProgramBinary::is_loaded() || !gl::ProgramBinary::is_loaded() {
+        return None;
+    }
+    let mut formats = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+    (formats > 0).then_some(directory)
+}
+
+/// 64 bit FNV-1a, stable across runs unlike `DefaultHasher`
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// hash of the stage sources and the driver that would compile them
+pub fn cache_key(sources: &[&str]) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    for source in sources {
+        hash = fnv1a(hash, source.as_bytes());
+        //keeps "ab" + "c" apart from "a" + "bc"
+        hash = fnv1a(hash, &[0]);
+    }
+    for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+        let string = unsafe { gl::GetString(name) };
+        if !string.is_null() {
+            let string = unsafe { std::ffi::CStr::from_ptr(string as *const std::ffi::c_char) };
+            hash = fnv1a(hash, string.to_bytes());
+        }
+        hash = fnv1a(hash, &[0]);
+    }
+    hash
+}
+
+fn cache_file(directory: &Path, key: u64) -> PathBuf {
+    directory.join(format!("{:016x}.bin", key))
+}
+
+/// creates a program from the cached binary for `key`, None if there is none or the driver
+/// rejected it
+pub fn load(directory: &Path, key: u64) -> Option<u32> {
+    let path = cache_file(directory, key);
+    let bytes = std::fs::read(&path).ok()?;
+
+    //magic, binary format, binary
+    let binary = bytes.strip_prefix(MAGIC).filter(|rest| rest.len() > 4);
+    let Some(binary) = binary else {
+        let _ = std::fs::remove_file(&path);
+        return None;
+    };
+    let format = u32::from_le_bytes(binary[..4].try_into().unwrap());
+    let binary = &binary[4..];
+
+    let program = unsafe { gl::CreateProgram() };
+    let mut result = gl::FALSE as i32;
+    unsafe {
+        gl::ProgramBinary(
+            program,
+            format,
+            binary.as_ptr() as *const std::ffi::c_void,
+            binary.len() as i32,
+        );
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut result);
+    }
+    if result == gl::FALSE as i32 {
+        println!(
+            "{}",
+            format!(
+                "Driver rejected cached shader {}, recompiling",
+                path.display()
+            )
+            .yellow()
+        );
+        unsafe { gl::DeleteProgram(program) };
+        let _ = std::fs::remove_file(&path);
+        return None;
+    }
+
+    println!(
+        "{}",
+        format!("Loaded shader from cache: {}", path.display()).cyan()
+    );
+    Some(program)
+}
+
+/// writes the binary of a linked program for `key`
+///
+/// the program has to be linked with `PROGRAM_BINARY_RETRIEVABLE_HINT` set. failing to write
+/// only costs a compile next time so it's printed and otherwise ignored
+pub fn store(directory: &Path, key: u64, program: u32) {
+    let mut length = 0;
+    unsafe { gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length) };
+    if length <= 0 {
+        return;
+    }
+
+    let mut binary = vec![0u8; length as usize];
+    let mut written = 0;
+    let mut format = 0;
+    unsafe {
+        gl::GetProgramBinary(
+            program,
+            length,
+            &mut written,
+            &mut format,
+            binary.as_mut_ptr() as *mut std::ffi::c_void,
+        );
+    }
+    binary.truncate(written.max(0) as usize);
+
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 4 + binary.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&format.to_le_bytes());
+    bytes.extend_from_slice(&binary);
+
+    let path = cache_file(directory, key);
+    let result = std::fs::create_dir_all(directory).and_then(|_| std::fs::write(&path, bytes));
+    if let Err(err) = result {
+        println!(
+            "{}",
+            format!("Failed to write shader cache {}: {}", path.display(), err).yellow()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::shader::Shader;
+    use crate::testing::mock_gl::{reject_program_binaries, with_mock_gl, GlCall};
+    use crate::testing::scratch_dir;
+
+    fn compiled_stages(calls: &[GlCall]) -> usize {
+        calls
+            .iter()
+            .filter(|call| matches!(call, GlCall::CreateShader(..)))
+            .count()
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_values() {
+        assert_eq!(fnv1a(0xcbf29ce484222325, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(0xcbf29ce484222325, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(0xcbf29ce484222325, b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn cached_programs_skip_compilation() {
+        let directory = scratch_dir("cached_programs");
+        let (_, calls) = with_mock_gl(|| {
+            enable(&directory);
+            Shader::new("res/shaders").unwrap();
+            Shader::new("res/shaders").unwrap();
+            disable();
+        });
+
+        assert_eq!(compiled_stages(&calls), 2);
+        assert!(calls.contains(&GlCall::ProgramParameteri(
+            3,
+            gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+            gl::TRUE as i32
+        )));
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::ProgramBinary { binary, .. } if binary == b"mock program 3"
+        )));
+    }
+
+    #[test]
+    fn rejected_binaries_are_compiled_from_source() {
+        let directory = scratch_dir("rejected_binaries");
+        let (shader, calls) = with_mock_gl(|| {
+            enable(&directory);
+            Shader::new("res/shaders").unwrap();
+            reject_program_binaries();
+            let shader = Shader::new("res/shaders");
+            disable();
+            shader
+        });
+
+        assert!(shader.is_ok());
+        assert_eq!(compiled_stages(&calls), 4);
+        //the rejected program is thrown away and the cache is written again
+        assert!(calls.contains(&GlCall::DeleteProgram(4)));
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,307 @@
+use super::shader_preprocessor::PreprocessedSource;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    /// optional, between vertex and fragment
+    Geometry,
+    Fragment,
+    /// only in compute programs, needs gl 4.3
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(&self) -> u32 {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "Vertex"),
+            ShaderStage::Geometry => write!(f, "Geometry"),
+            ShaderStage::Fragment => write!(f, "Fragment"),
+            ShaderStage::Compute => write!(f, "Compute"),
+        }
+    }
+}
+
+/// one line of a compiler info log, with the source around it when the line number could be parsed
+///
+/// `file` and `line` point into the original files, not the preprocessed source the driver saw
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileMessage {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    /// the shader directory has no source for a required stage
+    MissingStage {
+        path: String,
+        stage: ShaderStage,
+    },
+    /// the context is too old for a stage the shader uses
+    Unsupported {
+        path: String,
+        stage: ShaderStage,
+    },
+    /// a bad `#include` or `#shader` directive
+    Preprocess {
+        path: String,
+        line: u32,
+        message: String,
+    },
+    Compile {
+        stage: ShaderStage,
+        messages: Vec<CompileMessage>,
+        log: String,
+    },
+    Link {
+        log: String,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => write!(f, "Failed to read {}: {}", path, error),
+            ShaderError::MissingStage { path, stage } => {
+                write!(f, "No {} shader found in {}", stage, path)
+            }
+            ShaderError::Unsupported { path, stage } => write!(
+                f,
+                "{} shaders aren't supported by this OpenGL context ({})",
+                stage, path
+            ),
+            ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+            ShaderError::Compile {
+                stage, messages, ..
+            } => {
+                write!(f, "Failed to compile {} shader!", stage)?;
+                for message in messages {
+                    match (&message.file, message.line) {
+                        (Some(file), Some(line)) => {
+                            write!(f, "\n{}:{}: {}", file, line, message.message)?
+                        }
+                        (None, Some(line)) => write!(f, "\nline {}: {}", line, message.message)?,
+                        _ => write!(f, "\n{}", message.message)?,
+                    }
+                    if let Some(snippet) = &message.snippet {
+                        write!(f, "\n{}", snippet)?;
+                    }
+                }
+                Ok(())
+            }
+            ShaderError::Link { log } => write!(f, "Failed to link shader program!\n{}", log),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShaderError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// returned by `Shader::set_uniform` instead of letting gl fail silently
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformError {
+    /// not an active uniform, it doesn't exist or was optimized out
+    Missing { name: String },
+    TypeMismatch {
+        name: String,
+        expected: &'static str,
+        found: String,
+    },
+    /// more values than the array has elements
+    TooManyElements {
+        name: String,
+        size: i32,
+        found: usize,
+    },
+    /// storage blocks need gl 4.3, like compute shaders
+    Unsupported { name: String },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformError::Missing { name } => write!(f, "uniform '{}' doesn't exist", name),
+            UniformError::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "uniform '{}' is a {} but was set with a {}",
+                name, expected, found
+            ),
+            UniformError::TooManyElements { name, size, found } => write!(
+                f,
+                "uniform '{}' has {} elements but was set with {}",
+                name, size, found
+            ),
+            UniformError::Unsupported { name } => write!(
+                f,
+                "storage block '{}' isn't supported by this OpenGL context",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UniformError {}
+
+/// splits an info log into messages and maps them back to the files they came from
+///
+/// drivers disagree on the format, these are handled:
+/// `0(12) : error C0000: ...` (nvidia), `0:12(5): error: ...` (mesa) and `ERROR: 0:12: ...` (amd, intel)
+pub fn parse_info_log(log: &str, source: &PreprocessedSource) -> Vec<CompileMessage> {
+    log.lines()
+        .map(|line| line.trim().trim_end_matches('\0'))
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let Some((number, message)) = parse_location(line) else {
+                return CompileMessage {
+                    file: None,
+                    line: None,
+                    message: line.to_string(),
+                    snippet: None,
+                };
+            };
+            match source.locate(number) {
+                Some((file, line)) => CompileMessage {
+                    file: Some(file.path.display().to_string()),
+                    line: Some(line),
+                    message,
+                    snippet: snippet(&file.source, line),
+                },
+                //injected defines aren't in any file, show what the driver saw
+                None => CompileMessage {
+                    file: None,
+                    line: Some(number),
+                    message,
+                    snippet: snippet(&source.code, number),
+                },
+            }
+        })
+        .collect()
+}
+
+/// the line number and the message without the location prefix
+fn parse_location(message: &str) -> Option<(u32, String)> {
+    let (severity, rest) = match message.split_once(": ") {
+        Some((severity @ ("ERROR" | "WARNING"), rest)) => (Some(severity), rest),
+        _ => (None, message),
+    };
+
+    //skip the source string index
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    if digits == 0 {
+        return None;
+    }
+    let rest = &rest[digits..];
+    let rest = rest.strip_prefix('(').or_else(|| rest.strip_prefix(':'))?;
+
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let line = rest[..end].parse().ok()?;
+
+    //mesa adds the column in parentheses, nvidia closes the line number with one
+    let mut rest = &rest[end..];
+    if let Some(column) = rest.strip_prefix('(') {
+        rest = column.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let rest = rest
+        .trim_start_matches(')')
+        .trim_start()
+        .trim_start_matches(':')
+        .trim_start();
+
+    let message = match severity {
+        Some(severity) => format!("{}: {}", severity, rest),
+        None => rest.to_string(),
+    };
+    Some((line, message))
+}
+
+/// the line with one line of context on each side, the offending line is marked with `>`
+fn snippet(source: &str, line: u32) -> Option<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let index = (line as usize).checked_sub(1)?;
+    if index >= lines.len() {
+        return None;
+    }
+
+    let first = index.saturating_sub(1);
+    let last = (index + 1).min(lines.len() - 1);
+    let snippet = (first..=last)
+        .map(|i| {
+            let marker = if i == index { ">" } else { " " };
+            format!("{} {:4} | {}", marker, i + 1, lines[i])
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::shader_preprocessor::preprocess_source;
+    use std::path::Path;
+
+    #[test]
+    fn parses_line_numbers_from_common_drivers() {
+        assert_eq!(
+            parse_location("0(12) : error C1008: undefined variable"),
+            Some((12, "error C1008: undefined variable".to_string()))
+        );
+        assert_eq!(
+            parse_location("0:7(15): error: `foo' undeclared"),
+            Some((7, "error: `foo' undeclared".to_string()))
+        );
+        assert_eq!(
+            parse_location("ERROR: 0:3: 'x' : undeclared identifier"),
+            Some((3, "ERROR: 'x' : undeclared identifier".to_string()))
+        );
+        assert_eq!(parse_location("error: linking failed"), None);
+    }
+
+    #[test]
+    fn snippet_marks_the_failing_line() {
+        let source = "#version 330 core\nvoid main() {\n\tfoo;\n}";
+        let source = preprocess_source(Path::new("test.frag"), source, &[]).unwrap();
+        let messages = parse_info_log("0:3(2): error: `foo' undeclared\n", &source);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].file.as_deref(), Some("test.frag"));
+        assert_eq!(messages[0].line, Some(3));
+        assert_eq!(
+            messages[0].snippet.as_deref(),
+            Some("     2 | void main() {\n>    3 | \tfoo;\n     4 | }")
+        );
+    }
+}
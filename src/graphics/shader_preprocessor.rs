@@ -0,0 +1,474 @@
+//! turns shader files into the source that is handed to the driver
+//!
+//! - `#include "file.glsl"` pastes a file, paths are relative to the including file
+//! - `#pragma once` or a classic `#ifndef NAME` / `#define NAME` guard keeps a file from being pasted twice
+//! - defines passed in are injected right after `#version`
+//! - a single file can hold every stage in `#shader vertex` / `#shader fragment` sections,
+//!   `#shader geometry` and `#shader compute` work the same way
+//!
+//! every output line remembers where it came from so compile errors point at the right file and line
+
+use super::shader_error::{ShaderError, ShaderStage};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// a file that went into a shader, kept for error snippets and hot reloading
+#[derive(Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+    /// modification time from right before the file was read
+    pub modified: Option<SystemTime>,
+}
+
+/// where a line of the output came from, `file` indexes `PreprocessedSource::files`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceLocation {
+    pub file: usize,
+    pub line: u32,
+}
+
+pub struct PreprocessedSource {
+    pub code: String,
+    /// one entry per line of `code`, None for injected lines
+    pub lines: Vec<Option<SourceLocation>>,
+    pub files: Vec<SourceFile>,
+}
+
+impl PreprocessedSource {
+    /// maps a 1 based line of `code` back to the file and line it came from
+    pub fn locate(&self, line: u32) -> Option<(&SourceFile, u32)> {
+        let location = (*self.lines.get((line as usize).checked_sub(1)?)?)?;
+        Some((&self.files[location.file], location.line))
+    }
+}
+
+/// None if the file is gone
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn read_source(path: &Path) -> Result<SourceFile, ShaderError> {
+    //stat first so a write in between shows up as a change on the next check
+    let modified = modified_time(path);
+    let source = std::fs::read_to_string(path).map_err(|error| ShaderError::Io {
+        path: path.display().to_string(),
+        error,
+    })?;
+    Ok(SourceFile {
+        path: path.to_path_buf(),
+        source,
+        modified,
+    })
+}
+
+/// loads and preprocesses the stages of a shader that are in `wanted`
+///
+/// `path` is either a directory with .vert, .geom, .frag and .comp files or a single file with
+/// `#shader` sections. the other stages aren't preprocessed so errors in them don't matter,
+/// callers check that the stages they need were found
+pub fn load_stages(
+    path: &Path,
+    wanted: &[ShaderStage],
+    defines: &[(String, String)],
+) -> Result<Vec<(ShaderStage, PreprocessedSource)>, ShaderError> {
+    let io_error = |error| ShaderError::Io {
+        path: path.display().to_string(),
+        error,
+    };
+
+    let mut stages = Vec::new();
+    if path.is_dir() {
+        for file in std::fs::read_dir(path).map_err(io_error)? {
+            let file_path = file.map_err(io_error)?.path();
+            //directories and files without an extension are skipped
+            let stage = match file_path.extension().and_then(|ext| ext.to_str()) {
+                Some("frag") => ShaderStage::Fragment,
+                Some("vert") => ShaderStage::Vertex,
+                Some("geom") => ShaderStage::Geometry,
+                Some("comp") => ShaderStage::Compute,
+                _ => continue,
+            };
+            if !wanted.contains(&stage) {
+                continue;
+            }
+            let file = read_source(&file_path)?;
+            let text = file.source.clone();
+            //like before the last file of a stage wins
+            stages.retain(|(existing, _)| *existing != stage);
+            stages.push((stage, preprocess(file, 1, &text, defines)?));
+        }
+    } else {
+        let file = read_source(path)?;
+        for (stage, first_line, text) in split_sections(&file)? {
+            if !wanted.contains(&stage) {
+                continue;
+            }
+            stages.push((stage, preprocess(file.clone(), first_line, &text, defines)?));
+        }
+    }
+    Ok(stages)
+}
+
+/// preprocesses source that isn't read from disk, includes are still resolved relative to `path`
+pub fn preprocess_source(
+    path: &Path,
+    source: &str,
+    defines: &[(String, String)],
+) -> Result<PreprocessedSource, ShaderError> {
+    let file = SourceFile {
+        path: path.to_path_buf(),
+        source: source.to_string(),
+        modified: None,
+    };
+    preprocess(file, 1, source, defines)
+}
+
+/// splits a single file shader into (stage, line of the first line, text) sections
+fn split_sections(file: &SourceFile) -> Result<Vec<(ShaderStage, u32, String)>, ShaderError> {
+    let mut sections: Vec<(ShaderStage, u32, String)> = Vec::new();
+
+    for (i, line) in file.source.lines().enumerate() {
+        let number = i as u32 + 1;
+        if let Some(name) = line.trim_start().strip_prefix("#shader") {
+            let stage = match name.trim() {
+                "vertex" => ShaderStage::Vertex,
+                "geometry" => ShaderStage::Geometry,
+                "fragment" | "pixel" => ShaderStage::Fragment,
+                "compute" => ShaderStage::Compute,
+                name => {
+                    return Err(preprocess_error(
+                        file,
+                        number,
+                        format!("unknown shader stage '{}'", name),
+                    ))
+                }
+            };
+            if sections.iter().any(|(existing, _, _)| *existing == stage) {
+                return Err(preprocess_error(
+                    file,
+                    number,
+                    format!("second #shader {} section", name.trim()),
+                ));
+            }
+            sections.push((stage, number + 1, String::new()));
+            continue;
+        }
+
+        match sections.last_mut() {
+            Some((_, _, text)) => {
+                text.push_str(line);
+                text.push('\n');
+            }
+            None if line.trim().is_empty() => {}
+            None => {
+                return Err(preprocess_error(
+                    file,
+                    number,
+                    "code before the first #shader section".to_string(),
+                ))
+            }
+        }
+    }
+
+    Ok(sections)
+}
+
+fn preprocess_error(file: &SourceFile, line: u32, message: String) -> ShaderError {
+    ShaderError::Preprocess {
+        path: file.path.display().to_string(),
+        line,
+        message,
+    }
+}
+
+/// files are compared by their canonical path so "a/../b.glsl" and "b.glsl" are the same include
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// the macro name if the file starts with `#ifndef NAME` followed by `#define NAME`
+fn include_guard(source: &str) -> Option<&str> {
+    let mut lines = source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"));
+    let name = lines.next()?.strip_prefix("#ifndef")?.trim();
+    let define = lines.next()?.strip_prefix("#define")?;
+    (define.split_whitespace().next() == Some(name)).then_some(name)
+}
+
+/// the text between the quotes (or angle brackets) of an #include
+fn include_name(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let close = match rest.chars().next()? {
+        '"' => '"',
+        '<' => '>',
+        _ => return None,
+    };
+    let end = rest[1..].find(close)?;
+    Some(&rest[1..end + 1])
+}
+
+fn preprocess(
+    root: SourceFile,
+    first_line: u32,
+    text: &str,
+    defines: &[(String, String)],
+) -> Result<PreprocessedSource, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        output: Vec::new(),
+        lines: Vec::new(),
+        stack: vec![canonical(&root.path)],
+        files: vec![root],
+        once: HashSet::new(),
+        defined: defines.iter().map(|(name, _)| name.clone()).collect(),
+    };
+    preprocessor.process(0, first_line, text)?;
+
+    //#version has to stay the first line, the defines go right after it
+    let position = preprocessor
+        .output
+        .iter()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map_or(0, |index| index + 1);
+    for (i, (name, value)) in defines.iter().enumerate() {
+        preprocessor
+            .output
+            .insert(position + i, format!("#define {} {}", name, value));
+        preprocessor.lines.insert(position + i, None);
+    }
+
+    let mut code = preprocessor.output.join("\n");
+    code.push('\n');
+    Ok(PreprocessedSource {
+        code,
+        lines: preprocessor.lines,
+        files: preprocessor.files,
+    })
+}
+
+struct Preprocessor {
+    output: Vec<String>,
+    lines: Vec<Option<SourceLocation>>,
+    files: Vec<SourceFile>,
+    /// the files being pasted right now, to catch recursive includes
+    stack: Vec<PathBuf>,
+    /// files that had `#pragma once`
+    once: HashSet<PathBuf>,
+    /// every macro defined so far, guarded includes are skipped if their guard is in here
+    defined: HashSet<String>,
+}
+
+impl Preprocessor {
+    fn process(&mut self, file: usize, first_line: u32, text: &str) -> Result<(), ShaderError> {
+        for (i, line) in text.lines().enumerate() {
+            let number = first_line + i as u32;
+            let directive = line.trim_start();
+
+            if let Some(rest) = directive.strip_prefix("#include") {
+                let name = include_name(rest).ok_or_else(|| {
+                    preprocess_error(
+                        &self.files[file],
+                        number,
+                        "expected #include \"file\"".to_string(),
+                    )
+                })?;
+                let directory = self.files[file].path.parent().unwrap_or(Path::new(""));
+                let path = directory.join(name);
+                self.include(&path, file, number)?;
+                continue;
+            }
+
+            if directive.starts_with("#pragma once") {
+                let current = self.stack.last().unwrap().clone();
+                self.once.insert(current);
+                continue;
+            }
+
+            if let Some(rest) = directive.strip_prefix("#define") {
+                if let Some(name) = rest.split_whitespace().next() {
+                    self.defined.insert(name.to_string());
+                }
+            }
+
+            self.output.push(line.to_string());
+            self.lines.push(Some(SourceLocation { file, line: number }));
+        }
+        Ok(())
+    }
+
+    fn include(&mut self, path: &Path, from: usize, line: u32) -> Result<(), ShaderError> {
+        let key = canonical(path);
+        if self.once.contains(&key) {
+            return Ok(());
+        }
+        if self.stack.contains(&key) {
+            return Err(preprocess_error(
+                &self.files[from],
+                line,
+                format!("{} includes itself", path.display()),
+            ));
+        }
+
+        let file = read_source(path)?;
+        if let Some(guard) = include_guard(&file.source) {
+            if self.defined.contains(guard) {
+                return Ok(());
+            }
+        }
+
+        let text = file.source.clone();
+        let index = match self
+            .files
+            .iter()
+            .position(|known| canonical(&known.path) == key)
+        {
+            Some(index) => index,
+            None => {
+                self.files.push(file);
+                self.files.len() - 1
+            }
+        };
+
+        self.stack.push(key);
+        self.process(index, 1, &text)?;
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch_dir;
+
+    /// writes `files` into a fresh directory and returns it
+    fn scratch_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = scratch_dir(name);
+        for (file, source) in files {
+            std::fs::write(directory.join(file), source).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn includes_are_pasted_once_and_mapped_back() {
+        let directory = scratch_files(
+            "includes",
+            &[
+                ("once.glsl", "#pragma once\nfloat once() { return 1.0; }\n"),
+                (
+                    "guarded.glsl",
+                    "#ifndef GUARDED\n#define GUARDED\nfloat guarded() { return 2.0; }\n#endif\n",
+                ),
+                (
+                    "main.frag",
+                    "#version 330 core\n#include \"once.glsl\"\n#include \"guarded.glsl\"\n#include \"once.glsl\"\n#include \"guarded.glsl\"\nvoid main() {}\n",
+                ),
+            ],
+        );
+
+        let stages = load_stages(&directory, &[ShaderStage::Fragment], &[]).unwrap();
+        let (stage, source) = &stages[0];
+        assert_eq!(*stage, ShaderStage::Fragment);
+        assert_eq!(source.code.matches("float once()").count(), 1);
+        assert_eq!(source.code.matches("float guarded()").count(), 1);
+
+        let main_line = source
+            .code
+            .lines()
+            .position(|line| line == "void main() {}");
+        let (file, line) = source.locate(main_line.unwrap() as u32 + 1).unwrap();
+        assert!(file.path.ends_with("main.frag"));
+        assert_eq!(line, 6);
+
+        let (file, line) = source.locate(2).unwrap();
+        assert!(file.path.ends_with("once.glsl"));
+        assert_eq!(line, 2);
+    }
+
+    #[test]
+    fn recursive_includes_are_errors() {
+        let directory = scratch_files(
+            "recursive",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "#include \"a.glsl\"\n"),
+            ],
+        );
+        let result = preprocess_source(&directory.join("main.frag"), "#include \"a.glsl\"\n", &[]);
+        assert!(matches!(
+            result,
+            Err(ShaderError::Preprocess { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn defines_go_after_version() {
+        let defines = [("USE_FOG".to_string(), "1".to_string())];
+        let source = preprocess_source(
+            Path::new("test.frag"),
+            "#version 330 core\nvoid main() {}\n",
+            &defines,
+        )
+        .unwrap();
+        assert_eq!(
+            source.code,
+            "#version 330 core\n#define USE_FOG 1\nvoid main() {}\n"
+        );
+        assert!(source.locate(2).is_none());
+        assert_eq!(source.locate(3).map(|(_, line)| line), Some(2));
+    }
+
+    #[test]
+    fn single_files_are_split_into_sections() {
+        let directory = scratch_files(
+            "single_file",
+            &[(
+                "flat.glsl",
+                "#shader vertex\n#version 330 core\nvoid main() {}\n\n#shader fragment\n#version 330 core\nvoid main() {}\n",
+            )],
+        );
+
+        let stages = load_stages(
+            &directory.join("flat.glsl"),
+            &[ShaderStage::Vertex, ShaderStage::Fragment],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].0, ShaderStage::Vertex);
+        assert_eq!(stages[1].0, ShaderStage::Fragment);
+        //lines keep their numbers from the combined file
+        assert_eq!(stages[1].1.locate(1).map(|(_, line)| line), Some(6));
+    }
+
+    #[test]
+    fn stages_that_arent_wanted_are_skipped() {
+        let directory = scratch_files(
+            "unwanted_stages",
+            &[
+                ("shader.vert", "#version 330 core\nvoid main() {}\n"),
+                ("shader.frag", "#version 330 core\nvoid main() {}\n"),
+                (
+                    "shader.comp",
+                    "#version 430 core\n#include \"missing.glsl\"\n",
+                ),
+            ],
+        );
+
+        let stages = load_stages(
+            &directory,
+            &[ShaderStage::Vertex, ShaderStage::Fragment],
+            &[],
+        )
+        .unwrap();
+        let mut found: Vec<ShaderStage> = stages.iter().map(|(stage, _)| *stage).collect();
+        found.sort_by_key(|stage| *stage == ShaderStage::Fragment);
+        assert_eq!(found, [ShaderStage::Vertex, ShaderStage::Fragment]);
+    }
+}
@@ -0,0 +1,456 @@
+use gl::types::GLchar;
+
+/// an active uniform as reported by the driver after linking
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformInfo {
+    /// array uniforms are stored without the trailing `[0]`
+    pub name: String,
+    pub location: i32,
+    pub type_: u32,
+    /// number of array elements, 1 for everything that isn't an array
+    pub size: i32,
+}
+
+/// an active vertex attribute as reported by the driver after linking
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub location: i32,
+    pub type_: u32,
+    pub size: i32,
+}
+
+/// lists every active uniform and attribute of a linked program
+///
+/// uniforms inside uniform blocks have no location and are left out
+pub fn reflect(program: u32) -> (Vec<UniformInfo>, Vec<AttributeInfo>) {
+    let mut uniforms = Vec::new();
+    for (name, type_, size) in active_resources(
+        program,
+        gl::ACTIVE_UNIFORMS,
+        gl::ACTIVE_UNIFORM_MAX_LENGTH,
+        gl::GetActiveUniform,
+    ) {
+        let c_str = std::ffi::CString::new(name.as_str()).unwrap();
+        let location = unsafe { gl::GetUniformLocation(program, c_str.as_ptr()) };
+        if location == -1 {
+            continue;
+        }
+        uniforms.push(UniformInfo {
+            name: name.strip_suffix("[0]").unwrap_or(&name).to_string(),
+            location,
+            type_,
+            size,
+        });
+    }
+
+    let mut attributes = Vec::new();
+    for (name, type_, size) in active_resources(
+        program,
+        gl::ACTIVE_ATTRIBUTES,
+        gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+        gl::GetActiveAttrib,
+    ) {
+        let c_str = std::ffi::CString::new(name.as_str()).unwrap();
+        let location = unsafe { gl::GetAttribLocation(program, c_str.as_ptr()) };
+        attributes.push(AttributeInfo {
+            name,
+            location,
+            type_,
+            size,
+        });
+    }
+    attributes.sort_by_key(|attribute| attribute.location);
+
+    (uniforms, attributes)
+}
+
+/// an active uniform block, its members are set through a `UniformBuffer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniformBlockInfo {
+    pub name: String,
+    pub index: u32,
+    /// bytes the buffer bound to this block needs
+    pub data_size: i32,
+}
+
+/// lists every active uniform block of a linked program
+pub fn reflect_blocks(program: u32) -> Vec<UniformBlockInfo> {
+    let mut active = 0;
+    let mut buffer_size = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut active);
+        gl::GetProgramiv(
+            program,
+            gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH,
+            &mut buffer_size,
+        );
+    }
+
+    (0..active.max(0) as u32)
+        .map(|index| {
+            let mut name = vec![0u8; buffer_size.max(1) as usize];
+            let (mut length, mut data_size) = (0, 0);
+            unsafe {
+                gl::GetActiveUniformBlockName(
+                    program,
+                    index,
+                    buffer_size,
+                    &mut length,
+                    name.as_mut_ptr() as *mut GLchar,
+                );
+                gl::GetActiveUniformBlockiv(
+                    program,
+                    index,
+                    gl::UNIFORM_BLOCK_DATA_SIZE,
+                    &mut data_size,
+                );
+            }
+            name.truncate(length.max(0) as usize);
+            UniformBlockInfo {
+                name: String::from_utf8_lossy(&name).into_owned(),
+                index,
+                data_size,
+            }
+        })
+        .collect()
+}
+
+type GetActive = unsafe fn(u32, u32, i32, *mut i32, *mut i32, *mut u32, *mut GLchar);
+
+/// (name, type, size) of every resource glGetActiveUniform or glGetActiveAttrib reports
+fn active_resources(
+    program: u32,
+    count: u32,
+    max_length: u32,
+    get_active: GetActive,
+) -> Vec<(String, u32, i32)> {
+    let mut active = 0;
+    let mut buffer_size = 0;
+    unsafe {
+        gl::GetProgramiv(program, count, &mut active);
+        gl::GetProgramiv(program, max_length, &mut buffer_size);
+    }
+
+    (0..active.max(0) as u32)
+        .map(|index| {
+            let mut name = vec![0u8; buffer_size.max(1) as usize];
+            let (mut length, mut size, mut type_) = (0, 0, 0);
+            unsafe {
+                get_active(
+                    program,
+                    index,
+                    buffer_size,
+                    &mut length,
+                    &mut size,
+                    &mut type_,
+                    name.as_mut_ptr() as *mut GLchar,
+                );
+            }
+            name.truncate(length.max(0) as usize);
+            (String::from_utf8_lossy(&name).into_owned(), type_, size)
+        })
+        .collect()
+}
+
+/// the glsl name of a uniform type, used in error messages
+pub fn glsl_type_name(type_: u32) -> &'static str {
+    match type_ {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        _ => texture_unit_type_name(type_).unwrap_or("unknown"),
+    }
+}
+
+/// samplers and images are set with the texture or image unit they read from
+fn is_texture_unit(type_: u32) -> bool {
+    texture_unit_type_name(type_).is_some()
+}
+
+/// the glsl name of every sampler and image type
+fn texture_unit_type_name(type_: u32) -> Option<&'static str> {
+    let name = match type_ {
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_1D_SHADOW => "sampler1DShadow",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_1D_ARRAY => "sampler1DArray",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_CUBE_MAP_ARRAY => "samplerCubeArray",
+        gl::SAMPLER_1D_ARRAY_SHADOW => "sampler1DArrayShadow",
+        gl::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW => "samplerCubeArrayShadow",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::SAMPLER_2D_MULTISAMPLE_ARRAY => "sampler2DMSArray",
+        gl::SAMPLER_BUFFER => "samplerBuffer",
+        gl::SAMPLER_2D_RECT => "sampler2DRect",
+        gl::SAMPLER_2D_RECT_SHADOW => "sampler2DRectShadow",
+        gl::INT_SAMPLER_1D => "isampler1D",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::INT_SAMPLER_3D => "isampler3D",
+        gl::INT_SAMPLER_CUBE => "isamplerCube",
+        gl::INT_SAMPLER_1D_ARRAY => "isampler1DArray",
+        gl::INT_SAMPLER_2D_ARRAY => "isampler2DArray",
+        gl::INT_SAMPLER_CUBE_MAP_ARRAY => "isamplerCubeArray",
+        gl::INT_SAMPLER_2D_MULTISAMPLE => "isampler2DMS",
+        gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY => "isampler2DMSArray",
+        gl::INT_SAMPLER_BUFFER => "isamplerBuffer",
+        gl::INT_SAMPLER_2D_RECT => "isampler2DRect",
+        gl::UNSIGNED_INT_SAMPLER_1D => "usampler1D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::UNSIGNED_INT_SAMPLER_3D => "usampler3D",
+        gl::UNSIGNED_INT_SAMPLER_CUBE => "usamplerCube",
+        gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => "usampler1DArray",
+        gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => "usampler2DArray",
+        gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY => "usamplerCubeArray",
+        gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => "usampler2DMS",
+        gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => "usampler2DMSArray",
+        gl::UNSIGNED_INT_SAMPLER_BUFFER => "usamplerBuffer",
+        gl::UNSIGNED_INT_SAMPLER_2D_RECT => "usampler2DRect",
+        gl::IMAGE_1D => "image1D",
+        gl::IMAGE_2D => "image2D",
+        gl::IMAGE_3D => "image3D",
+        gl::IMAGE_2D_RECT => "image2DRect",
+        gl::IMAGE_CUBE => "imageCube",
+        gl::IMAGE_BUFFER => "imageBuffer",
+        gl::IMAGE_1D_ARRAY => "image1DArray",
+        gl::IMAGE_2D_ARRAY => "image2DArray",
+        gl::IMAGE_CUBE_MAP_ARRAY => "imageCubeArray",
+        gl::IMAGE_2D_MULTISAMPLE => "image2DMS",
+        gl::IMAGE_2D_MULTISAMPLE_ARRAY => "image2DMSArray",
+        gl::INT_IMAGE_1D => "iimage1D",
+        gl::INT_IMAGE_2D => "iimage2D",
+        gl::INT_IMAGE_3D => "iimage3D",
+        gl::INT_IMAGE_2D_RECT => "iimage2DRect",
+        gl::INT_IMAGE_CUBE => "iimageCube",
+        gl::INT_IMAGE_BUFFER => "iimageBuffer",
+        gl::INT_IMAGE_1D_ARRAY => "iimage1DArray",
+        gl::INT_IMAGE_2D_ARRAY => "iimage2DArray",
+        gl::INT_IMAGE_CUBE_MAP_ARRAY => "iimageCubeArray",
+        gl::INT_IMAGE_2D_MULTISAMPLE => "iimage2DMS",
+        gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY => "iimage2DMSArray",
+        gl::UNSIGNED_INT_IMAGE_1D => "uimage1D",
+        gl::UNSIGNED_INT_IMAGE_2D => "uimage2D",
+        gl::UNSIGNED_INT_IMAGE_3D => "uimage3D",
+        gl::UNSIGNED_INT_IMAGE_2D_RECT => "uimage2DRect",
+        gl::UNSIGNED_INT_IMAGE_CUBE => "uimageCube",
+        gl::UNSIGNED_INT_IMAGE_BUFFER => "uimageBuffer",
+        gl::UNSIGNED_INT_IMAGE_1D_ARRAY => "uimage1DArray",
+        gl::UNSIGNED_INT_IMAGE_2D_ARRAY => "uimage2DArray",
+        gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY => "uimageCubeArray",
+        gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE => "uimage2DMS",
+        gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY => "uimage2DMSArray",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// a single value that can be uploaded to a uniform, slices of these set arrays
+pub trait UniformElement: Sized {
+    const GLSL_TYPE: &'static str;
+
+    fn accepts(type_: u32) -> bool;
+
+    /// # Safety
+    /// the program owning `location` has to be bound
+    unsafe fn upload(location: i32, values: &[Self]);
+}
+
+/// anything `Shader::set_uniform` takes, a `UniformElement` or a slice of them
+pub trait UniformValue {
+    fn glsl_type(&self) -> String;
+
+    fn accepts(&self, type_: u32) -> bool;
+
+    /// number of array elements this sets
+    fn element_count(&self) -> usize;
+
+    /// # Safety
+    /// the program owning `location` has to be bound
+    unsafe fn upload(&self, location: i32);
+}
+
+impl<T: UniformElement> UniformValue for T {
+    fn glsl_type(&self) -> String {
+        T::GLSL_TYPE.to_string()
+    }
+
+    fn accepts(&self, type_: u32) -> bool {
+        T::accepts(type_)
+    }
+
+    fn element_count(&self) -> usize {
+        1
+    }
+
+    unsafe fn upload(&self, location: i32) {
+        T::upload(location, std::slice::from_ref(self));
+    }
+}
+
+impl<T: UniformElement> UniformValue for [T] {
+    fn glsl_type(&self) -> String {
+        format!("{}[{}]", T::GLSL_TYPE, self.len())
+    }
+
+    fn accepts(&self, type_: u32) -> bool {
+        T::accepts(type_)
+    }
+
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn upload(&self, location: i32) {
+        T::upload(location, self);
+    }
+}
+
+/// implements `UniformElement` for a type made of contiguous `$scalar`s
+/// that is uploaded with `$upload(location, count, pointer)`
+macro_rules! uniform_element {
+    ($type_:ty, $glsl:literal, $gl_type:path, $scalar:ty, $upload:path) => {
+        impl UniformElement for $type_ {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(type_: u32) -> bool {
+                type_ == $gl_type
+            }
+
+            unsafe fn upload(location: i32, values: &[Self]) {
+                $upload(
+                    location,
+                    values.len() as i32,
+                    values.as_ptr() as *const $scalar,
+                );
+            }
+        }
+    };
+}
+
+/// same for column major matrices uploaded with `$upload(location, count, transpose, pointer)`
+macro_rules! uniform_matrix {
+    ($type_:ty, $glsl:literal, $gl_type:path, $upload:path) => {
+        impl UniformElement for $type_ {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(type_: u32) -> bool {
+                type_ == $gl_type
+            }
+
+            unsafe fn upload(location: i32, values: &[Self]) {
+                $upload(
+                    location,
+                    values.len() as i32,
+                    gl::FALSE,
+                    values.as_ptr() as *const f32,
+                );
+            }
+        }
+    };
+}
+
+uniform_element!(f32, "float", gl::FLOAT, f32, gl::Uniform1fv);
+uniform_element!(glm::Vec2, "vec2", gl::FLOAT_VEC2, f32, gl::Uniform2fv);
+uniform_element!(glm::Vec3, "vec3", gl::FLOAT_VEC3, f32, gl::Uniform3fv);
+uniform_element!(glm::Vec4, "vec4", gl::FLOAT_VEC4, f32, gl::Uniform4fv);
+uniform_element!(glm::IVec2, "ivec2", gl::INT_VEC2, i32, gl::Uniform2iv);
+uniform_element!(glm::IVec3, "ivec3", gl::INT_VEC3, i32, gl::Uniform3iv);
+uniform_element!(glm::IVec4, "ivec4", gl::INT_VEC4, i32, gl::Uniform4iv);
+uniform_element!(u32, "uint", gl::UNSIGNED_INT, u32, gl::Uniform1uiv);
+uniform_element!(
+    glm::UVec2,
+    "uvec2",
+    gl::UNSIGNED_INT_VEC2,
+    u32,
+    gl::Uniform2uiv
+);
+uniform_element!(
+    glm::UVec3,
+    "uvec3",
+    gl::UNSIGNED_INT_VEC3,
+    u32,
+    gl::Uniform3uiv
+);
+uniform_element!(
+    glm::UVec4,
+    "uvec4",
+    gl::UNSIGNED_INT_VEC4,
+    u32,
+    gl::Uniform4uiv
+);
+uniform_matrix!(glm::Mat2, "mat2", gl::FLOAT_MAT2, gl::UniformMatrix2fv);
+uniform_matrix!(glm::Mat3, "mat3", gl::FLOAT_MAT3, gl::UniformMatrix3fv);
+uniform_matrix!(glm::Mat4, "mat4", gl::FLOAT_MAT4, gl::UniformMatrix4fv);
+
+/// ints also set bools and the unit of samplers and images
+impl UniformElement for i32 {
+    const GLSL_TYPE: &'static str = "int";
+
+    fn accepts(type_: u32) -> bool {
+        type_ == gl::INT || type_ == gl::BOOL || is_texture_unit(type_)
+    }
+
+    unsafe fn upload(location: i32, values: &[Self]) {
+        gl::Uniform1iv(location, values.len() as i32, values.as_ptr());
+    }
+}
+
+impl UniformElement for bool {
+    const GLSL_TYPE: &'static str = "bool";
+
+    fn accepts(type_: u32) -> bool {
+        type_ == gl::BOOL
+    }
+
+    unsafe fn upload(location: i32, values: &[Self]) {
+        let values: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+        gl::Uniform1iv(location, values.len() as i32, values.as_ptr());
+    }
+}
+
+/// bool vectors go up as ints like `bool` does
+macro_rules! uniform_bvec {
+    ($type_:ty, $glsl:literal, $gl_type:path, $upload:path) => {
+        impl UniformElement for $type_ {
+            const GLSL_TYPE: &'static str = $glsl;
+
+            fn accepts(type_: u32) -> bool {
+                type_ == $gl_type
+            }
+
+            unsafe fn upload(location: i32, values: &[Self]) {
+                let count = values.len() as i32;
+                let values: Vec<i32> = values
+                    .iter()
+                    .flat_map(|value| value.iter().map(|&value| value as i32))
+                    .collect();
+                $upload(location, count, values.as_ptr());
+            }
+        }
+    };
+}
+
+uniform_bvec!(glm::TVec2<bool>, "bvec2", gl::BOOL_VEC2, gl::Uniform2iv);
+uniform_bvec!(glm::TVec3<bool>, "bvec3", gl::BOOL_VEC3, gl::Uniform3iv);
+uniform_bvec!(glm::TVec4<bool>, "bvec4", gl::BOOL_VEC4, gl::Uniform4iv);
@@ -0,0 +1,127 @@
+use super::shader::Shader;
+use super::shader_error::ShaderError;
+use std::collections::HashMap;
+
+/// feature flags of a variant, bit i turns on the i-th feature passed to `ShaderVariants::new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct VariantKey(u32);
+
+/// permutations of one shader, each compiled with `#define FEATURE 1` for its enabled features
+///
+/// variants are compiled the first time they are asked for and kept after that
+pub struct ShaderVariants {
+    file_path: String,
+    features: Vec<String>,
+    variants: HashMap<VariantKey, Shader>,
+}
+
+impl ShaderVariants {
+    /// nothing is compiled yet, `file_path` is anything `Shader::new` accepts
+    pub fn new(file_path: &str, features: &[&str]) -> ShaderVariants {
+        assert!(
+            features.len() <= 32,
+            "a shader can have at most 32 features"
+        );
+        ShaderVariants {
+            file_path: file_path.to_string(),
+            features: features.iter().map(|feature| feature.to_string()).collect(),
+            variants: HashMap::new(),
+        }
+    }
+
+    /// the key of the variant with exactly `enabled` turned on
+    pub fn key(&self, enabled: &[&str]) -> VariantKey {
+        let mut key = 0;
+        for feature in enabled {
+            let bit = self
+                .features
+                .iter()
+                .position(|known| known == feature)
+                .unwrap_or_else(|| panic!("{} has no feature {}", self.file_path, feature));
+            key |= 1 << bit;
+        }
+        VariantKey(key)
+    }
+
+    /// the program for `key`, compiling it if this is the first time it's used
+    #[track_caller]
+    pub fn get(&mut self, key: VariantKey) -> Result<&mut Shader, ShaderError> {
+        if !self.variants.contains_key(&key) {
+            let defines: Vec<(&str, &str)> = self
+                .features
+                .iter()
+                .enumerate()
+                .filter(|(bit, _)| key.0 & (1 << bit) != 0)
+                .map(|(_, feature)| (feature.as_str(), "1"))
+                .collect();
+            let shader = Shader::with_defines(&self.file_path, &defines)?;
+            self.variants.insert(key, shader);
+        }
+        Ok(self.variants.get_mut(&key).unwrap())
+    }
+
+    /// reloads every compiled variant, see `Shader::reload_if_changed`
+    ///
+    /// all variants share the same files so only the first error is returned
+    pub fn reload_if_changed(&mut self) -> Result<bool, ShaderError> {
+        let mut reloaded = false;
+        let mut first_error = None;
+        for shader in self.variants.values_mut() {
+            match shader.reload_if_changed() {
+                Ok(swapped) => reloaded |= swapped,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(reloaded),
+        }
+    }
+
+    pub fn get_compiled_count(&self) -> usize {
+        self.variants.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+
+    #[test]
+    fn variants_are_compiled_once_on_first_use() {
+        let (compiled, calls) = with_mock_gl(|| {
+            let mut variants = ShaderVariants::new("res/shaders/batch", &["UNTEXTURED", "FOG"]);
+            assert_eq!(variants.get_compiled_count(), 0);
+
+            let key = variants.key(&["FOG"]);
+            variants.get(key).unwrap();
+            variants.get(key).unwrap();
+            variants.get(variants.key(&[])).unwrap();
+            variants.get_compiled_count()
+        });
+
+        assert_eq!(compiled, 2);
+        let programs = calls
+            .iter()
+            .filter(|call| matches!(call, GlCall::CreateProgram(_)))
+            .count();
+        assert_eq!(programs, 2);
+
+        let fog_sources = calls
+            .iter()
+            .filter(|call| match call {
+                GlCall::ShaderSource(_, source) => source.contains("#define FOG 1"),
+                _ => false,
+            })
+            .count();
+        //both stages of the FOG variant and nothing else
+        assert_eq!(fog_sources, 2);
+        assert!(!calls.iter().any(|call| match call {
+            GlCall::ShaderSource(_, source) => source.contains("#define UNTEXTURED"),
+            _ => false,
+        }));
+    }
+}
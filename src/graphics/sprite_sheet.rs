@@ -0,0 +1,148 @@
+use super::backend::TextureHandle;
+use super::renderer_2d::Sprite;
+use super::texture::Texture;
+use super::texture_atlas::TextureAtlas;
+
+/// frames cut out of one texture, each frame is a (u0, v0, u1, v1) rect like `Sprite::uv_rect`
+pub struct SpriteSheet<'a> {
+    texture: &'a Texture,
+    frames: Vec<glm::Vec4>,
+}
+
+impl<'a> SpriteSheet<'a> {
+    /// slices the whole texture into `columns` x `rows` equal cells
+    pub fn grid(texture: &'a Texture, columns: u32, rows: u32) -> SpriteSheet<'a> {
+        Self::grid_in(texture, glm::vec4(0.0, 0.0, 1.0, 1.0), columns, rows)
+    }
+
+    /// slices the `uv_rect` part of the texture into `columns` x `rows` equal cells
+    ///
+    /// frames are numbered like text is read, left to right starting at the top row
+    pub fn grid_in(
+        texture: &'a Texture,
+        uv_rect: glm::Vec4,
+        columns: u32,
+        rows: u32,
+    ) -> SpriteSheet<'a> {
+        assert!(
+            columns > 0 && rows > 0,
+            "a sprite sheet needs at least one cell"
+        );
+        let cell = glm::vec2(
+            (uv_rect.z - uv_rect.x) / columns as f32,
+            (uv_rect.w - uv_rect.y) / rows as f32,
+        );
+        let mut frames = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            //v goes up, the top row is at the end of the rect
+            let top = uv_rect.w - cell.y * row as f32;
+            for column in 0..columns {
+                let left = uv_rect.x + cell.x * column as f32;
+                frames.push(glm::vec4(left, top - cell.y, left + cell.x, top));
+            }
+        }
+        SpriteSheet { texture, frames }
+    }
+
+    /// slices the atlas image called `name` into `columns` x `rows` cells, None if the atlas
+    /// doesn't have it
+    pub fn from_atlas_grid(
+        atlas: &'a TextureAtlas,
+        name: &str,
+        columns: u32,
+        rows: u32,
+    ) -> Option<SpriteSheet<'a>> {
+        let region = atlas.get_region(name)?;
+        Some(Self::grid_in(
+            atlas.get_page(region.page),
+            region.uv_rect,
+            columns,
+            rows,
+        ))
+    }
+
+    /// one frame per atlas image in the order of `names`, None if one is missing or they
+    /// aren't all on the same page
+    pub fn from_atlas_frames(atlas: &'a TextureAtlas, names: &[&str]) -> Option<SpriteSheet<'a>> {
+        let regions = names
+            .iter()
+            .map(|name| atlas.get_region(name))
+            .collect::<Option<Vec<_>>>()?;
+        let page = regions.first()?.page;
+        if regions.iter().any(|region| region.page != page) {
+            return None;
+        }
+        Some(SpriteSheet {
+            texture: atlas.get_page(page),
+            frames: regions.iter().map(|region| region.uv_rect).collect(),
+        })
+    }
+
+    pub fn get_frame(&self, frame: usize) -> glm::Vec4 {
+        self.frames[frame]
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn get_texture(&self) -> &'a Texture {
+        self.texture
+    }
+
+    /// a sprite showing `frame`, usually `Animation::get_frame`
+    pub fn sprite(&self, frame: usize, position: glm::Vec2, size: glm::Vec2) -> Sprite {
+        let mut sprite = Sprite::new(TextureHandle::from(self.texture), position, size);
+        sprite.uv_rect = self.frames[frame];
+        sprite
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::texture::TextureFormat;
+    use crate::graphics::texture_atlas::AtlasBuilder;
+    use crate::graphics::texture_spec::TextureSpec;
+    use crate::testing::mock_gl::with_mock_gl;
+
+    #[test]
+    fn grids_are_read_from_the_top_left() {
+        let (frames, _) = with_mock_gl(|| {
+            let texture = Texture::blank(64, 32, TextureFormat::RGBA8, &TextureSpec::default());
+            let sheet = SpriteSheet::grid(&texture, 4, 2);
+            (0..sheet.get_frame_count())
+                .map(|frame| sheet.get_frame(frame))
+                .collect::<Vec<_>>()
+        });
+
+        assert_eq!(frames.len(), 8);
+        assert_eq!(frames[0], glm::vec4(0.0, 0.5, 0.25, 1.0));
+        assert_eq!(frames[3], glm::vec4(0.75, 0.5, 1.0, 1.0));
+        assert_eq!(frames[4], glm::vec4(0.0, 0.0, 0.25, 0.5));
+    }
+
+    #[test]
+    fn atlas_sheets_stay_inside_their_region() {
+        let (frames, _) = with_mock_gl(|| {
+            let mut builder = AtlasBuilder::new(16, 16);
+            builder.add_pixels("run", 8, 4, &[0; 8 * 4 * 4]);
+            builder.add_pixels("idle", 4, 4, &[0; 4 * 4 * 4]);
+            let atlas = builder.build(&TextureSpec::pixel_art()).unwrap();
+
+            let run = SpriteSheet::from_atlas_grid(&atlas, "run", 2, 1).unwrap();
+            let region = atlas.get_region("run").unwrap().uv_rect;
+            assert_eq!(run.get_frame(0).x, region.x);
+            assert_eq!(run.get_frame(1).z, region.z);
+            assert!(SpriteSheet::from_atlas_grid(&atlas, "jump", 2, 1).is_none());
+
+            let frames = SpriteSheet::from_atlas_frames(&atlas, &["idle", "run"]).unwrap();
+            (
+                frames.get_frame(0),
+                atlas.get_region("idle").unwrap().uv_rect,
+            )
+        });
+
+        assert_eq!(frames.0, frames.1);
+    }
+}
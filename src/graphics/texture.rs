@@ -1,66 +1,438 @@
 
+use super::gl_resource;
+use super::texture_error::TextureError;
+use super::texture_spec::{max_anisotropy, Filter, TextureSpec, Wrap, TEXTURE_MAX_ANISOTROPY};
+use ::stb_image::image::{load_with_depth, LoadResult};
 use stb_image::stb_image;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+
+/// how the pixels of a texture are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// read as gray
+    R8,
+    /// read as gray + alpha
+    RG8,
+    RGB8,
+    RGBA8,
+    R32F,
+    /// uploaded as half floats
+    RGBA16F,
+    RGBA32F,
+}
+
+impl TextureFormat {
+    /// the 8 bit format with 1 to 4 channels
+    pub fn from_channels(channels: i32) -> Option<TextureFormat> {
+        match channels {
+            1 => Some(TextureFormat::R8),
+            2 => Some(TextureFormat::RG8),
+            3 => Some(TextureFormat::RGB8),
+            4 => Some(TextureFormat::RGBA8),
+            _ => None,
+        }
+    }
+
+    pub fn get_channels(&self) -> i32 {
+        match self {
+            TextureFormat::R8 | TextureFormat::R32F => 1,
+            TextureFormat::RG8 => 2,
+            TextureFormat::RGB8 => 3,
+            TextureFormat::RGBA8 | TextureFormat::RGBA16F | TextureFormat::RGBA32F => 4,
+        }
+    }
+
+    /// size of one pixel in the data passed to uploads
+    pub fn bytes_per_pixel(&self) -> i32 {
+        match self {
+            TextureFormat::RGBA16F => 8,
+            TextureFormat::R32F => 4,
+            TextureFormat::RGBA32F => 16,
+            _ => self.get_channels(),
+        }
+    }
+
+    /// (internal format, pixel format, pixel type) for glTexImage2D
+    pub fn gl_formats(&self) -> (u32, u32, u32) {
+        match self {
+            TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::RG8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            TextureFormat::RGB8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::RGBA8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R32F => (gl::R32F, gl::RED, gl::FLOAT),
+            TextureFormat::RGBA16F => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT),
+            TextureFormat::RGBA32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+        }
+    }
+}
 
 pub struct Texture {
     id: u32,
     _file_path: String,
-    _local_buffer: *mut u8,
     width: i32,
     height: i32,
-    _bpp: i32,
+    format: TextureFormat,
+    spec: TextureSpec,
+}
+
+/// gl expects rows to start on 4 byte boundaries, stb_image packs them tightly
+fn unpack_alignment(width: i32, bytes_per_pixel: i32) -> i32 {
+    if (width * bytes_per_pixel) % 4 == 0 {
+        4
+    } else {
+        1
+    }
+}
+
+/// gpu memory of a texture, a full mip chain adds a third
+pub(super) fn texture_bytes(
+    width: i32,
+    height: i32,
+    format: TextureFormat,
+    spec: &TextureSpec,
+) -> usize {
+    let bytes = (width * height * format.bytes_per_pixel()) as usize;
+    if spec.mipmaps {
+        bytes * 4 / 3
+    } else {
+        bytes
+    }
+}
+
+/// name used in errors for images that weren't loaded from a file
+const MEMORY_PATH: &str = "<memory>";
+
+/// decodes an image file to RGBA8 pixels for textures that are assembled on the cpu,
+/// `flip` puts the rows bottom to top like `Texture` uploads them
+pub(crate) fn load_rgba(path: &str, flip: bool) -> Result<(i32, i32, Vec<u8>), TextureError> {
+    unsafe { stb_image::stbi_set_flip_vertically_on_load(flip as i32) };
+    match load_with_depth(path, 4, false) {
+        LoadResult::ImageU8(image) => Ok((image.width as i32, image.height as i32, image.data)),
+        LoadResult::ImageF32(_) => Err(TextureError::Load {
+            path: path.to_string(),
+            reason: "float images aren't supported here".to_string(),
+        }),
+        LoadResult::Error(reason) => Err(TextureError::Load {
+            path: path.to_string(),
+            reason,
+        }),
+    }
 }
 
 impl Texture {
-    pub fn new(path: &str) -> Texture {
-        let mut id = 0;
+    /// loads an image with as many channels as the file has
+    #[track_caller]
+    pub fn new(path: &str) -> Result<Texture, TextureError> {
+        Self::load(path, 0, &TextureSpec::default())
+    }
+
+    /// loads an image with the filtering, wrapping and mipmaps of `spec`
+    #[track_caller]
+    pub fn with_spec(path: &str, spec: &TextureSpec) -> Result<Texture, TextureError> {
+        Self::load(path, 0, spec)
+    }
+
+    /// loads an image converted to `channels` channels, e.g. 4 to always get RGBA
+    #[track_caller]
+    pub fn with_channels(path: &str, channels: i32) -> Result<Texture, TextureError> {
+        if TextureFormat::from_channels(channels).is_none() {
+            return Err(TextureError::UnsupportedChannels {
+                path: path.to_string(),
+                channels,
+            });
+        }
+        Self::load(path, channels, &TextureSpec::default())
+    }
+
+    /// decodes an image file that's already in memory, e.g. from `include_bytes!`
+    #[track_caller]
+    pub fn from_memory(bytes: &[u8], spec: &TextureSpec) -> Result<Texture, TextureError> {
+        Self::decode(MEMORY_PATH, 0, spec, |width, height, channels| unsafe {
+            stb_image::stbi_load_from_memory(
+                bytes.as_ptr(),
+                bytes.len() as i32,
+                width,
+                height,
+                channels,
+                0,
+            )
+        })
+    }
+
+    /// `desired_channels` of 0 keeps the channels of the file
+    #[track_caller]
+    fn load(
+        path: &str,
+        desired_channels: i32,
+        spec: &TextureSpec,
+    ) -> Result<Texture, TextureError> {
+        let c_path = CString::new(path).map_err(|_| TextureError::Load {
+            path: path.to_string(),
+            reason: "path contains a nul byte".to_string(),
+        })?;
+        Self::decode(
+            path,
+            desired_channels,
+            spec,
+            |width, height, channels| unsafe {
+                stb_image::stbi_load(c_path.as_ptr(), width, height, channels, desired_channels)
+            },
+        )
+    }
+
+    /// runs an stb_image loader that fills in (width, height, channels) and uploads its pixels
+    #[track_caller]
+    fn decode(
+        path: &str,
+        desired_channels: i32,
+        spec: &TextureSpec,
+        load: impl FnOnce(&mut i32, &mut i32, &mut i32) -> *mut u8,
+    ) -> Result<Texture, TextureError> {
         let mut width = 0;
         let mut height = 0;
-        let mut _local_buffer: *mut u8 = std::ptr::null_mut();
-        let mut bpp = 0;
+        let mut file_channels = 0;
+        unsafe { stb_image::stbi_set_flip_vertically_on_load(1) };
+        let pixels = load(&mut width, &mut height, &mut file_channels);
+        if pixels.is_null() {
+            let reason = unsafe { stb_image::stbi_failure_reason() };
+            let reason = if reason.is_null() {
+                "unknown error".to_string()
+            } else {
+                unsafe { CStr::from_ptr(reason) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            return Err(TextureError::Load {
+                path: path.to_string(),
+                reason,
+            });
+        }
 
-        unsafe {
-            stb_image::stbi_set_flip_vertically_on_load(1);
-            let c_path = CString::new(path).expect("CString::new failed");
-            _local_buffer =
-                stb_image::stbi_load(c_path.as_ptr(), &mut width, &mut height, &mut bpp, 0);
+        //stb_image reports the channels of the file even when it converted them
+        let channels = if desired_channels == 0 {
+            file_channels
+        } else {
+            desired_channels
+        };
+        let texture = match TextureFormat::from_channels(channels) {
+            Some(format) => Ok(Self::upload(
+                path,
+                width,
+                height,
+                format,
+                pixels as *const std::ffi::c_void,
+                spec,
+            )),
+            None => Err(TextureError::UnsupportedChannels {
+                path: path.to_string(),
+                channels,
+            }),
+        };
+        unsafe { stb_image::stbi_image_free(pixels as *mut std::ffi::c_void) };
+        texture
+    }
+
+    /// creates a texture from RGBA8 pixels, rows go bottom to top like glTexImage2D expects
+    #[track_caller]
+    pub fn from_rgba(width: i32, height: i32, pixels: &[u8]) -> Texture {
+        Self::from_pixels(
+            width,
+            height,
+            TextureFormat::RGBA8,
+            pixels,
+            &TextureSpec::default(),
+        )
+    }
+
+    /// creates a texture from tightly packed pixels in `format`, rows go bottom to top
+    #[track_caller]
+    pub fn from_pixels(
+        width: i32,
+        height: i32,
+        format: TextureFormat,
+        pixels: &[u8],
+        spec: &TextureSpec,
+    ) -> Texture {
+        assert_eq!(
+            pixels.len(),
+            (width * height * format.bytes_per_pixel()) as usize,
+            "pixel data doesn't match the texture size"
+        );
+        Self::upload(
+            MEMORY_PATH,
+            width,
+            height,
+            format,
+            pixels.as_ptr() as *const std::ffi::c_void,
+            spec,
+        )
+    }
+
+    /// creates a texture with undefined contents, to be filled with `update_region`,
+    /// rendering or compute shaders
+    #[track_caller]
+    pub fn blank(width: i32, height: i32, format: TextureFormat, spec: &TextureSpec) -> Texture {
+        Self::upload(MEMORY_PATH, width, height, format, std::ptr::null(), spec)
+    }
 
+    /// uploads `pixels`, null leaves the contents undefined
+    #[track_caller]
+    fn upload(
+        path: &str,
+        width: i32,
+        height: i32,
+        format: TextureFormat,
+        pixels: *const std::ffi::c_void,
+        spec: &TextureSpec,
+    ) -> Texture {
+        let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+        let mut id = 0;
+        unsafe {
             gl::GenTextures(1, &mut id);
             gl::BindTexture(gl::TEXTURE_2D, id);
+            Self::apply_swizzle(gl::TEXTURE_2D, format);
 
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-
+            gl::PixelStorei(
+                gl::UNPACK_ALIGNMENT,
+                unpack_alignment(width, format.bytes_per_pixel()),
+            );
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA8 as i32,
+                internal_format as i32,
                 width,
                 height,
                 0,
-                gl::RGB,
-                gl::UNSIGNED_BYTE,
-                _local_buffer as *const std::ffi::c_void,
+                pixel_format,
+                pixel_type,
+                pixels,
             );
+            //back to the default so other uploads aren't affected
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            //mipmaps are generated from the level that was just uploaded
+            Self::apply_spec(gl::TEXTURE_2D, spec, 1.0);
             gl::BindTexture(gl::TEXTURE_2D, 0);
-
-            if _local_buffer != std::ptr::null_mut() {
-                stb_image::stbi_image_free(_local_buffer as *mut std::ffi::c_void);
-            } else {
-                println!("Failed to load texture: {}", path);
-            }
         }
+        gl_resource::track("Texture", id, texture_bytes(width, height, format, spec));
 
         Texture {
-            id: id,
+            id,
             _file_path: path.to_string(),
-            _local_buffer: _local_buffer,
-            width: width,
-            height: height,
-            _bpp: 0,
+            width,
+            height,
+            format,
+            spec: *spec,
+        }
+    }
+
+    /// replaces the `width` x `height` block at (`x`, `y`) with tightly packed pixels in the
+    /// format of the texture, mipmaps are regenerated if the spec has them
+    pub fn update_region(&self, x: i32, y: i32, width: i32, height: i32, pixels: &[u8]) {
+        assert!(
+            x >= 0 && y >= 0 && x + width <= self.width && y + height <= self.height,
+            "region {}x{} at ({}, {}) is outside of the {}x{} texture",
+            width,
+            height,
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        assert_eq!(
+            pixels.len(),
+            (width * height * bytes_per_pixel) as usize,
+            "pixel data doesn't match the region size"
+        );
+
+        let (_, pixel_format, pixel_type) = self.format.gl_formats();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(
+                gl::UNPACK_ALIGNMENT,
+                unpack_alignment(width, bytes_per_pixel),
+            );
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x,
+                y,
+                width,
+                height,
+                pixel_format,
+                pixel_type,
+                pixels.as_ptr() as *const std::ffi::c_void,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if self.spec.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// one and two channel images are gray and gray + alpha, not red and red + green. sets the
+    /// swizzle of the texture bound to `target` for those formats
+    pub(super) unsafe fn apply_swizzle(target: u32, format: TextureFormat) {
+        let swizzle = match format {
+            TextureFormat::R8 => [gl::RED, gl::RED, gl::RED, gl::ONE],
+            TextureFormat::RG8 => [gl::RED, gl::RED, gl::RED, gl::GREEN],
+            _ => return,
+        };
+        let parameters = [
+            gl::TEXTURE_SWIZZLE_R,
+            gl::TEXTURE_SWIZZLE_G,
+            gl::TEXTURE_SWIZZLE_B,
+            gl::TEXTURE_SWIZZLE_A,
+        ];
+        for (parameter, source) in parameters.into_iter().zip(swizzle) {
+            gl::TexParameteri(target, parameter, source as i32);
+        }
+    }
+
+    /// writes the sampler state of `spec` into the texture bound to `target` and generates its
+    /// mipmaps
+    ///
+    /// anisotropy is only touched when it's turned on or was on before
+    pub(super) unsafe fn apply_spec(target: u32, spec: &TextureSpec, previous_anisotropy: f32) {
+        gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, spec.gl_min_filter() as i32);
+        gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, spec.gl_mag_filter() as i32);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_S, spec.wrap_s.gl_enum() as i32);
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_T, spec.wrap_t.gl_enum() as i32);
+        if spec.wrap_s == Wrap::ClampToBorder || spec.wrap_t == Wrap::ClampToBorder {
+            gl::TexParameterfv(target, gl::TEXTURE_BORDER_COLOR, spec.border_color.as_ptr());
+        }
+        if spec.anisotropy > 1.0 || previous_anisotropy > 1.0 {
+            if let Some(max) = max_anisotropy() {
+                let anisotropy = spec.anisotropy.clamp(1.0, max);
+                gl::TexParameterf(target, TEXTURE_MAX_ANISOTROPY, anisotropy);
+            }
+        }
+        if spec.mipmaps {
+            gl::GenerateMipmap(target);
+        }
+    }
+
+    /// changes the sampler state after creation, mipmaps are regenerated if `spec` has them
+    pub fn set_spec(&mut self, spec: TextureSpec) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            Self::apply_spec(gl::TEXTURE_2D, &spec, self.spec.anisotropy);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
         }
+        self.spec = spec;
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.set_spec(self.spec.filter(filter));
+    }
+
+    pub fn set_wrap(&mut self, wrap: Wrap) {
+        self.set_spec(self.spec.wrap(wrap));
+    }
+
+    pub fn get_spec(&self) -> &TextureSpec {
+        &self.spec
     }
 
     pub fn bind(&self, slot: u32) {
@@ -76,6 +448,20 @@ impl Texture {
         }
     }
 
+    /// binds the texture to image `unit` for `imageLoad`/`imageStore` in compute shaders,
+    /// `access` is gl::READ_ONLY, gl::WRITE_ONLY or gl::READ_WRITE. 3 channel textures have no
+    /// image format and can't be bound
+    pub fn bind_image(&self, unit: u32, access: u32) {
+        let (internal_format, _, _) = self.format.gl_formats();
+        unsafe {
+            gl::BindImageTexture(unit, self.id, 0, gl::FALSE, 0, access, internal_format);
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
     pub fn get_width(&self) -> i32 {
         self.width
     }
@@ -83,4 +469,228 @@ impl Texture {
     pub fn get_height(&self) -> i32 {
         self.height
     }
+
+    /// channels the pixels were uploaded with, 1 to 4
+    pub fn get_channels(&self) -> i32 {
+        self.format.get_channels()
+    }
+
+    pub fn get_format(&self) -> TextureFormat {
+        self.format
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        if gl_resource::release("Texture", self.id) {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+    use crate::testing::scratch_png;
+    use crate::utils::png_writer;
+
+    /// a 3x1 RGBA png, rows of 3 pixels don't line up with 4 bytes once converted to RGB
+    fn three_pixel_png(name: &str) -> String {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0];
+        scratch_png(name, 3, 1, &pixels)
+    }
+
+    /// (internal format, format, unpack alignment) of every upload
+    fn uploads(calls: &[GlCall]) -> Vec<(i32, u32, i32)> {
+        let mut alignment = 4;
+        let mut uploads = Vec::new();
+        for call in calls {
+            match call {
+                GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, value) => alignment = *value,
+                GlCall::TexImage2D {
+                    internal_format,
+                    format,
+                    ..
+                } => uploads.push((*internal_format, *format, alignment)),
+                _ => {}
+            }
+        }
+        uploads
+    }
+
+    #[test]
+    fn formats_follow_the_channel_count() {
+        let path = three_pixel_png("texture_channels");
+        let (channels, calls) = with_mock_gl(|| {
+            [
+                Texture::new(&path).unwrap(),
+                Texture::with_channels(&path, 3).unwrap(),
+                Texture::with_channels(&path, 1).unwrap(),
+            ]
+            .map(|texture| texture.get_channels())
+        });
+
+        assert_eq!(channels, [4, 3, 1]);
+        assert_eq!(
+            uploads(&calls),
+            [
+                (gl::RGBA8 as i32, gl::RGBA, 4),
+                (gl::RGB8 as i32, gl::RGB, 1),
+                (gl::R8 as i32, gl::RED, 1),
+            ]
+        );
+        //single channel textures read as gray
+        assert!(calls.contains(&GlCall::TexParameteri(
+            gl::TEXTURE_2D,
+            gl::TEXTURE_SWIZZLE_G,
+            gl::RED as i32
+        )));
+    }
+
+    #[test]
+    fn load_failures_are_errors() {
+        let ((missing, channels), calls) = with_mock_gl(|| {
+            (
+                Texture::new("res/textures/missing.png").err(),
+                Texture::with_channels("res/textures/ghost.png", 5).err(),
+            )
+        });
+
+        assert!(matches!(missing, Some(TextureError::Load { .. })));
+        assert_eq!(
+            channels,
+            Some(TextureError::UnsupportedChannels {
+                path: "res/textures/ghost.png".to_string(),
+                channels: 5,
+            })
+        );
+        assert!(calls.is_empty());
+    }
+
+    #[test]
+    fn specs_set_sampler_state_after_the_upload() {
+        let path = three_pixel_png("texture_spec");
+        let spec = TextureSpec::pixel_art()
+            .wrap(Wrap::ClampToBorder)
+            .border_color(glm::vec4(1.0, 0.0, 1.0, 1.0))
+            .mipmaps(Filter::Nearest)
+            .anisotropy(32.0);
+        let (_, calls) = with_mock_gl(|| {
+            let mut texture = Texture::with_spec(&path, &spec).unwrap();
+            texture.set_filter(Filter::Linear);
+        });
+
+        let target = gl::TEXTURE_2D;
+        let upload = calls
+            .iter()
+            .position(|call| matches!(call, GlCall::TexImage2D { .. }))
+            .unwrap();
+        let parameter = |call: &GlCall| calls[upload..].iter().position(|c| c == call);
+        let min_filter = gl::NEAREST_MIPMAP_NEAREST as i32;
+        let mipmaps = parameter(&GlCall::GenerateMipmap(target)).unwrap();
+        assert!(
+            parameter(&GlCall::TexParameteri(
+                target,
+                gl::TEXTURE_MIN_FILTER,
+                min_filter
+            ))
+            .unwrap()
+                < mipmaps
+        );
+        assert!(calls.contains(&GlCall::TexParameteri(
+            target,
+            gl::TEXTURE_WRAP_T,
+            gl::CLAMP_TO_BORDER as i32
+        )));
+        assert!(calls.contains(&GlCall::TexParameterfv(
+            target,
+            gl::TEXTURE_BORDER_COLOR,
+            [1.0, 0.0, 1.0, 1.0]
+        )));
+        //clamped to what the driver supports
+        assert!(calls.contains(&GlCall::TexParameterf(target, TEXTURE_MAX_ANISOTROPY, 16.0)));
+        //changing the filter later keeps the mipmaps
+        assert!(calls.contains(&GlCall::TexParameteri(
+            target,
+            gl::TEXTURE_MIN_FILTER,
+            gl::LINEAR_MIPMAP_NEAREST as i32
+        )));
+    }
+
+    #[test]
+    fn textures_can_come_from_memory() {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0];
+        let png = png_writer::encode_png(3, 1, &pixels);
+        let ((decoded, garbage, blank), calls) = with_mock_gl(|| {
+            let decoded = Texture::from_memory(&png, &TextureSpec::default()).unwrap();
+            let garbage = Texture::from_memory(b"not a png", &TextureSpec::default()).err();
+            let blank = Texture::blank(64, 32, TextureFormat::RGBA16F, &TextureSpec::default());
+            (
+                (decoded.get_width(), decoded.get_format()),
+                garbage,
+                (blank.get_width(), blank.get_height()),
+            )
+        });
+
+        assert_eq!(decoded, (3, TextureFormat::RGBA8));
+        assert!(matches!(
+            garbage,
+            Some(TextureError::Load { path, .. }) if path == "<memory>"
+        ));
+        assert_eq!(blank, (64, 32));
+        assert!(calls.contains(&GlCall::TexImage2D {
+            target: gl::TEXTURE_2D,
+            level: 0,
+            internal_format: gl::RGBA16F as i32,
+            width: 64,
+            height: 32,
+            format: gl::RGBA,
+            type_: gl::HALF_FLOAT,
+        }));
+    }
+
+    #[test]
+    fn regions_are_uploaded_with_their_own_alignment() {
+        let spec = TextureSpec::default().mipmaps(Filter::Linear);
+        let (_, calls) = with_mock_gl(|| {
+            let texture = Texture::from_pixels(4, 4, TextureFormat::RGB8, &[0; 48], &spec);
+            texture.update_region(1, 2, 3, 1, &[255; 9]);
+        });
+
+        let update = calls
+            .iter()
+            .position(|call| matches!(call, GlCall::TexSubImage2D { .. }))
+            .unwrap();
+        assert_eq!(
+            calls[update],
+            GlCall::TexSubImage2D {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 1,
+                format: gl::RGB,
+                type_: gl::UNSIGNED_BYTE,
+            }
+        );
+        //9 byte rows need byte alignment, the 12 byte rows of the texture didn't
+        assert_eq!(
+            calls[update - 1],
+            GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 1)
+        );
+        assert_eq!(
+            calls[update + 1],
+            GlCall::PixelStorei(gl::UNPACK_ALIGNMENT, 4)
+        );
+        assert_eq!(calls[update + 2], GlCall::GenerateMipmap(gl::TEXTURE_2D));
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the 4x4 texture")]
+    fn regions_outside_the_texture_panic() {
+        with_mock_gl(|| {
+            let texture = Texture::blank(4, 4, TextureFormat::R8, &TextureSpec::default());
+            texture.update_region(2, 2, 3, 1, &[0; 3]);
+        });
+    }
 }
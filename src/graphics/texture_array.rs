@@ -0,0 +1,354 @@
+use super::gl_resource;
+use super::texture::{load_rgba, texture_bytes, Texture, TextureFormat};
+use super::texture_error::TextureError;
+use super::texture_spec::TextureSpec;
+
+/// a GL_TEXTURE_2D_ARRAY, layers of the same size that shaders pick with an index
+///
+/// sampled as `sampler2DArray` with `texture(u_Layers, vec3(uv, layer))`, so sprites using
+/// different layers can share one texture slot. `Renderer2D::draw_texture_layer` batches them
+pub struct TextureArray {
+    id: u32,
+    width: i32,
+    height: i32,
+    layers: i32,
+    format: TextureFormat,
+    spec: TextureSpec,
+}
+
+impl TextureArray {
+    /// one layer per image, in the order of `paths`, fails when there are none
+    #[track_caller]
+    pub fn from_files(paths: &[&str], spec: &TextureSpec) -> Result<TextureArray, TextureError> {
+        let mut size = None;
+        let mut pixels = Vec::new();
+        for path in paths {
+            let (width, height, layer) = load_rgba(path, true)?;
+            let (expected_width, expected_height) = *size.get_or_insert((width, height));
+            if (width, height) != (expected_width, expected_height) {
+                return Err(TextureError::SizeMismatch {
+                    path: path.to_string(),
+                    width,
+                    height,
+                    expected_width,
+                    expected_height,
+                });
+            }
+            pixels.extend_from_slice(&layer);
+        }
+        let Some((width, height)) = size else {
+            return Err(TextureError::EmptyArray);
+        };
+        Ok(Self::from_pixels(
+            width,
+            height,
+            paths.len() as i32,
+            TextureFormat::RGBA8,
+            &pixels,
+            spec,
+        ))
+    }
+
+    /// slices a sheet into `columns` x `rows` layers, numbered left to right from the top row
+    ///
+    /// pixels that don't fill a whole cell on the right and bottom are left out
+    #[track_caller]
+    pub fn from_sheet(
+        path: &str,
+        columns: i32,
+        rows: i32,
+        spec: &TextureSpec,
+    ) -> Result<TextureArray, TextureError> {
+        assert!(columns > 0 && rows > 0, "a sheet needs at least one cell");
+        let (width, height, sheet) = load_rgba(path, true)?;
+        let (cell_width, cell_height) = (width / columns, height / rows);
+        let pixels = slice_cells(&sheet, width, (cell_width, cell_height), columns, rows);
+        Ok(Self::from_pixels(
+            cell_width,
+            cell_height,
+            columns * rows,
+            TextureFormat::RGBA8,
+            &pixels,
+            spec,
+        ))
+    }
+
+    /// creates the array from tightly packed layers in `format`, one after the other
+    #[track_caller]
+    pub fn from_pixels(
+        width: i32,
+        height: i32,
+        layers: i32,
+        format: TextureFormat,
+        pixels: &[u8],
+        spec: &TextureSpec,
+    ) -> TextureArray {
+        assert_eq!(
+            pixels.len(),
+            (width * height * layers * format.bytes_per_pixel()) as usize,
+            "pixel data doesn't match the array size"
+        );
+        Self::create(
+            width,
+            height,
+            layers,
+            format,
+            pixels.as_ptr() as *const std::ffi::c_void,
+            spec,
+        )
+    }
+
+    /// creates an array with undefined contents, filled with `update_layer`
+    #[track_caller]
+    pub fn blank(
+        width: i32,
+        height: i32,
+        layers: i32,
+        format: TextureFormat,
+        spec: &TextureSpec,
+    ) -> TextureArray {
+        Self::create(width, height, layers, format, std::ptr::null(), spec)
+    }
+
+    #[track_caller]
+    fn create(
+        width: i32,
+        height: i32,
+        layers: i32,
+        format: TextureFormat,
+        pixels: *const std::ffi::c_void,
+        spec: &TextureSpec,
+    ) -> TextureArray {
+        let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+            Texture::apply_swizzle(gl::TEXTURE_2D_ARRAY, format);
+            //rows are tightly packed
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                internal_format as i32,
+                width,
+                height,
+                layers,
+                0,
+                pixel_format,
+                pixel_type,
+                pixels,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            Texture::apply_spec(gl::TEXTURE_2D_ARRAY, spec, 1.0);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+        let bytes = texture_bytes(width, height, format, spec) * layers as usize;
+        gl_resource::track("TextureArray", id, bytes);
+
+        TextureArray {
+            id,
+            width,
+            height,
+            layers,
+            format,
+            spec: *spec,
+        }
+    }
+
+    /// replaces the pixels of one layer, mipmaps are regenerated if the spec has them
+    pub fn update_layer(&self, layer: i32, pixels: &[u8]) {
+        assert!(
+            (0..self.layers).contains(&layer),
+            "layer {} is outside of the {} layers",
+            layer,
+            self.layers
+        );
+        assert_eq!(
+            pixels.len(),
+            (self.width * self.height * self.format.bytes_per_pixel()) as usize,
+            "pixel data doesn't match the layer size"
+        );
+
+        let (_, pixel_format, pixel_type) = self.format.gl_formats();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                0,
+                layer,
+                self.width,
+                self.height,
+                1,
+                pixel_format,
+                pixel_type,
+                pixels.as_ptr() as *const std::ffi::c_void,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            if self.spec.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+            }
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    pub fn bind(&self, slot: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn get_width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn get_layer_count(&self) -> i32 {
+        self.layers
+    }
+}
+
+/// copies the RGBA cells of a sheet stored bottom to top into consecutive layers
+fn slice_cells(
+    sheet: &[u8],
+    width: i32,
+    (cell_width, cell_height): (i32, i32),
+    columns: i32,
+    rows: i32,
+) -> Vec<u8> {
+    let height = sheet.len() as i32 / (width * 4);
+    let row_bytes = (cell_width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_bytes * (cell_height * columns * rows) as usize);
+    for row in 0..rows {
+        //the top row of cells is at the end
+        let bottom = height - (row + 1) * cell_height;
+        for column in 0..columns {
+            for y in bottom..bottom + cell_height {
+                let start = ((y * width + column * cell_width) * 4) as usize;
+                pixels.extend_from_slice(&sheet[start..start + row_bytes]);
+            }
+        }
+    }
+    pixels
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        if gl_resource::release("TextureArray", self.id) {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+    use crate::testing::scratch_png;
+
+    #[test]
+    fn cells_are_numbered_from_the_top_left() {
+        //a 4x2 sheet of 2x1 cells, bottom row first, every pixel is its cell number
+        let sheet: Vec<u8> = [2, 2, 3, 3, 0, 0, 1, 1]
+            .iter()
+            .flat_map(|&cell| [cell; 4])
+            .collect();
+        let layers = slice_cells(&sheet, 4, (2, 1), 2, 2);
+
+        let cells: Vec<u8> = layers.chunks(8).map(|layer| layer[0]).collect();
+        assert_eq!(cells, [0, 1, 2, 3]);
+        assert!(layers
+            .chunks(8)
+            .all(|layer| layer.iter().all(|&v| v == layer[0])));
+    }
+
+    #[test]
+    fn sheets_are_sliced_into_layers() {
+        let path = scratch_png("texture_array_sheet", 4, 4, &[255; 4 * 4 * 4]);
+        let (layers, calls) = with_mock_gl(|| {
+            let array = TextureArray::from_sheet(&path, 2, 2, &TextureSpec::pixel_art()).unwrap();
+            (array.get_layer_count(), array.get_width())
+        });
+
+        assert_eq!(layers, (4, 2));
+        assert!(calls.contains(&GlCall::TexImage3D {
+            target: gl::TEXTURE_2D_ARRAY,
+            internal_format: gl::RGBA8 as i32,
+            width: 2,
+            height: 2,
+            depth: 4,
+        }));
+        assert!(calls.contains(&GlCall::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_MAG_FILTER,
+            gl::NEAREST as i32
+        )));
+    }
+
+    #[test]
+    fn layers_have_to_match_in_size() {
+        let small = scratch_png("texture_array_small", 2, 2, &[255; 2 * 2 * 4]);
+        let big = scratch_png("texture_array_big", 4, 2, &[255; 4 * 2 * 4]);
+        let (error, calls) = with_mock_gl(|| {
+            TextureArray::from_files(&[&small, &big], &TextureSpec::default()).err()
+        });
+
+        assert_eq!(
+            error,
+            Some(TextureError::SizeMismatch {
+                path: big,
+                width: 4,
+                height: 2,
+                expected_width: 2,
+                expected_height: 2,
+            })
+        );
+        assert!(calls.is_empty());
+    }
+
+    #[test]
+    fn arrays_need_a_layer() {
+        let (error, calls) =
+            with_mock_gl(|| TextureArray::from_files(&[], &TextureSpec::default()).err());
+
+        assert_eq!(error, Some(TextureError::EmptyArray));
+        assert!(calls.is_empty());
+    }
+
+    #[test]
+    fn gray_arrays_are_swizzled() {
+        let (_, calls) = with_mock_gl(|| {
+            TextureArray::from_pixels(
+                1,
+                1,
+                2,
+                TextureFormat::R8,
+                &[0, 255],
+                &TextureSpec::default(),
+            )
+        });
+
+        assert!(calls.contains(&GlCall::TexParameteri(
+            gl::TEXTURE_2D_ARRAY,
+            gl::TEXTURE_SWIZZLE_G,
+            gl::RED as i32
+        )));
+    }
+}
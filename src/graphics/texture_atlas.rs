@@ -0,0 +1,604 @@
+//! packs many images into a few big textures so sprites from different images share a batch
+//! texture slot instead of rebinding per draw
+//!
+//! `AtlasBuilder::pack` only does cpu work, the result can be saved with a json sidecar and
+//! loaded again with `TextureAtlas::load` without packing every start
+use super::backend::TextureHandle;
+use super::renderer_2d::Sprite;
+use super::texture::{load_rgba, Texture, TextureFormat};
+use super::texture_error::TextureError;
+use super::texture_spec::TextureSpec;
+use crate::utils::json::{self, JsonValue};
+use crate::utils::png_writer;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// where an image ended up, pixels are measured from the bottom left like texture coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// (u0, v0, u1, v1) like `Sprite::uv_rect`
+    pub uv_rect: glm::Vec4,
+}
+
+impl AtlasRegion {
+    fn new(
+        page: usize,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        page_size: (i32, i32),
+    ) -> AtlasRegion {
+        let (page_width, page_height) = (page_size.0 as f32, page_size.1 as f32);
+        AtlasRegion {
+            page,
+            x,
+            y,
+            width,
+            height,
+            uv_rect: glm::vec4(
+                x as f32 / page_width,
+                y as f32 / page_height,
+                (x + width) as f32 / page_width,
+                (y + height) as f32 / page_height,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+/// skyline bottom left packer, keeps the top edge of everything placed so far and puts each
+/// rectangle where that edge ends up lowest
+pub struct SkylinePacker {
+    width: i32,
+    height: i32,
+    skyline: Vec<Segment>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> SkylinePacker {
+        SkylinePacker {
+            width,
+            height,
+            skyline: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// finds room for a `width` x `height` rectangle, None when it doesn't fit anymore
+    pub fn pack(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        //lowest top edge first, then the narrowest segment to leave wide gaps for wide images
+        let (index, y) = (0..self.skyline.len())
+            .filter_map(|index| Some((index, self.fit(index, width, height)?)))
+            .min_by_key(|&(index, y)| (y + height, self.skyline[index].width))?;
+
+        let x = self.skyline[index].x;
+        self.place(index, x, y + height, width);
+        Some((x, y))
+    }
+
+    /// the y a rectangle starting at segment `index` would sit at
+    fn fit(&self, index: usize, width: i32, height: i32) -> Option<i32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let y = self.skyline[index..]
+            .iter()
+            .take_while(|segment| segment.x < x + width)
+            .map(|segment| segment.y)
+            .max()?;
+        (y + height <= self.height).then_some(y)
+    }
+
+    fn place(&mut self, index: usize, x: i32, top: i32, width: i32) {
+        self.skyline.insert(index, Segment { x, y: top, width });
+
+        //cut away what the new segment covers
+        let end = x + width;
+        while let Some(next) = self.skyline.get_mut(index + 1) {
+            if next.x >= end {
+                break;
+            }
+            let covered = end - next.x;
+            if next.width <= covered {
+                self.skyline.remove(index + 1);
+            } else {
+                next.x += covered;
+                next.width -= covered;
+                break;
+            }
+        }
+
+        //neighbours at the same height are one segment
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// page size and named regions of an atlas
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasLayout {
+    pub page_width: i32,
+    pub page_height: i32,
+    pub page_count: usize,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+/// a packed atlas that isn't on the gpu yet, pages are RGBA8 with rows bottom to top
+pub struct PackedAtlas {
+    pub layout: AtlasLayout,
+    pub pages: Vec<Vec<u8>>,
+}
+
+struct AtlasImage {
+    name: String,
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+}
+
+/// collects images and packs them into pages
+///
+/// `padding` keeps transparent pixels between images, `extrude` repeats the edge pixels of
+/// every image outwards so linear filtering and mipmaps don't bleed in their neighbours
+pub struct AtlasBuilder {
+    page_width: i32,
+    page_height: i32,
+    padding: i32,
+    extrude: i32,
+    images: Vec<AtlasImage>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_width: i32, page_height: i32) -> AtlasBuilder {
+        AtlasBuilder {
+            page_width,
+            page_height,
+            padding: 0,
+            extrude: 0,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn padding(self, padding: i32) -> AtlasBuilder {
+        AtlasBuilder { padding, ..self }
+    }
+
+    pub fn extrude(self, extrude: i32) -> AtlasBuilder {
+        AtlasBuilder { extrude, ..self }
+    }
+
+    /// loads an image file as RGBA, `name` is what it's looked up by later
+    pub fn add_image(&mut self, name: &str, path: &str) -> Result<(), TextureError> {
+        //the same orientation Texture uploads with
+        let (width, height, pixels) = load_rgba(path, true)?;
+        self.add_pixels(name, width, height, &pixels);
+        Ok(())
+    }
+
+    /// adds RGBA8 pixels with rows bottom to top, the image can't be empty
+    pub fn add_pixels(&mut self, name: &str, width: i32, height: i32, rgba: &[u8]) {
+        assert!(
+            width > 0 && height > 0,
+            "atlas image {} is {}x{}, images can't be empty",
+            name,
+            width,
+            height
+        );
+        assert_eq!(
+            rgba.len(),
+            (width * height * 4) as usize,
+            "pixel data doesn't match the image size"
+        );
+        assert!(
+            self.images.iter().all(|image| image.name != name),
+            "atlas already has an image called {}",
+            name
+        );
+        self.images.push(AtlasImage {
+            name: name.to_string(),
+            width,
+            height,
+            pixels: rgba.to_vec(),
+        });
+    }
+
+    /// packs the images into as many pages as needed and draws the pages, no gl involved
+    pub fn pack(&self) -> Result<PackedAtlas, TextureError> {
+        let border = self.extrude * 2;
+        let page_size = (self.page_width, self.page_height);
+
+        //tall images first packs tighter
+        let mut order: Vec<&AtlasImage> = self.images.iter().collect();
+        order.sort_by(|a, b| {
+            (b.height, b.width)
+                .cmp(&(a.height, a.width))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut packers: Vec<SkylinePacker> = Vec::new();
+        let mut pages: Vec<Vec<u8>> = Vec::new();
+        let mut regions = BTreeMap::new();
+        for image in order {
+            let (width, height) = (image.width + border, image.height + border);
+            if width > self.page_width || height > self.page_height {
+                return Err(TextureError::TooLarge {
+                    name: image.name.clone(),
+                    width: image.width,
+                    height: image.height,
+                    page_width: self.page_width,
+                    page_height: self.page_height,
+                });
+            }
+
+            //padding after every rectangle, the packer is wider so the last one can go over
+            let (packed_width, packed_height) = (width + self.padding, height + self.padding);
+            let placed = packers
+                .iter_mut()
+                .enumerate()
+                .find_map(|(page, packer)| Some((page, packer.pack(packed_width, packed_height)?)));
+            let (page, (x, y)) = match placed {
+                Some(placed) => placed,
+                None => {
+                    let mut packer = SkylinePacker::new(
+                        self.page_width + self.padding,
+                        self.page_height + self.padding,
+                    );
+                    let position = packer.pack(packed_width, packed_height).unwrap();
+                    packers.push(packer);
+                    pages.push(vec![0; (self.page_width * self.page_height * 4) as usize]);
+                    (packers.len() - 1, position)
+                }
+            };
+
+            let (x, y) = (x + self.extrude, y + self.extrude);
+            self.draw(&mut pages[page], image, x, y);
+            regions.insert(
+                image.name.clone(),
+                AtlasRegion::new(page, x, y, image.width, image.height, page_size),
+            );
+        }
+
+        Ok(PackedAtlas {
+            layout: AtlasLayout {
+                page_width: self.page_width,
+                page_height: self.page_height,
+                page_count: pages.len(),
+                regions,
+            },
+            pages,
+        })
+    }
+
+    /// packs and uploads in one go
+    pub fn build(&self, spec: &TextureSpec) -> Result<TextureAtlas, TextureError> {
+        Ok(self.pack()?.upload(spec))
+    }
+
+    /// copies `image` to (`x`, `y`) and extrudes its edges
+    fn draw(&self, page: &mut [u8], image: &AtlasImage, x: i32, y: i32) {
+        let page_width = self.page_width as usize;
+        let extrude = self.extrude;
+        for row in -extrude..image.height + extrude {
+            let source_row = row.clamp(0, image.height - 1) as usize;
+            for column in -extrude..image.width + extrude {
+                let source_column = column.clamp(0, image.width - 1) as usize;
+                let source = (source_row * image.width as usize + source_column) * 4;
+                let target = ((y + row) as usize * page_width + (x + column) as usize) * 4;
+                page[target..target + 4].copy_from_slice(&image.pixels[source..source + 4]);
+            }
+        }
+    }
+}
+
+/// `<stem>_<page>.png` next to the sidecar
+fn page_file(path: &Path, page: usize) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    format!("{}_{}.png", stem, page)
+}
+
+impl PackedAtlas {
+    pub fn upload(&self, spec: &TextureSpec) -> TextureAtlas {
+        let layout = &self.layout;
+        let pages = self
+            .pages
+            .iter()
+            .map(|pixels| {
+                Texture::from_pixels(
+                    layout.page_width,
+                    layout.page_height,
+                    TextureFormat::RGBA8,
+                    pixels,
+                    spec,
+                )
+            })
+            .collect();
+        TextureAtlas {
+            pages,
+            layout: self.layout.clone(),
+        }
+    }
+
+    /// writes the layout to the json file at `path` and every page as a png next to it
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let path = Path::new(path);
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let (width, height) = (self.layout.page_width, self.layout.page_height);
+        let row = width as usize * 4;
+
+        let mut files = Vec::new();
+        for (index, pixels) in self.pages.iter().enumerate() {
+            //pngs are stored top row first
+            let flipped: Vec<u8> = pixels.chunks(row).rev().flatten().copied().collect();
+            let file = page_file(path, index);
+            png_writer::write_png(
+                directory.join(&file).to_str().unwrap(),
+                width as u32,
+                height as u32,
+                &flipped,
+            )?;
+            files.push(JsonValue::String(file));
+        }
+
+        let number = |value: i32| JsonValue::Number(value as f64);
+        let regions = self
+            .layout
+            .regions
+            .iter()
+            .map(|(name, region)| {
+                let region = JsonValue::Object(vec![
+                    ("page".to_string(), number(region.page as i32)),
+                    ("x".to_string(), number(region.x)),
+                    ("y".to_string(), number(region.y)),
+                    ("width".to_string(), number(region.width)),
+                    ("height".to_string(), number(region.height)),
+                ]);
+                (name.clone(), region)
+            })
+            .collect();
+        let sidecar = JsonValue::Object(vec![
+            ("page_width".to_string(), number(width)),
+            ("page_height".to_string(), number(height)),
+            ("pages".to_string(), JsonValue::Array(files)),
+            ("regions".to_string(), JsonValue::Object(regions)),
+        ]);
+        std::fs::write(path, sidecar.to_pretty_string())
+    }
+}
+
+/// the uploaded pages of an atlas and where every image is on them
+pub struct TextureAtlas {
+    pages: Vec<Texture>,
+    layout: AtlasLayout,
+}
+
+impl TextureAtlas {
+    /// loads an atlas written by `PackedAtlas::save`
+    pub fn load(path: &str, spec: &TextureSpec) -> Result<TextureAtlas, TextureError> {
+        let error = |reason: String| TextureError::Load {
+            path: path.to_string(),
+            reason,
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let sidecar = json::parse(&text).map_err(error)?;
+
+        let int = |value: &JsonValue, key: &str| {
+            value
+                .get(key)
+                .and_then(JsonValue::as_i32)
+                .ok_or_else(|| error(format!("missing number \"{}\"", key)))
+        };
+        let page_width = int(&sidecar, "page_width")?;
+        let page_height = int(&sidecar, "page_height")?;
+        let files = sidecar
+            .get("pages")
+            .and_then(JsonValue::as_array)
+            .ok_or_else(|| error("missing \"pages\"".to_string()))?;
+        let entries = sidecar
+            .get("regions")
+            .and_then(JsonValue::as_object)
+            .ok_or_else(|| error("missing \"regions\"".to_string()))?;
+
+        let mut regions = BTreeMap::new();
+        for (name, region) in entries {
+            let page = int(region, "page")? as usize;
+            if page >= files.len() {
+                return Err(error(format!(
+                    "{} is on page {} which doesn't exist",
+                    name, page
+                )));
+            }
+            let region = AtlasRegion::new(
+                page,
+                int(region, "x")?,
+                int(region, "y")?,
+                int(region, "width")?,
+                int(region, "height")?,
+                (page_width, page_height),
+            );
+            regions.insert(name.clone(), region);
+        }
+
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut pages = Vec::with_capacity(files.len());
+        for file in files {
+            let file = file
+                .as_str()
+                .ok_or_else(|| error("page names have to be strings".to_string()))?;
+            pages.push(Texture::with_spec(
+                directory.join(file).to_str().unwrap(),
+                spec,
+            )?);
+        }
+
+        Ok(TextureAtlas {
+            layout: AtlasLayout {
+                page_width,
+                page_height,
+                page_count: pages.len(),
+                regions,
+            },
+            pages,
+        })
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.layout.regions.get(name)
+    }
+
+    pub fn get_page(&self, page: usize) -> &Texture {
+        &self.pages[page]
+    }
+
+    pub fn get_layout(&self) -> &AtlasLayout {
+        &self.layout
+    }
+
+    /// a sprite showing the image called `name`, None if the atlas doesn't have it
+    pub fn sprite(&self, name: &str, position: glm::Vec2, size: glm::Vec2) -> Option<Sprite> {
+        let region = self.get_region(name)?;
+        let texture = TextureHandle::from(&self.pages[region.page]);
+        let mut sprite = Sprite::new(texture, position, size);
+        sprite.uv_rect = region.uv_rect;
+        Some(sprite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_gl::{with_mock_gl, GlCall};
+    use crate::testing::scratch_dir;
+
+    fn overlaps(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.page == b.page
+            && a.x < b.x + b.width
+            && b.x < a.x + a.width
+            && a.y < b.y + b.height
+            && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn skyline_fills_the_lowest_gap_first() {
+        let mut packer = SkylinePacker::new(10, 10);
+        assert_eq!(packer.pack(6, 4), Some((0, 0)));
+        assert_eq!(packer.pack(4, 2), Some((6, 0)));
+        //goes on top of the lower 4x2 instead of the 6x4
+        assert_eq!(packer.pack(4, 3), Some((6, 2)));
+        assert_eq!(packer.pack(10, 5), Some((0, 5)));
+        assert_eq!(packer.pack(1, 2), None);
+    }
+
+    #[test]
+    fn images_get_padding_and_extruded_edges() {
+        let mut builder = AtlasBuilder::new(16, 16).padding(1).extrude(1);
+        //a 2x2 with a different color per pixel
+        builder.add_pixels(
+            "corners",
+            2,
+            2,
+            &[1, 0, 0, 255, 2, 0, 0, 255, 3, 0, 0, 255, 4, 0, 0, 255],
+        );
+        for i in 0..6 {
+            builder.add_pixels(&format!("block{}", i), 5, 3, &[9; 5 * 3 * 4]);
+        }
+        let atlas = builder.pack().unwrap();
+
+        let regions: Vec<_> = atlas.layout.regions.values().collect();
+        for (i, a) in regions.iter().enumerate() {
+            for b in &regions[i + 1..] {
+                //padding and both extruded borders keep images 3 pixels apart
+                let grown = AtlasRegion::new(
+                    b.page,
+                    b.x - 3,
+                    b.y - 3,
+                    b.width + 6,
+                    b.height + 6,
+                    (16, 16),
+                );
+                assert!(!overlaps(a, &grown), "{:?} is too close to {:?}", a, b);
+            }
+        }
+        assert_eq!(atlas.layout.page_count, 2);
+
+        let corners = atlas.layout.regions["corners"];
+        let page = &atlas.pages[corners.page];
+        let pixel = |x: i32, y: i32| page[((y * 16 + x) * 4) as usize];
+        let (x, y) = (corners.x, corners.y);
+        assert_eq!(pixel(x, y), 1);
+        //the bottom left pixel is repeated left, below and diagonally
+        assert_eq!(
+            [pixel(x - 1, y), pixel(x, y - 1), pixel(x - 1, y - 1)],
+            [1, 1, 1]
+        );
+        assert_eq!([pixel(x + 2, y + 1), pixel(x + 1, y + 2)], [4, 4]);
+        assert_eq!(
+            corners.uv_rect,
+            glm::vec4(x as f32, y as f32, (x + 2) as f32, (y + 2) as f32) / 16.0
+        );
+    }
+
+    #[test]
+    fn images_bigger_than_a_page_are_errors() {
+        let mut builder = AtlasBuilder::new(8, 8).extrude(1);
+        builder.add_pixels("wide", 7, 1, &[0; 7 * 4]);
+        assert!(matches!(
+            builder.pack(),
+            Err(TextureError::TooLarge { name, width: 7, .. }) if name == "wide"
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "images can't be empty")]
+    fn empty_images_are_rejected() {
+        AtlasBuilder::new(8, 8)
+            .extrude(1)
+            .add_pixels("empty", 0, 4, &[]);
+    }
+
+    #[test]
+    fn saved_atlases_load_back() {
+        let path = scratch_dir("atlas").join("atlas.json");
+        let path = path.to_str().unwrap();
+        let mut builder = AtlasBuilder::new(8, 8).padding(2);
+        builder.add_pixels("a", 3, 2, &[255; 3 * 2 * 4]);
+        builder.add_pixels("b", 4, 4, &[128; 4 * 4 * 4]);
+        let packed = builder.pack().unwrap();
+        packed.save(path).unwrap();
+
+        let (atlas, calls) = with_mock_gl(|| TextureAtlas::load(path, &TextureSpec::pixel_art()));
+        let atlas = atlas.unwrap();
+
+        assert_eq!(atlas.get_layout(), &packed.layout);
+        assert!(atlas.get_region("missing").is_none());
+        let sprite = atlas
+            .sprite("a", glm::vec2(0.0, 0.0), glm::vec2(3.0, 2.0))
+            .unwrap();
+        assert_eq!(sprite.uv_rect, atlas.get_region("a").unwrap().uv_rect);
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| matches!(call, GlCall::TexImage2D { width: 8, .. }))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureError {
+    /// stb_image couldn't open or decode the file, `reason` is what it reported
+    Load { path: String, reason: String },
+    /// only 1 to 4 channels (R, RG, RGB, RGBA) can be uploaded
+    UnsupportedChannels { path: String, channels: i32 },
+    /// an image added to an atlas is bigger than its pages, `name` is the one it was added with
+    TooLarge {
+        name: String,
+        width: i32,
+        height: i32,
+        page_width: i32,
+        page_height: i32,
+    },
+    /// every layer of an array and every face of a cubemap has to be the same size
+    SizeMismatch {
+        path: String,
+        width: i32,
+        height: i32,
+        expected_width: i32,
+        expected_height: i32,
+    },
+    /// a texture array was created from an empty list of images
+    EmptyArray,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Load { path, reason } => {
+                write!(f, "Failed to load texture {}: {}", path, reason)
+            }
+            TextureError::UnsupportedChannels { path, channels } => write!(
+                f,
+                "Texture {} has {} channels, only 1 to 4 are supported",
+                path, channels
+            ),
+            TextureError::TooLarge {
+                name,
+                width,
+                height,
+                page_width,
+                page_height,
+            } => write!(
+                f,
+                "Atlas image {} is {}x{} and doesn't fit on a {}x{} page",
+                name, width, height, page_width, page_height
+            ),
+            TextureError::SizeMismatch {
+                path,
+                width,
+                height,
+                expected_width,
+                expected_height,
+            } => write!(
+                f,
+                "Texture {} is {}x{}, expected {}x{}",
+                path, width, height, expected_width, expected_height
+            ),
+            TextureError::EmptyArray => write!(f, "A texture array needs at least one image"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
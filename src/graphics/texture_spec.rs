@@ -0,0 +1,197 @@
+/// how texels are picked when a texture is scaled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// blocky, what pixel art wants
+    Nearest,
+    Linear,
+}
+
+/// what happens outside of the 0..1 texture coordinate range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// `TextureSpec::border_color` outside the texture
+    ClampToBorder,
+}
+
+impl Wrap {
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// sampler state of a texture, built like `TextureSpec::default().filter(Filter::Nearest)`
+///
+/// the default is linear filtering, clamp to edge and no mipmaps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureSpec {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// filter between mipmap levels, only used with `mipmaps`
+    pub mipmap_filter: Filter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub border_color: glm::Vec4,
+    /// generates the whole mip chain after every upload
+    pub mipmaps: bool,
+    /// 1 turns it off, clamped to what the driver supports and ignored where it isn't
+    pub anisotropy: f32,
+}
+
+impl Default for TextureSpec {
+    fn default() -> TextureSpec {
+        TextureSpec {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: Filter::Linear,
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            border_color: glm::vec4(0.0, 0.0, 0.0, 0.0),
+            mipmaps: false,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+impl TextureSpec {
+    /// nearest filtering without mipmaps so pixels stay sharp
+    pub fn pixel_art() -> TextureSpec {
+        TextureSpec::default().filter(Filter::Nearest)
+    }
+
+    /// sets the min and mag filter
+    pub fn filter(self, filter: Filter) -> TextureSpec {
+        TextureSpec {
+            min_filter: filter,
+            mag_filter: filter,
+            ..self
+        }
+    }
+
+    pub fn min_filter(self, min_filter: Filter) -> TextureSpec {
+        TextureSpec { min_filter, ..self }
+    }
+
+    pub fn mag_filter(self, mag_filter: Filter) -> TextureSpec {
+        TextureSpec { mag_filter, ..self }
+    }
+
+    /// sets the wrap mode of both axes
+    pub fn wrap(self, wrap: Wrap) -> TextureSpec {
+        TextureSpec {
+            wrap_s: wrap,
+            wrap_t: wrap,
+            ..self
+        }
+    }
+
+    pub fn wrap_s(self, wrap_s: Wrap) -> TextureSpec {
+        TextureSpec { wrap_s, ..self }
+    }
+
+    pub fn wrap_t(self, wrap_t: Wrap) -> TextureSpec {
+        TextureSpec { wrap_t, ..self }
+    }
+
+    pub fn border_color(self, border_color: glm::Vec4) -> TextureSpec {
+        TextureSpec {
+            border_color,
+            ..self
+        }
+    }
+
+    /// turns on mipmaps, `mipmap_filter` blends between levels
+    pub fn mipmaps(self, mipmap_filter: Filter) -> TextureSpec {
+        TextureSpec {
+            mipmaps: true,
+            mipmap_filter,
+            ..self
+        }
+    }
+
+    pub fn anisotropy(self, anisotropy: f32) -> TextureSpec {
+        TextureSpec { anisotropy, ..self }
+    }
+
+    /// GL_TEXTURE_MIN_FILTER, which picks a mipmap variant when mipmaps are on
+    pub fn gl_min_filter(&self) -> u32 {
+        match (self.mipmaps, self.min_filter, self.mipmap_filter) {
+            (false, Filter::Nearest, _) => gl::NEAREST,
+            (false, Filter::Linear, _) => gl::LINEAR,
+            (true, Filter::Nearest, Filter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (true, Filter::Nearest, Filter::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (true, Filter::Linear, Filter::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (true, Filter::Linear, Filter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    pub fn gl_mag_filter(&self) -> u32 {
+        match self.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+/// core in 4.6 and in GL_ARB/EXT_texture_filter_anisotropic, the gl bindings don't have them
+pub const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+/// the highest anisotropy the driver allows, None if it doesn't do anisotropic filtering
+pub fn max_anisotropy() -> Option<f32> {
+    let mut supported = false;
+    unsafe {
+        let mut major = 0;
+        let mut minor = 0;
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        supported |= (major, minor) >= (4, 6);
+
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        for i in 0..count.max(0) as u32 {
+            let name = gl::GetStringi(gl::EXTENSIONS, i);
+            if name.is_null() {
+                continue;
+            }
+            let name = std::ffi::CStr::from_ptr(name as *const std::ffi::c_char);
+            supported |= matches!(
+                name.to_bytes(),
+                b"GL_ARB_texture_filter_anisotropic" | b"GL_EXT_texture_filter_anisotropic"
+            );
+        }
+    }
+    if !supported {
+        return None;
+    }
+
+    let mut max = 0.0;
+    unsafe { gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max) };
+    (max >= 1.0).then_some(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mipmaps_pick_the_combined_min_filter() {
+        let spec = TextureSpec::pixel_art();
+        assert_eq!(spec.gl_min_filter(), gl::NEAREST);
+        assert_eq!(spec.gl_mag_filter(), gl::NEAREST);
+
+        let spec = spec.mipmaps(Filter::Linear);
+        assert_eq!(spec.gl_min_filter(), gl::NEAREST_MIPMAP_LINEAR);
+        assert_eq!(
+            spec.min_filter(Filter::Linear).gl_min_filter(),
+            gl::LINEAR_MIPMAP_LINEAR
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::graphics::buffers::vertex_buffer_layout::AttributeMode;
+use std::fmt;
+
+/// a vertex array that doesn't fit the attributes of a shader
+#[derive(Debug, Clone, PartialEq)]
+pub enum VertexLayoutError {
+    /// the shader reads an attribute no buffer of the vertex array provides
+    MissingAttribute { name: String, location: i32 },
+    /// the buffer has more components than the attribute, gl would drop the rest
+    TooManyComponents {
+        name: String,
+        location: i32,
+        glsl_type: &'static str,
+        count: i32,
+    },
+    /// an int attribute fed floats or the other way around, gl doesn't convert between them
+    ComponentTypeMismatch {
+        name: String,
+        location: i32,
+        glsl_type: &'static str,
+        mode: AttributeMode,
+    },
+    /// the backend can't read the component type of the element, the software one only has floats
+    UnsupportedType {
+        name: String,
+        location: i32,
+        type_: u32,
+    },
+}
+
+impl fmt::Display for VertexLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VertexLayoutError::MissingAttribute { name, location } => write!(
+                f,
+                "attribute '{}' at location {} isn't provided by any vertex buffer",
+                name, location
+            ),
+            VertexLayoutError::TooManyComponents {
+                name,
+                location,
+                glsl_type,
+                count,
+            } => write!(
+                f,
+                "attribute '{}' at location {} is a {} but the vertex buffer provides {} components",
+                name, location, glsl_type, count
+            ),
+            VertexLayoutError::ComponentTypeMismatch {
+                name,
+                location,
+                glsl_type,
+                mode,
+            } => write!(
+                f,
+                "attribute '{}' at location {} is a {} but the vertex buffer element is {:?}",
+                name, location, glsl_type, mode
+            ),
+            VertexLayoutError::UnsupportedType {
+                name,
+                location,
+                type_,
+            } => write!(
+                f,
+                "attribute '{}' at location {} is fed components of type 0x{:X} the backend can't read",
+                name, location, type_
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VertexLayoutError {}
@@ -1,28 +1,190 @@
 extern crate nalgebra_glm as glm;
 extern crate stb_image;
 
+use colored::*;
 use glfw::{Action, Context, Key};
 
 //pub mod egui_backend;
 pub mod graphics;
 pub mod utils;
 
-use graphics::buffers::{index_buffer, vertex_array, vertex_buffer, vertex_buffer_layout};
+#[cfg(test)]
+mod testing;
+
+use graphics::buffers::uniform_buffer::CameraData;
+use graphics::framebuffer::Framebuffer;
+use graphics::framebuffer_error::FramebufferError;
+use graphics::gl_resource::{self, ContextGuard};
+use graphics::post_process::{PostPass, PostProcessStack};
 use graphics::renderer::{debug_message_callback, Renderer};
-use graphics::shader;
-use graphics::texture;
+use graphics::renderer_2d::Renderer2D;
+use graphics::texture_atlas::{AtlasBuilder, TextureAtlas};
+use graphics::texture_spec::TextureSpec;
+use graphics::vertex_layout_error::VertexLayoutError;
 use utils::camera::Camera2D;
 use utils::fps_manager::FPSManager;
+use utils::png_writer;
 use utils::rgb_color::Color;
 
 const MOVE_SPEED: f32 = 200.0; //pixels per second
 const WINDOW_WIDTH: u32 = 960;
 const WINDOW_HEIGHT: u32 = 540;
+//packed on the first start, delete it after changing the sprites
+const ATLAS_PATH: &str = "target/atlas/sprites.json";
+
+/// everything that gets drawn each frame, shared by the window loop and headless mode
+struct Scene {
+    renderer: Renderer,
+    renderer_2d: Renderer2D,
+    camera: Camera2D,
+    start_time: std::time::Instant,
+    proj: glm::Mat4,
+    //all sprites come from one page so they share a texture slot
+    atlas: TextureAtlas,
+    //1, 2 and 3 toggle grayscale, bloom and crt, the vignette is always on
+    post: PostProcessStack,
+}
+
+impl Scene {
+    fn new() -> Result<Scene, Box<dyn std::error::Error>> {
+        Ok(Scene {
+            renderer: Renderer::new(),
+            renderer_2d: Renderer2D::new()?,
+            camera: Camera2D::new(),
+            start_time: std::time::Instant::now(),
+            proj: glm::ortho(0.0, 960.0, 0.0, 540.0, -1.0, 1.0), //orthographic projection converts the pixel space to normalized device coordinates
+            atlas: Self::load_atlas()?,
+            post: Self::create_post_stack()?,
+        })
+    }
+
+    fn create_post_stack() -> Result<PostProcessStack, Box<dyn std::error::Error>> {
+        let mut post = PostProcessStack::new(WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32)?;
+        for pass in PostPass::bloom(0.8, 0.6)? {
+            post.push(pass);
+        }
+        post.push(PostPass::grayscale(1.0)?);
+        post.push(PostPass::crt(0.05, 0.25)?);
+        post.push(PostPass::vignette(0.75, 0.45)?);
+        for name in ["bloom", "grayscale", "crt"] {
+            post.set_enabled(name, false);
+        }
+        Ok(post)
+    }
+
+    /// follows the window, the projection stays the same so the scene is stretched
+    fn resize(&mut self, width: i32, height: i32) -> Result<(), FramebufferError> {
+        unsafe { gl::Viewport(0, 0, width, height) };
+        self.post.resize(width, height)
+    }
+
+    /// flips a post process effect on or off
+    fn toggle_effect(&mut self, name: &str) {
+        let enabled = self
+            .post
+            .get_passes()
+            .iter()
+            .any(|pass| pass.get_name() == name && pass.is_enabled());
+        self.post.set_enabled(name, !enabled);
+    }
+
+    fn load_atlas() -> Result<TextureAtlas, Box<dyn std::error::Error>> {
+        let spec = TextureSpec::default();
+        if std::path::Path::new(ATLAS_PATH).exists() {
+            return Ok(TextureAtlas::load(ATLAS_PATH, &spec)?);
+        }
+
+        //mogcat alone is 2000x2000
+        let mut builder = AtlasBuilder::new(4096, 2048).padding(2).extrude(1);
+        for name in ["mogcat", "ghost", "guy"] {
+            builder.add_image(name, &format!("res/textures/{}.png", name))?;
+        }
+        let packed = builder.pack()?;
+        //failing to save only means packing again next time
+        let saved = std::path::Path::new(ATLAS_PATH)
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| packed.save(ATLAS_PATH));
+        if let Err(err) = saved {
+            println!(
+                "{}",
+                format!(
+                    "Warning: couldn't save the atlas to {}: {}",
+                    ATLAS_PATH, err
+                )
+                .yellow()
+            );
+        }
+        Ok(packed.upload(&spec))
+    }
+
+    /// draws a frame, a shader that doesn't match its vertices fails it
+    fn render(&mut self) -> Result<(), VertexLayoutError> {
+        let translation_a: glm::Vec2 = glm::vec2(100.0, 100.0);
+        let translation_b: glm::Vec2 = glm::vec2(400.0, 100.0);
+        let sprite_size: glm::Vec2 = glm::vec2(100.0, 100.0);
+        //let mut colors = Color::new(1.0, 0.0, 0.0);
+
+        self.post.begin(&mut self.renderer);
+        //transparent black like gl's default clear color
+        self.renderer.clear(glm::vec4(0.0, 0.0, 0.0, 0.0));
+
+        //the Camera block every shader can read
+        let camera_data = CameraData {
+            view: self.camera.get_view_matrix(),
+            projection: self.proj,
+            resolution: glm::vec2(WINDOW_WIDTH as f32, WINDOW_HEIGHT as f32),
+            time: self.start_time.elapsed().as_secs_f32(),
+        };
+
+        //world sprites move with the camera
+        self.renderer_2d.begin_scene(&camera_data);
+        for translation in [translation_a, translation_b] {
+            let mogcat = self.atlas.sprite("mogcat", translation, sprite_size);
+            self.renderer_2d.draw_sprite(&mogcat.unwrap());
+        }
+        let world = self.renderer_2d.end_scene();
+
+        //screen space sprites ignore the camera
+        self.renderer_2d.begin_scene(&CameraData {
+            view: glm::Mat4::identity(),
+            ..camera_data
+        });
+        let ghost = self.atlas.sprite(
+            "ghost",
+            glm::vec2(WINDOW_WIDTH as f32 / 2.0, WINDOW_HEIGHT as f32 / 2.0),
+            sprite_size,
+        );
+        self.renderer_2d.draw_sprite(&ghost.unwrap());
+        let screen = self.renderer_2d.end_scene();
+
+        self.post.end(&mut self.renderer)?;
+        world.and(screen)
+    }
+}
 
 fn main() {
+    //`--headless [file.png]` renders one frame offscreen and saves it instead of opening a window,
+    //add `--osmesa` when there is no display server at all (needs glfw built with OSMesa).
+    //`--no-shader-cache` always compiles shaders from source
+    let args: Vec<String> = std::env::args().collect();
+    let headless = args.iter().position(|arg| arg == "--headless");
+    let osmesa = args.iter().any(|arg| arg == "--osmesa");
+    let shader_cache = !args.iter().any(|arg| arg == "--no-shader-cache");
+
     use glfw::fail_on_errors;
     let mut glfw = glfw::init(fail_on_errors!()).unwrap();
 
+    if headless.is_some() {
+        //a hidden window still gives us a context, this works with mesa llvmpipe on ci boxes
+        glfw.window_hint(glfw::WindowHint::Visible(false));
+    }
+    if osmesa {
+        glfw.window_hint(glfw::WindowHint::ContextCreationApi(
+            glfw::ContextCreationApi::OsMesa,
+        ));
+    }
+
     //create window with gl context
     let (mut window, events) = glfw
         .create_window(
@@ -35,9 +197,12 @@ fn main() {
 
     //window.make_current();
     window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
 
     //init gl and load the opengl function pointers
     gl::load_with(|s| window.get_proc_address(s) as *const _);
+    //dropped before the window, anything outliving it doesn't call into the dead context
+    let _context = ContextGuard::new();
 
     unsafe {
         println!(
@@ -48,65 +213,45 @@ fn main() {
         );
     }
 
-    unsafe {
-        gl::Enable(gl::DEBUG_OUTPUT);
-        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
-        gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+    //debug output is core in 4.3, software contexts might not have it
+    if gl::DebugMessageCallback::is_loaded() {
+        unsafe {
+            gl::Enable(gl::DEBUG_OUTPUT);
+            gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+        }
     }
 
-    let positions: [f32; 16] = [
-        -50.0, -50.0, 0.0, 0.0, 50.0, -50.0, 1.0, 0.0, 50.0, 50.0, 1.0, 1.0, -50.0, 50.0, 0.0, 1.0,
-    ];
-
-    let indices: [u32; 6] = [0, 1, 2, 2, 3, 0];
-
     unsafe {
         gl::Enable(gl::BLEND);
         gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
 
-    let va = vertex_array::VertexArray::new();
-    va.bind();
-
-    let vb = vertex_buffer::VertexBuffer::new(&positions);
-
-    let mut layout = vertex_buffer_layout::VertexBufferLayout::new();
-    layout.push::<f32>(2);
-    layout.push::<f32>(2);
-    va.add_buffer(&vb, &layout);
-
-    let ib = index_buffer::IndexBuffer::new(&indices);
-
-    let proj: glm::Mat4 = glm::ortho(0.0, 960.0, 0.0, 540.0, -1.0, 1.0); //orthographic projection converts the pixel space to normalized device coordinates
-
-    let mut mvp = proj;
-
-    let mut shader = shader::Shader::new("res/shaders");
-    shader.bind();
-    shader.set_uniform4f("u_Color", 0.2, 0.8, 1.0, 1.0);
-
-    shader.set_uniform_mat4f("u_MVP", &mvp);
-
-    let texture = texture::Texture::new("res/textures/mogcat.png");
-    texture.bind(0);
-
-    let texture2 = texture::Texture::new("res/textures/ghost.png");
-    texture2.bind(1);
-
-    va.unbind();
-    vb.unbind();
-    ib.unbind();
-    shader.unbind();
+    //linked programs are kept next to the build output, stale entries just stop being hit
+    if shader_cache {
+        graphics::shader_cache::enable("target/shader_cache");
+    }
 
     //this is where shit goes down\
 
-    let renderer = Renderer::new();
-
-    let mut camera = Camera2D::new();
-
-    let translation_a: glm::Vec3 = glm::Vec3::new(100.0, 100.0, 0.0);
-    let translation_b: glm::Vec3 = glm::Vec3::new(400.0, 100.0, 0.0);
-    //let mut colors = Color::new(1.0, 0.0, 0.0);
+    let mut scene = match Scene::new() {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("{}", err.to_string().red());
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(index) = headless {
+        let output = args
+            .get(index + 1)
+            .filter(|arg| !arg.starts_with("--"))
+            .map(String::as_str)
+            .unwrap_or("frame.png");
+        render_headless(&mut scene, output);
+        shutdown(scene);
+        return;
+    }
 
     // Create an FPS counter
     let mut fps_counter = FPSManager::new();
@@ -120,44 +265,17 @@ fn main() {
             window.set_title(&format!("Top 10 Windows Ever Made | FPS: {}", fps));
         });
 
+        //pick up shader edits, a broken shader keeps the last working version
+        match scene.renderer_2d.reload_shaders() {
+            Ok(true) => println!("{}", "Reloaded batch shader".green()),
+            Ok(false) => {}
+            Err(err) => eprintln!("{}", err.to_string().red()),
+        }
+
         // Render here
-        renderer.clear();
-
-        //bind shader program
-        shader.bind();
-        //shader.set_uniform4f("u_Color", colors.r, colors.g, colors.b, 1.0);
-        // Draw the triangles
-        //sets the mvp matrix to the position of a then b to render image twice
-        {
-            let model: glm::Mat4 = glm::translate(&glm::Mat4::identity(), &translation_a);
-            let view: glm::Mat4 = camera.get_view_matrix();
-            mvp = proj * model * view; //should probably be done on gpu
-            texture.bind(0);
-            shader.set_uniform1i("u_Texture", 0);
-            shader.set_uniform_mat4f("u_MVP", &mvp);
-            renderer.draw(&va, &ib, &shader);
-        }
-
-        {
-            let model: glm::Mat4 = glm::translate(&glm::Mat4::identity(), &translation_b);
-            let view: glm::Mat4 = camera.get_view_matrix();
-            mvp = proj * model * view;
-            texture.bind(0);
-            shader.set_uniform1i("u_Texture", 0);
-            shader.set_uniform_mat4f("u_MVP", &mvp);
-            renderer.draw(&va, &ib, &shader);
-        }
-
-        {
-            let model: glm::Mat4 = glm::translate(
-                &glm::Mat4::identity(),
-                &glm::vec3(WINDOW_WIDTH as f32 / 2.0, WINDOW_HEIGHT as f32 / 2.0, 0.0),
-            );
-            mvp = proj * model;
-            texture2.bind(1);
-            shader.set_uniform1i("u_Texture", 1);
-            shader.set_uniform_mat4f("u_MVP", &mvp);
-            renderer.draw(&va, &ib, &shader);
+        if let Err(err) = scene.render() {
+            eprintln!("{}", err.to_string().red());
+            window.set_should_close(true);
         }
 
         //check for glfw events
@@ -168,6 +286,21 @@ fn main() {
                 glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
                     window.set_should_close(true);
                 }
+                //minimizing reports 0x0, the targets can't be empty so they keep their size
+                glfw::WindowEvent::FramebufferSize(width, height) if width > 0 && height > 0 => {
+                    if let Err(err) = scene.resize(width, height) {
+                        eprintln!("{}", err.to_string().red());
+                    }
+                }
+                glfw::WindowEvent::Key(Key::Num1, _, Action::Press, _) => {
+                    scene.toggle_effect("grayscale")
+                }
+                glfw::WindowEvent::Key(Key::Num2, _, Action::Press, _) => {
+                    scene.toggle_effect("bloom")
+                }
+                glfw::WindowEvent::Key(Key::Num3, _, Action::Press, _) => {
+                    scene.toggle_effect("crt")
+                }
                 glfw::WindowEvent::Key(key, _, action, _) => {
                     //add/remove keys as they are pressed/released
                     if action == Action::Press {
@@ -182,25 +315,25 @@ fn main() {
 
         //handle keys pressed
         if keys_pressed.contains(&Key::A) {
-            camera.move_camera(glm::vec2(
+            scene.camera.move_camera(glm::vec2(
                 -MOVE_SPEED * fps_counter.time_delta.as_secs_f32(),
                 0.0,
             ));
         }
         if keys_pressed.contains(&Key::D) {
-            camera.move_camera(glm::vec2(
+            scene.camera.move_camera(glm::vec2(
                 MOVE_SPEED * fps_counter.time_delta.as_secs_f32(),
                 0.0,
             ));
         }
         if keys_pressed.contains(&Key::W) {
-            camera.move_camera(glm::vec2(
+            scene.camera.move_camera(glm::vec2(
                 0.0,
                 MOVE_SPEED * fps_counter.time_delta.as_secs_f32(),
             ));
         }
         if keys_pressed.contains(&Key::S) {
-            camera.move_camera(glm::vec2(
+            scene.camera.move_camera(glm::vec2(
                 0.0,
                 -MOVE_SPEED * fps_counter.time_delta.as_secs_f32(),
             ));
@@ -209,4 +342,45 @@ fn main() {
         window.swap_buffers();
         glfw.poll_events();
     }
+
+    shutdown(scene);
+}
+
+/// frees the scene and reports gl objects that are still alive, which would be leaks
+fn shutdown(scene: Scene) {
+    drop(scene);
+    gl_resource::report_leaks();
+}
+
+/// renders a single frame into an offscreen framebuffer and writes it to `output` as a png
+fn render_headless(scene: &mut Scene, output: &str) {
+    let framebuffer = match Framebuffer::new(WINDOW_WIDTH as i32, WINDOW_HEIGHT as i32) {
+        Ok(framebuffer) => framebuffer,
+        Err(err) => {
+            eprintln!("{}", err.to_string().red());
+            std::process::exit(1);
+        }
+    };
+    scene.renderer.push_render_target(&framebuffer);
+    let rendered = scene.render();
+    scene.renderer.pop_render_target();
+    if let Err(err) = rendered {
+        eprintln!("{}", err.to_string().red());
+        std::process::exit(1);
+    }
+
+    let pixels = match framebuffer.read_pixels() {
+        Ok(pixels) => pixels,
+        Err(err) => {
+            eprintln!("{}", err.to_string().red());
+            std::process::exit(1);
+        }
+    };
+    match png_writer::write_png(output, WINDOW_WIDTH, WINDOW_HEIGHT, &pixels) {
+        Ok(()) => println!("{}", format!("Saved frame to {}", output).green()),
+        Err(err) => {
+            eprintln!("{}", format!("Failed to write {}: {}", output, err).red());
+            std::process::exit(1);
+        }
+    }
 }
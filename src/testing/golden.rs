@@ -0,0 +1,312 @@
+//! golden image comparison: render a described scene, read it back and compare it against
+//! a checked in reference in `res/golden`
+//!
+//! set `GOLDEN_BLESS=1` to (re)write the references from the current output instead of comparing,
+//! on failure the actual image and a diff image are written to `target/golden`
+
+use crate::graphics::backend::{RenderBackend, TextureHandle};
+use crate::graphics::buffers::uniform_buffer::CameraData;
+use crate::graphics::framebuffer::Framebuffer;
+use crate::graphics::renderer::Renderer;
+use crate::graphics::renderer_2d::{Renderer2D, Sprite};
+use crate::graphics::texture::Texture;
+use crate::utils::camera::Camera2D;
+use crate::utils::png_writer;
+
+const GOLDEN_DIR: &str = "res/golden";
+
+/// a quad in a golden scene, `texture` is a path relative to the repo root
+pub struct SceneQuad {
+    pub texture: Option<&'static str>,
+    pub position: glm::Vec2,
+    pub size: glm::Vec2,
+    pub rotation: f32,
+    pub uv_rect: glm::Vec4,
+    pub tint: glm::Vec4,
+}
+
+impl SceneQuad {
+    pub fn colored(position: glm::Vec2, size: glm::Vec2, color: glm::Vec4) -> SceneQuad {
+        SceneQuad {
+            texture: None,
+            position,
+            size,
+            rotation: 0.0,
+            uv_rect: glm::vec4(0.0, 0.0, 1.0, 1.0),
+            tint: color,
+        }
+    }
+
+    pub fn textured(texture: &'static str, position: glm::Vec2, size: glm::Vec2) -> SceneQuad {
+        SceneQuad {
+            texture: Some(texture),
+            ..SceneQuad::colored(position, size, glm::vec4(1.0, 1.0, 1.0, 1.0))
+        }
+    }
+}
+
+/// everything needed to render one frame, the projection maps one unit to one pixel
+pub struct GoldenScene {
+    pub width: u32,
+    pub height: u32,
+    pub clear_color: glm::Vec4,
+    pub camera_position: glm::Vec2,
+    pub quads: Vec<SceneQuad>,
+}
+
+impl GoldenScene {
+    pub fn new(width: u32, height: u32) -> GoldenScene {
+        GoldenScene {
+            width,
+            height,
+            clear_color: glm::vec4(0.0, 0.0, 0.0, 1.0),
+            camera_position: glm::vec2(0.0, 0.0),
+            quads: Vec::new(),
+        }
+    }
+
+    /// renders the scene offscreen with the batch renderer, returns RGBA8 top row first
+    pub fn render(&self) -> Vec<u8> {
+        super::with_gl_context(self.width, self.height, || {
+            let textures: Vec<Option<Texture>> = self
+                .quads
+                .iter()
+                .map(|quad| {
+                    quad.texture
+                        .map(|path| Texture::new(path).unwrap_or_else(|error| panic!("{}", error)))
+                })
+                .collect();
+
+            let mut renderer = Renderer::new();
+            let mut renderer_2d = Renderer2D::new().unwrap_or_else(|error| panic!("{}", error));
+
+            let framebuffer = Framebuffer::new(self.width as i32, self.height as i32)
+                .unwrap_or_else(|error| panic!("{}", error));
+            framebuffer.bind();
+            renderer.clear(self.clear_color);
+
+            renderer_2d.begin_scene(&self.camera());
+            for (quad, texture) in self.quads.iter().zip(&textures) {
+                renderer_2d.draw_sprite(&Sprite {
+                    position: quad.position,
+                    size: quad.size,
+                    rotation: quad.rotation,
+                    uv_rect: quad.uv_rect,
+                    tint: quad.tint,
+                    texture: texture.as_ref().map(TextureHandle::from),
+                    layer: 0,
+                });
+            }
+            renderer_2d
+                .end_scene()
+                .unwrap_or_else(|error| panic!("{}", error));
+            framebuffer.unbind();
+
+            framebuffer
+                .read_pixels()
+                .unwrap_or_else(|error| panic!("{}", error))
+        })
+    }
+
+    /// renders the scene with the batch renderer on top of `backend`, returns RGBA8 top row first
+    pub fn render_with(&self, backend: impl RenderBackend) -> Vec<u8> {
+        let mut renderer_2d =
+            Renderer2D::with_backend(backend).unwrap_or_else(|error| panic!("{}", error));
+
+        let mut loaded: Vec<(&str, TextureHandle)> = Vec::new();
+        let mut textures = Vec::new();
+        for quad in &self.quads {
+            let texture = quad.texture.map(|path| {
+                if let Some(&(_, texture)) = loaded.iter().find(|(p, _)| *p == path) {
+                    return texture;
+                }
+                let (width, height, pixels) =
+                    load_png(path).unwrap_or_else(|| panic!("Failed to load texture: {}", path));
+                //textures are uploaded bottom row first
+                let row_len = (width * 4) as usize;
+                let flipped: Vec<u8> = pixels.chunks(row_len).rev().flatten().copied().collect();
+                let texture = renderer_2d.get_backend_mut().create_texture(
+                    width as i32,
+                    height as i32,
+                    &flipped,
+                );
+                loaded.push((path, texture));
+                texture
+            });
+            textures.push(texture);
+        }
+
+        renderer_2d.get_backend_mut().clear(self.clear_color);
+        renderer_2d.begin_scene(&self.camera());
+        for (quad, texture) in self.quads.iter().zip(textures) {
+            renderer_2d.draw_sprite(&Sprite {
+                position: quad.position,
+                size: quad.size,
+                rotation: quad.rotation,
+                uv_rect: quad.uv_rect,
+                tint: quad.tint,
+                texture,
+                layer: 0,
+            });
+        }
+        renderer_2d
+            .end_scene()
+            .unwrap_or_else(|error| panic!("{}", error));
+        renderer_2d.get_backend().read_pixels()
+    }
+
+    /// pixel space projection seen from `camera_position`
+    fn camera(&self) -> CameraData {
+        let mut camera = Camera2D::new();
+        camera.set_position(self.camera_position);
+        let resolution = glm::vec2(self.width as f32, self.height as f32);
+        CameraData {
+            view: camera.get_view_matrix(),
+            projection: glm::ortho(0.0, resolution.x, 0.0, resolution.y, -1.0, 1.0),
+            resolution,
+            time: 0.0,
+        }
+    }
+}
+
+/// result of comparing two images of the same size
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub max_difference: u8,
+    /// red where pixels differ, a dimmed grayscale of the actual image elsewhere
+    pub diff_image: Vec<u8>,
+}
+
+/// compares RGBA8 images channel by channel, a pixel mismatches when any channel differs by more than `tolerance`
+pub fn compare(actual: &[u8], expected: &[u8], tolerance: u8) -> Comparison {
+    assert_eq!(actual.len(), expected.len(), "images have different sizes");
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff_image = Vec::with_capacity(actual.len());
+
+    for (a, e) in actual.chunks(4).zip(expected.chunks(4)) {
+        let difference = a
+            .iter()
+            .zip(e)
+            .map(|(a, e)| a.abs_diff(*e))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            diff_image.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 3 / 4) as u8;
+            diff_image.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff_image,
+    }
+}
+
+/// loads a png as RGBA8 top row first, None if it doesn't exist or can't be decoded
+pub fn load_png(path: &str) -> Option<(u32, u32, Vec<u8>)> {
+    let _guard = super::lock();
+    unsafe {
+        //Texture::new turns flipping on for the whole process
+        stb_image::stb_image::stbi_set_flip_vertically_on_load(0);
+    }
+    match stb_image::image::load_with_depth(path, 4, false) {
+        stb_image::image::LoadResult::ImageU8(image) => {
+            Some((image.width as u32, image.height as u32, image.data))
+        }
+        _ => None,
+    }
+}
+
+/// panics with a helpful message when `pixels` doesn't match `res/golden/<name>.png`
+pub fn assert_golden(name: &str, width: u32, height: u32, pixels: &[u8], tolerance: u8) {
+    let reference_path = format!("{}/{}.png", GOLDEN_DIR, name);
+
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        png_writer::write_png(&reference_path, width, height, pixels)
+            .expect("Failed to write golden image");
+        return;
+    }
+
+    let (ref_width, ref_height, reference) = load_png(&reference_path).unwrap_or_else(|| {
+        panic!(
+            "missing golden image {}, run with GOLDEN_BLESS=1 to create it",
+            reference_path
+        )
+    });
+    assert!(
+        ref_width == width && ref_height == height,
+        "golden image {} is {}x{} but the output is {}x{}",
+        reference_path,
+        ref_width,
+        ref_height,
+        width,
+        height
+    );
+
+    let comparison = compare(pixels, &reference, tolerance);
+    if comparison.mismatched_pixels == 0 {
+        return;
+    }
+
+    let output_dir = format!(
+        "{}/golden",
+        std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string())
+    );
+    let actual_path = format!("{}/{}.actual.png", output_dir, name);
+    let diff_path = format!("{}/{}.diff.png", output_dir, name);
+    let written = std::fs::create_dir_all(&output_dir)
+        .and_then(|_| png_writer::write_png(&actual_path, width, height, pixels))
+        .and_then(|_| png_writer::write_png(&diff_path, width, height, &comparison.diff_image));
+
+    panic!(
+        "{} differs from {}: {} pixels off by more than {} (max difference {}), {}",
+        name,
+        reference_path,
+        comparison.mismatched_pixels,
+        tolerance,
+        comparison.max_difference,
+        match written {
+            Ok(()) => format!("see {} and {}", actual_path, diff_path),
+            Err(err) => format!("couldn't write the diff images: {}", err),
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_images_match() {
+        let image = vec![10, 20, 30, 255, 40, 50, 60, 255];
+        let comparison = compare(&image, &image, 0);
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert_eq!(comparison.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let actual = vec![10, 20, 30, 255];
+        let expected = vec![12, 19, 30, 254];
+        assert_eq!(compare(&actual, &expected, 2).mismatched_pixels, 0);
+        assert_eq!(compare(&actual, &expected, 1).mismatched_pixels, 1);
+    }
+
+    #[test]
+    fn diff_image_marks_mismatches_red() {
+        let actual = vec![0, 0, 0, 255, 200, 200, 200, 255];
+        let expected = vec![0, 0, 0, 255, 0, 0, 0, 255];
+        let comparison = compare(&actual, &expected, 0);
+        assert_eq!(comparison.mismatched_pixels, 1);
+        assert_eq!(comparison.max_difference, 200);
+        assert_eq!(&comparison.diff_image[4..], &[255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,76 @@
+//! rendering regression tests, see the module docs in `testing` for how to run them
+
+use super::golden::{assert_golden, GoldenScene, SceneQuad};
+use crate::graphics::backend::software::SoftwareBackend;
+
+/// gpus are allowed to round 0.5 either way when blending
+const TOLERANCE: u8 = 2;
+
+fn blending_scene() -> GoldenScene {
+    let mut scene = GoldenScene::new(64, 64);
+    scene.quads.push(SceneQuad::colored(
+        glm::vec2(24.0, 24.0),
+        glm::vec2(32.0, 32.0),
+        glm::vec4(1.0, 0.0, 0.0, 1.0),
+    ));
+    //half transparent blue over the red quad and the background
+    scene.quads.push(SceneQuad::colored(
+        glm::vec2(40.0, 40.0),
+        glm::vec2(32.0, 32.0),
+        glm::vec4(0.0, 0.0, 1.0, 0.5),
+    ));
+    scene
+}
+
+fn uv_orientation_scene() -> GoldenScene {
+    //the texture is drawn 1:1 so the top left quadrant of the file has to end up top left on screen
+    let mut scene = GoldenScene::new(64, 64);
+    scene.quads.push(SceneQuad::textured(
+        "res/golden/textures/quadrants.png",
+        glm::vec2(32.0, 32.0),
+        glm::vec2(32.0, 32.0),
+    ));
+    scene
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn blending() {
+    let scene = blending_scene();
+    let pixels = scene.render();
+    assert_golden("blending", scene.width, scene.height, &pixels, TOLERANCE);
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn uv_orientation() {
+    let scene = uv_orientation_scene();
+    let pixels = scene.render();
+    assert_golden(
+        "uv_orientation",
+        scene.width,
+        scene.height,
+        &pixels,
+        TOLERANCE,
+    );
+}
+
+#[test]
+fn blending_software() {
+    let scene = blending_scene();
+    let pixels = scene.render_with(SoftwareBackend::new(64, 64));
+    assert_golden("blending", scene.width, scene.height, &pixels, TOLERANCE);
+}
+
+#[test]
+fn uv_orientation_software() {
+    let scene = uv_orientation_scene();
+    let pixels = scene.render_with(SoftwareBackend::new(64, 64));
+    assert_golden(
+        "uv_orientation",
+        scene.width,
+        scene.height,
+        &pixels,
+        TOLERANCE,
+    );
+}
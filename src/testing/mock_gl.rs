@@ -0,0 +1,1515 @@
+//! fake gl driver for unit tests
+//!
+//! `with_mock_gl` points the gl function pointers at the functions below, they hand out object
+//! names and answer queries like a driver that never fails and record every command into a log.
+//! `fail_compile` and `fail_link` make the next compile or link report an error instead,
+//! `set_active_uniforms`, `set_active_attributes` and `set_active_uniform_blocks` decide what
+//! reflection finds in linked programs. `reject_program_binaries` makes glProgramBinary fail,
+//! `set_version` changes the context version from the default 4.6 and `set_framebuffer_status`
+//! makes framebuffers incomplete. framebuffer bindings and the viewport are tracked so they can
+//! be queried back. buffers made with glBufferStorage get memory that glMapBufferRange hands out
+//! and `mapped_memory` reads back, fences are always signaled.
+//! functions that aren't mocked stay unloaded and panic with "function not loaded" when called
+
+use crate::graphics::gl_resource::ContextGuard;
+use gl::types::{
+    GLbitfield, GLboolean, GLchar, GLenum, GLfloat, GLint, GLintptr, GLsizei, GLsizeiptr, GLsync,
+    GLuint, GLuint64,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_void;
+
+/// a recorded gl command with its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlCall {
+    GenBuffers(Vec<u32>),
+    BindBuffer(u32, u32),
+    BufferData {
+        target: u32,
+        size: isize,
+        data: Option<Vec<u8>>,
+        usage: u32,
+    },
+    BufferSubData {
+        target: u32,
+        offset: isize,
+        data: Vec<u8>,
+    },
+    BindBufferBase {
+        target: u32,
+        index: u32,
+        buffer: u32,
+    },
+    ClearBufferData(u32),
+    BufferStorage {
+        target: u32,
+        size: isize,
+        flags: u32,
+    },
+    MapBufferRange {
+        target: u32,
+        offset: isize,
+        length: isize,
+        access: u32,
+    },
+    /// the sync objects are numbered like other names
+    FenceSync(usize),
+    ClientWaitSync(usize),
+    DeleteSync(usize),
+    DeleteBuffers(Vec<u32>),
+    GenVertexArrays(Vec<u32>),
+    DeleteVertexArrays(Vec<u32>),
+    BindVertexArray(u32),
+    EnableVertexAttribArray(u32),
+    VertexAttribPointer {
+        index: u32,
+        size: i32,
+        type_: u32,
+        normalized: bool,
+        stride: i32,
+        offset: usize,
+    },
+    VertexAttribIPointer {
+        index: u32,
+        size: i32,
+        type_: u32,
+        stride: i32,
+        offset: usize,
+    },
+    GenTextures(Vec<u32>),
+    ActiveTexture(u32),
+    BindTexture(u32, u32),
+    TexParameteri(u32, u32, i32),
+    TexParameterf(u32, u32, f32),
+    /// only 4 component parameters like the border color are mocked
+    TexParameterfv(u32, u32, [f32; 4]),
+    GenerateMipmap(u32),
+    TexImage2D {
+        target: u32,
+        level: i32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        type_: u32,
+    },
+    TexImage3D {
+        target: u32,
+        internal_format: i32,
+        width: i32,
+        height: i32,
+        depth: i32,
+    },
+    /// (target, layer)
+    TexSubImage3D(u32, i32),
+    TexSubImage2D {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        type_: u32,
+    },
+    PixelStorei(u32, i32),
+    CreateShader(u32, u32),
+    ShaderSource(u32, String),
+    CompileShader(u32),
+    DeleteShader(u32),
+    CreateProgram(u32),
+    DeleteProgram(u32),
+    AttachShader(u32, u32),
+    LinkProgram(u32),
+    ProgramParameteri(u32, u32, i32),
+    ProgramBinary {
+        program: u32,
+        format: u32,
+        binary: Vec<u8>,
+    },
+    ValidateProgram(u32),
+    UseProgram(u32),
+    GetUniformLocation(u32, String),
+    UniformBlockBinding {
+        program: u32,
+        block: u32,
+        binding: u32,
+    },
+    ShaderStorageBlockBinding {
+        program: u32,
+        block: u32,
+        binding: u32,
+    },
+    Uniform1i(i32, i32),
+    Uniform1iv(i32, Vec<i32>),
+    Uniform1f(i32, f32),
+    Uniform4f(i32, [f32; 4]),
+    UniformMatrix4fv(i32, Vec<f32>),
+    /// glUniform{1,2,3,4}fv
+    Uniformfv {
+        location: i32,
+        components: usize,
+        values: Vec<f32>,
+    },
+    /// glUniform{2,3,4}iv, glUniform1iv is `Uniform1iv`
+    Uniformiv {
+        location: i32,
+        components: usize,
+        values: Vec<i32>,
+    },
+    Uniform1uiv(i32, Vec<u32>),
+    /// glUniform{2,3,4}uiv
+    Uniformuiv {
+        location: i32,
+        components: usize,
+        values: Vec<u32>,
+    },
+    /// glUniformMatrix{2,3}fv, glUniformMatrix4fv is `UniformMatrix4fv`
+    UniformMatrixfv {
+        location: i32,
+        columns: usize,
+        values: Vec<f32>,
+    },
+    GenFramebuffers(Vec<u32>),
+    BindFramebuffer(u32, u32),
+    FramebufferTexture2D {
+        target: u32,
+        attachment: u32,
+        texture_target: u32,
+        texture: u32,
+        level: i32,
+    },
+    GenRenderbuffers(Vec<u32>),
+    BindRenderbuffer(u32, u32),
+    /// (internal format, width, height)
+    RenderbufferStorage(u32, i32, i32),
+    /// (samples, internal format, width, height)
+    RenderbufferStorageMultisample(i32, u32, i32, i32),
+    FramebufferRenderbuffer {
+        attachment: u32,
+        renderbuffer: u32,
+    },
+    DrawBuffers(Vec<u32>),
+    DrawBuffer(u32),
+    ReadBuffer(u32),
+    BlitFramebuffer {
+        source: [i32; 4],
+        destination: [i32; 4],
+        mask: u32,
+        filter: u32,
+    },
+    DeleteFramebuffers(Vec<u32>),
+    DeleteRenderbuffers(Vec<u32>),
+    DeleteTextures(Vec<u32>),
+    Viewport(i32, i32, i32, i32),
+    ReadPixels {
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        format: u32,
+        type_: u32,
+    },
+    Enable(u32),
+    Disable(u32),
+    BlendFunc(u32, u32),
+    ClearColor([f32; 4]),
+    Clear(u32),
+    DrawElements {
+        mode: u32,
+        count: i32,
+        type_: u32,
+        offset: usize,
+    },
+    DrawElementsBaseVertex {
+        count: i32,
+        offset: usize,
+        base_vertex: i32,
+    },
+    DispatchCompute(u32, u32, u32),
+    MemoryBarrier(u32),
+    BindImageTexture {
+        unit: u32,
+        texture: u32,
+        access: u32,
+        format: u32,
+    },
+}
+
+#[derive(Default)]
+struct MockState {
+    calls: Vec<GlCall>,
+    last_name: u32,
+    uniform_locations: HashMap<(u32, String), i32>,
+    shader_types: HashMap<u32, u32>,
+    compile_errors: HashMap<u32, String>,
+    link_error: Option<String>,
+    active_uniforms: Vec<(String, u32, i32)>,
+    active_attributes: Vec<(String, u32, i32)>,
+    active_uniform_blocks: Vec<(String, i32)>,
+    reject_program_binaries: bool,
+    binary_programs: HashSet<u32>,
+    active_storage_blocks: Vec<String>,
+    version: Option<(i32, i32)>,
+    framebuffer_status: Option<u32>,
+    draw_framebuffer: u32,
+    read_framebuffer: u32,
+    viewport: [i32; 4],
+    bound_buffers: HashMap<u32, u32>,
+    buffer_memory: HashMap<u32, Vec<u8>>,
+    enabled: HashSet<u32>,
+}
+
+thread_local! {
+    static STATE: RefCell<MockState> = RefCell::new(MockState::default());
+}
+
+fn record(call: GlCall) {
+    STATE.with(|state| state.borrow_mut().calls.push(call));
+}
+
+fn next_name() -> u32 {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.last_name += 1;
+        state.last_name
+    })
+}
+
+/// runs `f` against the mock driver and returns its result with every command it issued
+///
+/// the context ends when `f` returns, objects returned from `f` are dropped without deleting
+pub fn with_mock_gl<T>(f: impl FnOnce() -> T) -> (T, Vec<GlCall>) {
+    //the gl function pointers are process wide, tests with a real context use the same lock
+    let _guard = super::lock();
+    gl::load_with(lookup);
+    STATE.with(|state| *state.borrow_mut() = MockState::default());
+    let context = ContextGuard::new();
+
+    let result = f();
+    drop(context);
+    let calls = STATE.with(|state| std::mem::take(&mut state.borrow_mut().calls));
+    (result, calls)
+}
+
+/// every shader of `type_` compiled from now on fails with `log`
+pub fn fail_compile(type_: u32, log: &str) {
+    STATE.with(|state| {
+        state
+            .borrow_mut()
+            .compile_errors
+            .insert(type_, log.to_string())
+    });
+}
+
+/// every program linked from now on fails with `log`
+pub fn fail_link(log: &str) {
+    STATE.with(|state| state.borrow_mut().link_error = Some(log.to_string()));
+}
+
+/// programs created with glProgramBinary from now on fail to link like after a driver update
+pub fn reject_program_binaries() {
+    STATE.with(|state| state.borrow_mut().reject_program_binaries = true);
+}
+
+/// names of the shader storage blocks every program reports from now on
+pub fn set_active_storage_blocks(blocks: &[&str]) {
+    STATE.with(|state| {
+        state.borrow_mut().active_storage_blocks =
+            blocks.iter().map(|name| name.to_string()).collect()
+    });
+}
+
+/// the context version reported from now on
+pub fn set_version(major: i32, minor: i32) {
+    STATE.with(|state| state.borrow_mut().version = Some((major, minor)));
+}
+
+/// what glCheckFramebufferStatus returns from now on
+pub fn set_framebuffer_status(status: u32) {
+    STATE.with(|state| state.borrow_mut().framebuffer_status = Some(status));
+}
+
+/// what was written through the mapping of a buffer made with glBufferStorage
+pub fn mapped_memory(buffer: u32) -> Vec<u8> {
+    STATE.with(|state| state.borrow().buffer_memory[&buffer].clone())
+}
+
+fn version() -> (i32, i32) {
+    STATE.with(|state| state.borrow().version.unwrap_or((4, 6)))
+}
+
+/// (name, type, size) of the uniforms every program reports from now on, arrays end in `[0]`
+pub fn set_active_uniforms(uniforms: &[(&str, u32, i32)]) {
+    STATE.with(|state| state.borrow_mut().active_uniforms = to_resources(uniforms));
+}
+
+/// (name, type, size) of the attributes every program reports from now on, in location order
+pub fn set_active_attributes(attributes: &[(&str, u32, i32)]) {
+    STATE.with(|state| state.borrow_mut().active_attributes = to_resources(attributes));
+}
+
+/// (name, data size) of the uniform blocks every program reports from now on
+pub fn set_active_uniform_blocks(blocks: &[(&str, i32)]) {
+    STATE.with(|state| {
+        state.borrow_mut().active_uniform_blocks = blocks
+            .iter()
+            .map(|(name, size)| (name.to_string(), *size))
+            .collect()
+    });
+}
+
+fn to_resources(resources: &[(&str, u32, i32)]) -> Vec<(String, u32, i32)> {
+    resources
+        .iter()
+        .map(|(name, type_, size)| (name.to_string(), *type_, *size))
+        .collect()
+}
+
+fn lookup(name: &str) -> *const c_void {
+    match name {
+        "glGenBuffers" => gen_buffers as *const c_void,
+        "glBindBuffer" => bind_buffer as *const c_void,
+        "glBufferData" => buffer_data as *const c_void,
+        "glBufferSubData" => buffer_sub_data as *const c_void,
+        "glBindBufferBase" => bind_buffer_base as *const c_void,
+        "glClearBufferData" => clear_buffer_data as *const c_void,
+        "glDeleteBuffers" => delete_buffers as *const c_void,
+        "glBufferStorage" => buffer_storage as *const c_void,
+        "glMapBufferRange" => map_buffer_range as *const c_void,
+        "glFenceSync" => fence_sync as *const c_void,
+        "glClientWaitSync" => client_wait_sync as *const c_void,
+        "glDeleteSync" => delete_sync as *const c_void,
+        "glGenVertexArrays" => gen_vertex_arrays as *const c_void,
+        "glDeleteVertexArrays" => delete_vertex_arrays as *const c_void,
+        "glBindVertexArray" => bind_vertex_array as *const c_void,
+        "glEnableVertexAttribArray" => enable_vertex_attrib_array as *const c_void,
+        "glVertexAttribPointer" => vertex_attrib_pointer as *const c_void,
+        "glVertexAttribIPointer" => vertex_attrib_ipointer as *const c_void,
+        "glGenTextures" => gen_textures as *const c_void,
+        "glActiveTexture" => active_texture as *const c_void,
+        "glBindTexture" => bind_texture as *const c_void,
+        "glTexParameteri" => tex_parameteri as *const c_void,
+        "glTexParameterf" => tex_parameterf as *const c_void,
+        "glTexParameterfv" => tex_parameterfv as *const c_void,
+        "glGenerateMipmap" => generate_mipmap as *const c_void,
+        "glGetFloatv" => get_floatv as *const c_void,
+        "glGetStringi" => get_stringi as *const c_void,
+        "glTexImage2D" => tex_image_2d as *const c_void,
+        "glTexSubImage2D" => tex_sub_image_2d as *const c_void,
+        "glTexImage3D" => tex_image_3d as *const c_void,
+        "glTexSubImage3D" => tex_sub_image_3d as *const c_void,
+        "glPixelStorei" => pixel_storei as *const c_void,
+        "glCreateShader" => create_shader as *const c_void,
+        "glShaderSource" => shader_source as *const c_void,
+        "glCompileShader" => compile_shader as *const c_void,
+        "glGetShaderiv" => get_shaderiv as *const c_void,
+        "glGetShaderInfoLog" => get_shader_info_log as *const c_void,
+        "glDeleteShader" => delete_shader as *const c_void,
+        "glCreateProgram" => create_program as *const c_void,
+        "glDeleteProgram" => delete_program as *const c_void,
+        "glAttachShader" => attach_shader as *const c_void,
+        "glLinkProgram" => link_program as *const c_void,
+        "glProgramParameteri" => program_parameteri as *const c_void,
+        "glProgramBinary" => program_binary as *const c_void,
+        "glGetProgramBinary" => get_program_binary as *const c_void,
+        "glGetString" => get_string as *const c_void,
+        "glValidateProgram" => validate_program as *const c_void,
+        "glGetProgramiv" => get_programiv as *const c_void,
+        "glGetProgramInfoLog" => get_program_info_log as *const c_void,
+        "glUseProgram" => use_program as *const c_void,
+        "glGetUniformLocation" => get_uniform_location as *const c_void,
+        "glUniform1i" => uniform1i as *const c_void,
+        "glUniform1iv" => uniform1iv as *const c_void,
+        "glUniform1f" => uniform1f as *const c_void,
+        "glUniform4f" => uniform4f as *const c_void,
+        "glUniformMatrix4fv" => uniform_matrix4fv as *const c_void,
+        "glUniform1fv" => uniform1fv as *const c_void,
+        "glUniform2fv" => uniform2fv as *const c_void,
+        "glUniform3fv" => uniform3fv as *const c_void,
+        "glUniform4fv" => uniform4fv as *const c_void,
+        "glUniform2iv" => uniform2iv as *const c_void,
+        "glUniform3iv" => uniform3iv as *const c_void,
+        "glUniform4iv" => uniform4iv as *const c_void,
+        "glUniform1uiv" => uniform1uiv as *const c_void,
+        "glUniform2uiv" => uniform2uiv as *const c_void,
+        "glUniform3uiv" => uniform3uiv as *const c_void,
+        "glUniform4uiv" => uniform4uiv as *const c_void,
+        "glUniformMatrix2fv" => uniform_matrix2fv as *const c_void,
+        "glUniformMatrix3fv" => uniform_matrix3fv as *const c_void,
+        "glGetActiveUniform" => get_active_uniform as *const c_void,
+        "glGetActiveAttrib" => get_active_attrib as *const c_void,
+        "glGetAttribLocation" => get_attrib_location as *const c_void,
+        "glGetActiveUniformBlockName" => get_active_uniform_block_name as *const c_void,
+        "glGetActiveUniformBlockiv" => get_active_uniform_blockiv as *const c_void,
+        "glUniformBlockBinding" => uniform_block_binding as *const c_void,
+        "glGetProgramResourceIndex" => get_program_resource_index as *const c_void,
+        "glShaderStorageBlockBinding" => shader_storage_block_binding as *const c_void,
+        "glGenFramebuffers" => gen_framebuffers as *const c_void,
+        "glBindFramebuffer" => bind_framebuffer as *const c_void,
+        "glFramebufferTexture2D" => framebuffer_texture_2d as *const c_void,
+        "glCheckFramebufferStatus" => check_framebuffer_status as *const c_void,
+        "glGenRenderbuffers" => gen_renderbuffers as *const c_void,
+        "glBindRenderbuffer" => bind_renderbuffer as *const c_void,
+        "glRenderbufferStorage" => renderbuffer_storage as *const c_void,
+        "glRenderbufferStorageMultisample" => renderbuffer_storage_multisample as *const c_void,
+        "glFramebufferRenderbuffer" => framebuffer_renderbuffer as *const c_void,
+        "glDrawBuffers" => draw_buffers as *const c_void,
+        "glDrawBuffer" => draw_buffer as *const c_void,
+        "glReadBuffer" => read_buffer as *const c_void,
+        "glBlitFramebuffer" => blit_framebuffer as *const c_void,
+        "glDeleteFramebuffers" => delete_framebuffers as *const c_void,
+        "glDeleteRenderbuffers" => delete_renderbuffers as *const c_void,
+        "glDeleteTextures" => delete_textures as *const c_void,
+        "glViewport" => viewport as *const c_void,
+        "glReadPixels" => read_pixels as *const c_void,
+        "glGetIntegerv" => get_integerv as *const c_void,
+        "glEnable" => enable as *const c_void,
+        "glDisable" => disable as *const c_void,
+        "glIsEnabled" => is_enabled as *const c_void,
+        "glBlendFunc" => blend_func as *const c_void,
+        "glClearColor" => clear_color as *const c_void,
+        "glClear" => clear as *const c_void,
+        "glDrawElements" => draw_elements as *const c_void,
+        "glDrawElementsBaseVertex" => draw_elements_base_vertex as *const c_void,
+        "glDispatchCompute" => dispatch_compute as *const c_void,
+        "glMemoryBarrier" => memory_barrier as *const c_void,
+        "glBindImageTexture" => bind_image_texture as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+/// fills `names` with fresh object names and returns them
+unsafe fn gen_names(n: GLsizei, names: *mut GLuint) -> Vec<u32> {
+    let generated: Vec<u32> = (0..n).map(|_| next_name()).collect();
+    std::ptr::copy_nonoverlapping(generated.as_ptr(), names, generated.len());
+    generated
+}
+
+unsafe fn read_bytes(data: *const c_void, size: usize) -> Vec<u8> {
+    std::slice::from_raw_parts(data as *const u8, size).to_vec()
+}
+
+extern "system" fn gen_buffers(n: GLsizei, buffers: *mut GLuint) {
+    record(GlCall::GenBuffers(unsafe { gen_names(n, buffers) }));
+}
+
+extern "system" fn bind_buffer(target: GLenum, buffer: GLuint) {
+    STATE.with(|state| state.borrow_mut().bound_buffers.insert(target, buffer));
+    record(GlCall::BindBuffer(target, buffer));
+}
+
+extern "system" fn buffer_data(
+    target: GLenum,
+    size: GLsizeiptr,
+    data: *const c_void,
+    usage: GLenum,
+) {
+    let data = (!data.is_null()).then(|| unsafe { read_bytes(data, size as usize) });
+    record(GlCall::BufferData {
+        target,
+        size,
+        data,
+        usage,
+    });
+}
+
+extern "system" fn buffer_sub_data(
+    target: GLenum,
+    offset: GLintptr,
+    size: GLsizeiptr,
+    data: *const c_void,
+) {
+    record(GlCall::BufferSubData {
+        target,
+        offset,
+        data: unsafe { read_bytes(data, size as usize) },
+    });
+}
+
+extern "system" fn bind_buffer_base(target: GLenum, index: GLuint, buffer: GLuint) {
+    record(GlCall::BindBufferBase {
+        target,
+        index,
+        buffer,
+    });
+}
+
+extern "system" fn clear_buffer_data(
+    target: GLenum,
+    _internal_format: GLenum,
+    _format: GLenum,
+    _type: GLenum,
+    _data: *const c_void,
+) {
+    record(GlCall::ClearBufferData(target));
+}
+
+extern "system" fn buffer_storage(
+    target: GLenum,
+    size: GLsizeiptr,
+    _data: *const c_void,
+    flags: GLbitfield,
+) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let buffer = state.bound_buffers.get(&target).copied().unwrap_or(0);
+        state.buffer_memory.insert(buffer, vec![0; size as usize]);
+    });
+    record(GlCall::BufferStorage {
+        target,
+        size,
+        flags,
+    });
+}
+
+extern "system" fn map_buffer_range(
+    target: GLenum,
+    offset: GLintptr,
+    length: GLsizeiptr,
+    access: GLbitfield,
+) -> *mut c_void {
+    record(GlCall::MapBufferRange {
+        target,
+        offset,
+        length,
+        access,
+    });
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let buffer = state.bound_buffers.get(&target).copied().unwrap_or(0);
+        //the vec is never resized, so the pointer stays valid until the buffer is gone
+        match state.buffer_memory.get_mut(&buffer) {
+            Some(memory) => unsafe { memory.as_mut_ptr().add(offset as usize) as *mut c_void },
+            None => std::ptr::null_mut(),
+        }
+    })
+}
+
+extern "system" fn fence_sync(_condition: GLenum, _flags: GLbitfield) -> GLsync {
+    let sync = next_name() as usize;
+    record(GlCall::FenceSync(sync));
+    sync as GLsync
+}
+
+extern "system" fn client_wait_sync(
+    sync: GLsync,
+    _flags: GLbitfield,
+    _timeout: GLuint64,
+) -> GLenum {
+    record(GlCall::ClientWaitSync(sync as usize));
+    gl::ALREADY_SIGNALED
+}
+
+extern "system" fn delete_sync(sync: GLsync) {
+    record(GlCall::DeleteSync(sync as usize));
+}
+
+extern "system" fn delete_buffers(n: GLsizei, buffers: *const GLuint) {
+    let buffers = unsafe { std::slice::from_raw_parts(buffers, n as usize) };
+    record(GlCall::DeleteBuffers(buffers.to_vec()));
+}
+
+extern "system" fn gen_vertex_arrays(n: GLsizei, arrays: *mut GLuint) {
+    record(GlCall::GenVertexArrays(unsafe { gen_names(n, arrays) }));
+}
+
+extern "system" fn delete_vertex_arrays(n: GLsizei, arrays: *const GLuint) {
+    let arrays = unsafe { std::slice::from_raw_parts(arrays, n as usize) };
+    record(GlCall::DeleteVertexArrays(arrays.to_vec()));
+}
+
+extern "system" fn bind_vertex_array(array: GLuint) {
+    record(GlCall::BindVertexArray(array));
+}
+
+extern "system" fn enable_vertex_attrib_array(index: GLuint) {
+    record(GlCall::EnableVertexAttribArray(index));
+}
+
+extern "system" fn vertex_attrib_pointer(
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: *const c_void,
+) {
+    record(GlCall::VertexAttribPointer {
+        index,
+        size,
+        type_,
+        normalized: normalized != gl::FALSE,
+        stride,
+        offset: pointer as usize,
+    });
+}
+
+extern "system" fn vertex_attrib_ipointer(
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    stride: GLsizei,
+    pointer: *const c_void,
+) {
+    record(GlCall::VertexAttribIPointer {
+        index,
+        size,
+        type_,
+        stride,
+        offset: pointer as usize,
+    });
+}
+
+extern "system" fn gen_textures(n: GLsizei, textures: *mut GLuint) {
+    record(GlCall::GenTextures(unsafe { gen_names(n, textures) }));
+}
+
+extern "system" fn active_texture(texture: GLenum) {
+    record(GlCall::ActiveTexture(texture));
+}
+
+extern "system" fn bind_texture(target: GLenum, texture: GLuint) {
+    record(GlCall::BindTexture(target, texture));
+}
+
+extern "system" fn tex_parameteri(target: GLenum, pname: GLenum, param: GLint) {
+    record(GlCall::TexParameteri(target, pname, param));
+}
+
+extern "system" fn tex_parameterf(target: GLenum, pname: GLenum, param: GLfloat) {
+    record(GlCall::TexParameterf(target, pname, param));
+}
+
+extern "system" fn tex_parameterfv(target: GLenum, pname: GLenum, params: *const GLfloat) {
+    let mut values = [0.0; 4];
+    unsafe { std::ptr::copy_nonoverlapping(params, values.as_mut_ptr(), 4) };
+    record(GlCall::TexParameterfv(target, pname, values));
+}
+
+extern "system" fn generate_mipmap(target: GLenum) {
+    record(GlCall::GenerateMipmap(target));
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_2d(
+    target: GLenum,
+    level: GLint,
+    internal_format: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    _border: GLint,
+    format: GLenum,
+    type_: GLenum,
+    _pixels: *const c_void,
+) {
+    record(GlCall::TexImage2D {
+        target,
+        level,
+        internal_format,
+        width,
+        height,
+        format,
+        type_,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_sub_image_2d(
+    _target: GLenum,
+    _level: GLint,
+    x: GLint,
+    y: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+    _pixels: *const c_void,
+) {
+    record(GlCall::TexSubImage2D {
+        x,
+        y,
+        width,
+        height,
+        format,
+        type_,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_3d(
+    target: GLenum,
+    _level: GLint,
+    internal_format: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    depth: GLsizei,
+    _border: GLint,
+    _format: GLenum,
+    _type: GLenum,
+    _pixels: *const c_void,
+) {
+    record(GlCall::TexImage3D {
+        target,
+        internal_format,
+        width,
+        height,
+        depth,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_sub_image_3d(
+    target: GLenum,
+    _level: GLint,
+    _x: GLint,
+    _y: GLint,
+    z: GLint,
+    _width: GLsizei,
+    _height: GLsizei,
+    _depth: GLsizei,
+    _format: GLenum,
+    _type: GLenum,
+    _pixels: *const c_void,
+) {
+    record(GlCall::TexSubImage3D(target, z));
+}
+
+extern "system" fn pixel_storei(pname: GLenum, param: GLint) {
+    record(GlCall::PixelStorei(pname, param));
+}
+
+extern "system" fn create_shader(type_: GLenum) -> GLuint {
+    let id = next_name();
+    STATE.with(|state| state.borrow_mut().shader_types.insert(id, type_));
+    record(GlCall::CreateShader(type_, id));
+    id
+}
+
+extern "system" fn shader_source(
+    shader: GLuint,
+    count: GLsizei,
+    strings: *const *const GLchar,
+    lengths: *const GLint,
+) {
+    let mut source = String::new();
+    for i in 0..count as usize {
+        unsafe {
+            let string = *strings.add(i);
+            //a null length array means every string is nul terminated
+            if lengths.is_null() || *lengths.add(i) < 0 {
+                source.push_str(&std::ffi::CStr::from_ptr(string).to_string_lossy());
+            } else {
+                let bytes = read_bytes(string as *const c_void, *lengths.add(i) as usize);
+                source.push_str(&String::from_utf8_lossy(&bytes));
+            }
+        }
+    }
+    record(GlCall::ShaderSource(shader, source));
+}
+
+extern "system" fn compile_shader(shader: GLuint) {
+    record(GlCall::CompileShader(shader));
+}
+
+fn compile_error(shader: GLuint) -> Option<String> {
+    STATE.with(|state| {
+        let state = state.borrow();
+        let type_ = state.shader_types.get(&shader)?;
+        state.compile_errors.get(type_).cloned()
+    })
+}
+
+fn link_error(program: GLuint) -> Option<String> {
+    STATE.with(|state| {
+        let state = state.borrow();
+        if state.reject_program_binaries && state.binary_programs.contains(&program) {
+            return Some("program binary is incompatible with this driver".to_string());
+        }
+        state.link_error.clone()
+    })
+}
+
+/// status and log length queries shared by shaders and programs
+fn status_query(error: Option<String>, status: GLenum, pname: GLenum) -> GLint {
+    if pname == status {
+        return if error.is_some() { gl::FALSE } else { gl::TRUE } as GLint;
+    }
+    match pname {
+        //the reported length includes the nul terminator
+        gl::INFO_LOG_LENGTH => error.map_or(0, |log| log.len() as GLint + 1),
+        _ => 0,
+    }
+}
+
+/// copies `log` into the callers buffer like glGet*InfoLog, truncated and nul terminated
+unsafe fn write_info_log(
+    log: Option<String>,
+    max_length: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    let log = log.unwrap_or_default();
+    let written = log.len().min((max_length.max(1) - 1) as usize);
+    if max_length > 0 {
+        std::ptr::copy_nonoverlapping(log.as_ptr() as *const GLchar, info_log, written);
+        *info_log.add(written) = 0;
+    }
+    if !length.is_null() {
+        *length = written as GLsizei;
+    }
+}
+
+extern "system" fn get_shaderiv(shader: GLuint, pname: GLenum, params: *mut GLint) {
+    let value = status_query(compile_error(shader), gl::COMPILE_STATUS, pname);
+    unsafe { *params = value };
+}
+
+extern "system" fn get_shader_info_log(
+    shader: GLuint,
+    max_length: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    unsafe { write_info_log(compile_error(shader), max_length, length, info_log) };
+}
+
+extern "system" fn delete_shader(shader: GLuint) {
+    record(GlCall::DeleteShader(shader));
+}
+
+extern "system" fn create_program() -> GLuint {
+    let id = next_name();
+    record(GlCall::CreateProgram(id));
+    id
+}
+
+extern "system" fn delete_program(program: GLuint) {
+    record(GlCall::DeleteProgram(program));
+}
+
+extern "system" fn attach_shader(program: GLuint, shader: GLuint) {
+    record(GlCall::AttachShader(program, shader));
+}
+
+extern "system" fn link_program(program: GLuint) {
+    record(GlCall::LinkProgram(program));
+}
+
+extern "system" fn program_parameteri(program: GLuint, pname: GLenum, value: GLint) {
+    record(GlCall::ProgramParameteri(program, pname, value));
+}
+
+/// the binary every program hands out, `program_binary` takes anything
+fn mock_binary(program: GLuint) -> Vec<u8> {
+    format!("mock program {}", program).into_bytes()
+}
+
+const MOCK_BINARY_FORMAT: GLenum = 0x4d4f;
+
+extern "system" fn program_binary(
+    program: GLuint,
+    format: GLenum,
+    binary: *const c_void,
+    length: GLsizei,
+) {
+    STATE.with(|state| state.borrow_mut().binary_programs.insert(program));
+    record(GlCall::ProgramBinary {
+        program,
+        format,
+        binary: unsafe { read_bytes(binary, length as usize) },
+    });
+}
+
+extern "system" fn get_program_binary(
+    program: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    format: *mut GLenum,
+    binary: *mut c_void,
+) {
+    let bytes = mock_binary(program);
+    let written = bytes.len().min(buf_size.max(0) as usize);
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), binary as *mut u8, written);
+        *length = written as GLsizei;
+        *format = MOCK_BINARY_FORMAT;
+    }
+}
+
+extern "system" fn get_string(name: GLenum) -> *const u8 {
+    let string: &'static [u8] = match name {
+        gl::VENDOR => b"LearnRust\0",
+        gl::RENDERER => b"mock gl\0",
+        gl::VERSION => b"4.6.0 mock\0",
+        _ => return std::ptr::null(),
+    };
+    string.as_ptr()
+}
+
+/// no extensions, everything the tests need is core in the default 4.6
+extern "system" fn get_stringi(_name: GLenum, _index: GLuint) -> *const u8 {
+    std::ptr::null()
+}
+
+extern "system" fn validate_program(program: GLuint) {
+    record(GlCall::ValidateProgram(program));
+}
+
+extern "system" fn get_programiv(program: GLuint, pname: GLenum, params: *mut GLint) {
+    //every compute shader claims `layout(local_size_x = 64) in;`
+    if pname == gl::COMPUTE_WORK_GROUP_SIZE {
+        unsafe { std::ptr::copy_nonoverlapping([64, 1, 1].as_ptr(), params, 3) };
+        return;
+    }
+    //the longest name plus the nul terminator
+    let max_length = |resources: &[(String, u32, i32)]| {
+        resources
+            .iter()
+            .map(|(name, _, _)| name.len() as GLint + 1)
+            .max()
+            .unwrap_or(0)
+    };
+    let value = STATE.with(|state| {
+        let state = state.borrow();
+        match pname {
+            gl::VALIDATE_STATUS => Some(gl::TRUE as GLint),
+            gl::ACTIVE_UNIFORMS => Some(state.active_uniforms.len() as GLint),
+            gl::ACTIVE_UNIFORM_MAX_LENGTH => Some(max_length(&state.active_uniforms)),
+            gl::ACTIVE_ATTRIBUTES => Some(state.active_attributes.len() as GLint),
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH => Some(max_length(&state.active_attributes)),
+            gl::PROGRAM_BINARY_LENGTH => Some(mock_binary(program).len() as GLint),
+            gl::ACTIVE_UNIFORM_BLOCKS => Some(state.active_uniform_blocks.len() as GLint),
+            gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH => state
+                .active_uniform_blocks
+                .iter()
+                .map(|(name, _)| name.len() as GLint + 1)
+                .max()
+                .or(Some(0)),
+            _ => None,
+        }
+    });
+    let value = value.unwrap_or_else(|| status_query(link_error(program), gl::LINK_STATUS, pname));
+    unsafe { *params = value };
+}
+
+/// shared by glGetActiveUniform and glGetActiveAttrib
+unsafe fn write_active_resource(
+    resource: Option<(String, u32, i32)>,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    let (resource_name, resource_type, resource_size) =
+        resource.expect("no active resource at this index");
+    write_info_log(Some(resource_name), buf_size, length, name);
+    *size = resource_size;
+    *type_ = resource_type;
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn get_active_uniform(
+    _program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    let uniform = STATE.with(|state| state.borrow().active_uniforms.get(index as usize).cloned());
+    unsafe { write_active_resource(uniform, buf_size, length, size, type_, name) };
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn get_active_attrib(
+    _program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    let attribute = STATE.with(|state| {
+        state
+            .borrow()
+            .active_attributes
+            .get(index as usize)
+            .cloned()
+    });
+    unsafe { write_active_resource(attribute, buf_size, length, size, type_, name) };
+}
+
+/// attributes are at the location of their index in `set_active_attributes`
+extern "system" fn get_attrib_location(_program: GLuint, name: *const GLchar) -> GLint {
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy();
+    STATE.with(|state| {
+        state
+            .borrow()
+            .active_attributes
+            .iter()
+            .position(|(attribute, _, _)| *attribute == name)
+            .map_or(-1, |index| index as GLint)
+    })
+}
+
+fn active_uniform_block(index: GLuint) -> (String, i32) {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .active_uniform_blocks
+            .get(index as usize)
+            .cloned()
+            .expect("no active uniform block at this index")
+    })
+}
+
+extern "system" fn get_active_uniform_block_name(
+    _program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    name: *mut GLchar,
+) {
+    let (block_name, _) = active_uniform_block(index);
+    unsafe { write_info_log(Some(block_name), buf_size, length, name) };
+}
+
+extern "system" fn get_active_uniform_blockiv(
+    _program: GLuint,
+    index: GLuint,
+    pname: GLenum,
+    params: *mut GLint,
+) {
+    let (_, data_size) = active_uniform_block(index);
+    let value = match pname {
+        gl::UNIFORM_BLOCK_DATA_SIZE => data_size,
+        _ => 0,
+    };
+    unsafe { *params = value };
+}
+
+extern "system" fn uniform_block_binding(program: GLuint, block: GLuint, binding: GLuint) {
+    record(GlCall::UniformBlockBinding {
+        program,
+        block,
+        binding,
+    });
+}
+
+/// storage blocks are at their index in `set_active_storage_blocks`
+extern "system" fn get_program_resource_index(
+    _program: GLuint,
+    interface: GLenum,
+    name: *const GLchar,
+) -> GLuint {
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }.to_string_lossy();
+    if interface != gl::SHADER_STORAGE_BLOCK {
+        return gl::INVALID_INDEX;
+    }
+    STATE.with(|state| {
+        state
+            .borrow()
+            .active_storage_blocks
+            .iter()
+            .position(|block| *block == name)
+            .map_or(gl::INVALID_INDEX, |index| index as GLuint)
+    })
+}
+
+extern "system" fn shader_storage_block_binding(program: GLuint, block: GLuint, binding: GLuint) {
+    record(GlCall::ShaderStorageBlockBinding {
+        program,
+        block,
+        binding,
+    });
+}
+
+extern "system" fn get_program_info_log(
+    program: GLuint,
+    max_length: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    unsafe { write_info_log(link_error(program), max_length, length, info_log) };
+}
+
+extern "system" fn use_program(program: GLuint) {
+    record(GlCall::UseProgram(program));
+}
+
+/// every (program, name) pair gets its own location, names are never reported missing
+extern "system" fn get_uniform_location(program: GLuint, name: *const GLchar) -> GLint {
+    let name = unsafe { std::ffi::CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned();
+    record(GlCall::GetUniformLocation(program, name.clone()));
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let next = state.uniform_locations.len() as GLint;
+        *state
+            .uniform_locations
+            .entry((program, name))
+            .or_insert(next)
+    })
+}
+
+extern "system" fn uniform1i(location: GLint, value: GLint) {
+    record(GlCall::Uniform1i(location, value));
+}
+
+extern "system" fn uniform1iv(location: GLint, count: GLsizei, values: *const GLint) {
+    let values = unsafe { std::slice::from_raw_parts(values, count as usize) }.to_vec();
+    record(GlCall::Uniform1iv(location, values));
+}
+
+extern "system" fn uniform1f(location: GLint, value: GLfloat) {
+    record(GlCall::Uniform1f(location, value));
+}
+
+extern "system" fn uniform4f(location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat, v3: GLfloat) {
+    record(GlCall::Uniform4f(location, [v0, v1, v2, v3]));
+}
+
+extern "system" fn uniform_matrix4fv(
+    location: GLint,
+    count: GLsizei,
+    _transpose: GLboolean,
+    value: *const GLfloat,
+) {
+    let values = unsafe { std::slice::from_raw_parts(value, count as usize * 16) }.to_vec();
+    record(GlCall::UniformMatrix4fv(location, values));
+}
+
+/// records a glUniform*v call with `$components` values per element
+macro_rules! uniform_v {
+    ($name:ident, $type_:ty, $components:literal, $call:ident) => {
+        extern "system" fn $name(location: GLint, count: GLsizei, value: *const $type_) {
+            let values =
+                unsafe { std::slice::from_raw_parts(value, count as usize * $components) }.to_vec();
+            record(GlCall::$call {
+                location,
+                components: $components,
+                values,
+            });
+        }
+    };
+}
+
+uniform_v!(uniform1fv, GLfloat, 1, Uniformfv);
+uniform_v!(uniform2fv, GLfloat, 2, Uniformfv);
+uniform_v!(uniform3fv, GLfloat, 3, Uniformfv);
+uniform_v!(uniform4fv, GLfloat, 4, Uniformfv);
+uniform_v!(uniform2iv, GLint, 2, Uniformiv);
+uniform_v!(uniform3iv, GLint, 3, Uniformiv);
+uniform_v!(uniform4iv, GLint, 4, Uniformiv);
+uniform_v!(uniform2uiv, GLuint, 2, Uniformuiv);
+uniform_v!(uniform3uiv, GLuint, 3, Uniformuiv);
+uniform_v!(uniform4uiv, GLuint, 4, Uniformuiv);
+
+extern "system" fn uniform1uiv(location: GLint, count: GLsizei, values: *const GLuint) {
+    let values = unsafe { std::slice::from_raw_parts(values, count as usize) }.to_vec();
+    record(GlCall::Uniform1uiv(location, values));
+}
+
+/// records a glUniformMatrix*fv call for `$columns` x `$columns` matrices
+macro_rules! uniform_matrix_fv {
+    ($name:ident, $columns:literal) => {
+        extern "system" fn $name(
+            location: GLint,
+            count: GLsizei,
+            _transpose: GLboolean,
+            value: *const GLfloat,
+        ) {
+            let len = count as usize * $columns * $columns;
+            let values = unsafe { std::slice::from_raw_parts(value, len) }.to_vec();
+            record(GlCall::UniformMatrixfv {
+                location,
+                columns: $columns,
+                values,
+            });
+        }
+    };
+}
+
+uniform_matrix_fv!(uniform_matrix2fv, 2);
+uniform_matrix_fv!(uniform_matrix3fv, 3);
+
+extern "system" fn gen_framebuffers(n: GLsizei, framebuffers: *mut GLuint) {
+    record(GlCall::GenFramebuffers(unsafe {
+        gen_names(n, framebuffers)
+    }));
+}
+
+extern "system" fn bind_framebuffer(target: GLenum, framebuffer: GLuint) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if target != gl::READ_FRAMEBUFFER {
+            state.draw_framebuffer = framebuffer;
+        }
+        if target != gl::DRAW_FRAMEBUFFER {
+            state.read_framebuffer = framebuffer;
+        }
+    });
+    record(GlCall::BindFramebuffer(target, framebuffer));
+}
+
+extern "system" fn framebuffer_texture_2d(
+    target: GLenum,
+    attachment: GLenum,
+    texture_target: GLenum,
+    texture: GLuint,
+    level: GLint,
+) {
+    record(GlCall::FramebufferTexture2D {
+        target,
+        attachment,
+        texture_target,
+        texture,
+        level,
+    });
+}
+
+extern "system" fn check_framebuffer_status(_target: GLenum) -> GLenum {
+    STATE.with(|state| {
+        state
+            .borrow()
+            .framebuffer_status
+            .unwrap_or(gl::FRAMEBUFFER_COMPLETE)
+    })
+}
+
+extern "system" fn gen_renderbuffers(n: GLsizei, renderbuffers: *mut GLuint) {
+    record(GlCall::GenRenderbuffers(unsafe {
+        gen_names(n, renderbuffers)
+    }));
+}
+
+extern "system" fn bind_renderbuffer(target: GLenum, renderbuffer: GLuint) {
+    record(GlCall::BindRenderbuffer(target, renderbuffer));
+}
+
+extern "system" fn renderbuffer_storage(
+    _target: GLenum,
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    record(GlCall::RenderbufferStorage(internal_format, width, height));
+}
+
+extern "system" fn renderbuffer_storage_multisample(
+    _target: GLenum,
+    samples: GLsizei,
+    internal_format: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    record(GlCall::RenderbufferStorageMultisample(
+        samples,
+        internal_format,
+        width,
+        height,
+    ));
+}
+
+extern "system" fn framebuffer_renderbuffer(
+    _target: GLenum,
+    attachment: GLenum,
+    _renderbuffer_target: GLenum,
+    renderbuffer: GLuint,
+) {
+    record(GlCall::FramebufferRenderbuffer {
+        attachment,
+        renderbuffer,
+    });
+}
+
+extern "system" fn draw_buffers(n: GLsizei, buffers: *const GLenum) {
+    let buffers = unsafe { std::slice::from_raw_parts(buffers, n as usize) };
+    record(GlCall::DrawBuffers(buffers.to_vec()));
+}
+
+extern "system" fn draw_buffer(buffer: GLenum) {
+    record(GlCall::DrawBuffer(buffer));
+}
+
+extern "system" fn read_buffer(buffer: GLenum) {
+    record(GlCall::ReadBuffer(buffer));
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn blit_framebuffer(
+    src_x0: GLint,
+    src_y0: GLint,
+    src_x1: GLint,
+    src_y1: GLint,
+    dst_x0: GLint,
+    dst_y0: GLint,
+    dst_x1: GLint,
+    dst_y1: GLint,
+    mask: gl::types::GLbitfield,
+    filter: GLenum,
+) {
+    record(GlCall::BlitFramebuffer {
+        source: [src_x0, src_y0, src_x1, src_y1],
+        destination: [dst_x0, dst_y0, dst_x1, dst_y1],
+        mask,
+        filter,
+    });
+}
+
+extern "system" fn delete_framebuffers(n: GLsizei, framebuffers: *const GLuint) {
+    let framebuffers = unsafe { std::slice::from_raw_parts(framebuffers, n as usize) };
+    record(GlCall::DeleteFramebuffers(framebuffers.to_vec()));
+}
+
+extern "system" fn delete_renderbuffers(n: GLsizei, renderbuffers: *const GLuint) {
+    let renderbuffers = unsafe { std::slice::from_raw_parts(renderbuffers, n as usize) };
+    record(GlCall::DeleteRenderbuffers(renderbuffers.to_vec()));
+}
+
+extern "system" fn delete_textures(n: GLsizei, textures: *const GLuint) {
+    let textures = unsafe { std::slice::from_raw_parts(textures, n as usize) };
+    record(GlCall::DeleteTextures(textures.to_vec()));
+}
+
+extern "system" fn viewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+    STATE.with(|state| state.borrow_mut().viewport = [x, y, width, height]);
+    record(GlCall::Viewport(x, y, width, height));
+}
+
+/// leaves the destination untouched, callers get whatever they initialized it with
+extern "system" fn read_pixels(
+    x: GLint,
+    y: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+    _pixels: *mut c_void,
+) {
+    record(GlCall::ReadPixels {
+        x,
+        y,
+        width,
+        height,
+        format,
+        type_,
+    });
+}
+
+extern "system" fn get_integerv(pname: GLenum, data: *mut GLint) {
+    if pname == gl::VIEWPORT {
+        let viewport = STATE.with(|state| state.borrow().viewport);
+        unsafe { std::ptr::copy_nonoverlapping(viewport.as_ptr(), data, 4) };
+        return;
+    }
+    let value = match pname {
+        gl::DRAW_FRAMEBUFFER_BINDING => STATE.with(|state| state.borrow().draw_framebuffer as i32),
+        gl::READ_FRAMEBUFFER_BINDING => STATE.with(|state| state.borrow().read_framebuffer as i32),
+        gl::MAX_COLOR_ATTACHMENTS | gl::MAX_DRAW_BUFFERS | gl::MAX_SAMPLES => 8,
+        gl::MAX_TEXTURE_IMAGE_UNITS => 16,
+        gl::NUM_PROGRAM_BINARY_FORMATS => 1,
+        gl::MAJOR_VERSION => version().0,
+        gl::MINOR_VERSION => version().1,
+        _ => 0,
+    };
+    unsafe { *data = value };
+}
+
+extern "system" fn get_floatv(pname: GLenum, data: *mut GLfloat) {
+    let value = match pname {
+        //GL_MAX_TEXTURE_MAX_ANISOTROPY
+        0x84FF => 16.0,
+        _ => 0.0,
+    };
+    unsafe { *data = value };
+}
+
+extern "system" fn enable(cap: GLenum) {
+    STATE.with(|state| state.borrow_mut().enabled.insert(cap));
+    record(GlCall::Enable(cap));
+}
+
+extern "system" fn disable(cap: GLenum) {
+    STATE.with(|state| state.borrow_mut().enabled.remove(&cap));
+    record(GlCall::Disable(cap));
+}
+
+extern "system" fn is_enabled(cap: GLenum) -> GLboolean {
+    let enabled = STATE.with(|state| state.borrow().enabled.contains(&cap));
+    if enabled {
+        gl::TRUE
+    } else {
+        gl::FALSE
+    }
+}
+
+extern "system" fn blend_func(sfactor: GLenum, dfactor: GLenum) {
+    record(GlCall::BlendFunc(sfactor, dfactor));
+}
+
+extern "system" fn clear_color(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+    record(GlCall::ClearColor([red, green, blue, alpha]));
+}
+
+extern "system" fn clear(mask: gl::types::GLbitfield) {
+    record(GlCall::Clear(mask));
+}
+
+extern "system" fn draw_elements(
+    mode: GLenum,
+    count: GLsizei,
+    type_: GLenum,
+    indices: *const c_void,
+) {
+    record(GlCall::DrawElements {
+        mode,
+        count,
+        type_,
+        offset: indices as usize,
+    });
+}
+
+extern "system" fn draw_elements_base_vertex(
+    _mode: GLenum,
+    count: GLsizei,
+    _type: GLenum,
+    indices: *const c_void,
+    base_vertex: GLint,
+) {
+    record(GlCall::DrawElementsBaseVertex {
+        count,
+        offset: indices as usize,
+        base_vertex,
+    });
+}
+
+extern "system" fn dispatch_compute(x: GLuint, y: GLuint, z: GLuint) {
+    record(GlCall::DispatchCompute(x, y, z));
+}
+
+extern "system" fn memory_barrier(barriers: gl::types::GLbitfield) {
+    record(GlCall::MemoryBarrier(barriers));
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn bind_image_texture(
+    unit: GLuint,
+    texture: GLuint,
+    _level: GLint,
+    _layered: GLboolean,
+    _layer: GLint,
+    access: GLenum,
+    format: GLenum,
+) {
+    record(GlCall::BindImageTexture {
+        unit,
+        texture,
+        access,
+        format,
+    });
+}
@@ -0,0 +1,68 @@
+//! helpers for tests that need an OpenGL context, either a real one or the `mock_gl` driver
+//!
+//! tests that render are marked `#[ignore]` so a plain `cargo test` works everywhere,
+//! run them with `cargo test -- --ignored` (under `xvfb-run` with mesa llvmpipe on ci,
+//! or set `LEARNRUST_OSMESA=1` when glfw is built with OSMesa)
+
+pub mod golden;
+mod golden_tests;
+pub mod mock_gl;
+
+use crate::graphics::gl_resource::ContextGuard;
+use crate::utils::png_writer;
+use glfw::{fail_on_errors, Context};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+/// glfw and the stb_image flip flag are global, so everything touching them runs one at a time
+static GL_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock() -> MutexGuard<'static, ()> {
+    GL_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// runs `f` with a current context from a hidden `width` x `height` window
+pub fn with_gl_context<T>(width: u32, height: u32, f: impl FnOnce() -> T) -> T {
+    let _guard = lock();
+
+    let mut glfw = glfw::init(fail_on_errors!()).expect("Failed to initialize GLFW.");
+    glfw.window_hint(glfw::WindowHint::Visible(false));
+    if std::env::var_os("LEARNRUST_OSMESA").is_some() {
+        glfw.window_hint(glfw::WindowHint::ContextCreationApi(
+            glfw::ContextCreationApi::OsMesa,
+        ));
+    }
+
+    let (mut window, _events) = glfw
+        .create_window(width, height, "test", glfw::WindowMode::Windowed)
+        .expect("Failed to create GLFW window.");
+    window.make_current();
+    gl::load_with(|s| window.get_proc_address(s) as *const _);
+    let _context = ContextGuard::new();
+
+    unsafe {
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    }
+
+    f()
+}
+
+/// an empty directory in the temp dir for files a test writes, anything left from an earlier
+/// run is removed. `name` has to be unique per test
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("learnrust_{}", name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// writes RGBA `pixels` as a `width` x `height` png into `scratch_dir(name)`, returns its path
+pub fn scratch_png(name: &str, width: u32, height: u32, pixels: &[u8]) -> String {
+    let path = scratch_dir(name).join("image.png");
+    let path = path.to_str().unwrap();
+    png_writer::write_png(path, width, height, pixels).unwrap();
+    path.to_string()
+}
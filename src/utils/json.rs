@@ -0,0 +1,371 @@
+//! just enough json to write and read back our own sidecar files, no serde needed
+
+/// a parsed json value, objects keep the order of their keys
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// the value of `key` if this is an object that has it
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// None for numbers with a fraction or outside the i32 range instead of truncating them
+    pub fn as_i32(&self) -> Option<i32> {
+        let number = self.as_f64()?;
+        let in_range = number >= i32::MIN as f64 && number <= i32::MAX as f64;
+        (in_range && number.fract() == 0.0).then_some(number as i32)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, JsonValue)]> {
+        match self {
+            JsonValue::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// two space indented json, arrays and objects of plain values stay on one line
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, 0);
+        out.push('\n');
+        out
+    }
+
+    fn is_flat(&self) -> bool {
+        match self {
+            JsonValue::Array(values) => values.iter().all(|value| !value.is_nested()),
+            JsonValue::Object(entries) => entries.iter().all(|(_, value)| !value.is_nested()),
+            _ => true,
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, JsonValue::Array(_) | JsonValue::Object(_))
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            JsonValue::Number(number) => out.push_str(&number.to_string()),
+            JsonValue::String(string) => write_string(out, string),
+            JsonValue::Array(values) => {
+                let items: Vec<_> = values.iter().map(|value| (None, value)).collect();
+                write_items(out, indent, ('[', ']'), &items, self.is_flat());
+            }
+            JsonValue::Object(entries) => {
+                let items: Vec<_> = entries
+                    .iter()
+                    .map(|(name, value)| (Some(name.as_str()), value))
+                    .collect();
+                write_items(out, indent, ('{', '}'), &items, self.is_flat());
+            }
+        }
+    }
+}
+
+fn write_items(
+    out: &mut String,
+    indent: usize,
+    (open, close): (char, char),
+    items: &[(Option<&str>, &JsonValue)],
+    flat: bool,
+) {
+    out.push(open);
+    for (i, (name, value)) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if flat {
+            if i > 0 {
+                out.push(' ');
+            }
+        } else {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent + 1));
+        }
+        if let Some(name) = name {
+            write_string(out, name);
+            out.push_str(": ");
+        }
+        value.write(out, indent + 1);
+    }
+    if !flat && !items.is_empty() {
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+    }
+    out.push(close);
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// parses a whole json document, the error says what was expected and at which byte
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        position: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position != parser.bytes.len() {
+        return Err(parser.error("end of input"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, expected: &str) -> String {
+        format!("expected {} at byte {}", expected, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("'{}'", byte as char)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str, value: JsonValue) -> Result<JsonValue, String> {
+        if !self.bytes[self.position..].starts_with(keyword.as_bytes()) {
+            return Err(self.error(keyword));
+        }
+        self.position += keyword.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b't') => self.keyword("true", JsonValue::Bool(true)),
+            Some(b'f') => self.keyword("false", JsonValue::Bool(false)),
+            Some(b'n') => self.keyword("null", JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(JsonValue::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.expect(b':')?;
+            entries.push((name, self.value()?));
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                _ => return Err(self.error("',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                _ => return Err(self.error("',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.bytes.get(self.position) != Some(&b'"') {
+            return Err(self.error("a string"));
+        }
+        self.position += 1;
+        let mut string = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(self.error("a closing '\"'"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.bytes.get(self.position) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'u') => {
+                            let hex = self
+                                .bytes
+                                .get(self.position + 1..self.position + 5)
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or_else(|| self.error("4 hex digits"))?;
+                            self.position += 4;
+                            //surrogate pairs aren't needed for our files
+                            char::from_u32(hex).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error("an escape sequence")),
+                    };
+                    self.position += 1;
+                    string.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte => string.push(byte),
+            }
+        }
+        //the input was a &str and escapes are written as utf-8, so this can't fail
+        Ok(String::from_utf8(string).unwrap())
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') =
+            self.bytes.get(self.position)
+        {
+            self.position += 1;
+        }
+        //the bytes are all ascii
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        text.parse().map(JsonValue::Number).map_err(|_| {
+            self.position = start;
+            self.error("a number")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_json_parses_back() {
+        let value = JsonValue::Object(vec![
+            (
+                "name".to_string(),
+                JsonValue::String("a \"quoted\"\nline".to_string()),
+            ),
+            (
+                "sizes".to_string(),
+                JsonValue::Array(vec![JsonValue::Number(1.0), JsonValue::Number(-2.5)]),
+            ),
+            (
+                "nested".to_string(),
+                JsonValue::Object(vec![("ok".to_string(), JsonValue::Bool(true))]),
+            ),
+            ("nothing".to_string(), JsonValue::Null),
+        ]);
+
+        let text = value.to_pretty_string();
+        assert!(text.contains("\"sizes\": [1, -2.5]"));
+        assert_eq!(parse(&text), Ok(value));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            parse("{\"a\": }"),
+            Err("expected a value at byte 6".to_string())
+        );
+        assert_eq!(
+            parse("[1, 2] x"),
+            Err("expected end of input at byte 7".to_string())
+        );
+        assert_eq!(
+            parse("\"\\u00e9t\\u00e9\"").unwrap().as_str(),
+            Some("\u{e9}t\u{e9}")
+        );
+    }
+
+    #[test]
+    fn only_whole_numbers_in_range_are_i32s() {
+        let numbers: Vec<Option<i32>> = [-7.0, 2.5, 3e9, -3e9, f64::NAN]
+            .iter()
+            .map(|&number| JsonValue::Number(number).as_i32())
+            .collect();
+        assert_eq!(numbers, [Some(-7), None, None, None, None]);
+    }
+}
@@ -1,3 +1,5 @@
 pub mod fps_manager;
+pub mod png_writer;
 pub mod rgb_color;
-pub mod camera;
\ No newline at end of file
+pub mod camera;
+pub mod json;